@@ -25,22 +25,32 @@ fn main() {
     }
 
     println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={OUTPUT_DIR}");
+    println!("cargo::rustc-check-cfg=cfg(embedded_ddi)");
 
-    // Ensure output directory exists
-    if !Path::new(OUTPUT_DIR).exists() {
-        fs::create_dir_all(OUTPUT_DIR).expect("Failed to create DDI directory");
-    }
-
-    // Check if the file already exists
-    if Path::new(OUTPUT_FILES[0]).exists() {
+    // Without the feature the image only comes from the cache at runtime
+    if std::env::var_os("CARGO_FEATURE_EMBED_DDI").is_none() {
         return;
     }
 
-    // Download the file using reqwest
-    println!("Downloading BuildManifest.plist...");
-    for (i, url) in URLS.iter().enumerate() {
-        let response = get(*url).expect("Failed to send request");
-        let bytes = response.bytes().expect("Failed to read response");
-        fs::write(OUTPUT_FILES[i], &bytes).expect("Failed to write file");
+    if !OUTPUT_FILES.iter().all(|x| Path::new(x).exists()) {
+        fs::create_dir_all(OUTPUT_DIR).expect("Failed to create DDI directory");
+        println!("Downloading the developer disk image...");
+        for (url, file) in URLS.iter().zip(OUTPUT_FILES) {
+            if let Err(e) = download(url, file) {
+                // Offline builds still work, the image can be imported at runtime instead
+                println!(
+                    "cargo:warning=Not embedding the developer disk image, failed to download {url}: {e}"
+                );
+                return;
+            }
+        }
     }
+    println!("cargo:rustc-cfg=embedded_ddi");
+}
+
+fn download(url: &str, file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let bytes = get(url)?.error_for_status()?.bytes()?;
+    fs::write(file, &bytes)?;
+    Ok(())
 }
@@ -0,0 +1,624 @@
+// Jackson Coxson
+
+use std::{collections::HashMap, path::PathBuf};
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use futures_util::{Stream, stream::FuturesUnordered};
+use idevice::{
+    IdeviceError, IdeviceService,
+    afc::{AfcClient, errors::AfcError, opcode::AfcFopenMode},
+    installation_proxy::InstallationProxyClient,
+    pairing_file::PairingFile,
+    usbmuxd::{UsbmuxdAddr, UsbmuxdDevice},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, LABEL, container, pairing, settings};
+
+const BUNDLED_APPS: &str = include_str!("../apps.json");
+const USER_APPS_FILE: &str = "apps.json";
+/// How backups are told apart, in their names after the pairing file's name
+const BACKUP_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// An app that accepts a pairing file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupportedApp {
+    /// Also matched against the display name, so renamed copies like "SideStore (1)" are found
+    pub name: String,
+    /// Bundle ID patterns, see [`bundle_id_matches`]
+    #[serde(default)]
+    pub bundle_ids: Vec<String>,
+    /// Where the pairing file goes, relative to the app's Documents folder
+    pub path: String,
+    #[serde(default)]
+    pub format: pairing::Format,
+    /// Whether the app reads the device's UDID from the pairing file, as SideStore does
+    #[serde(default)]
+    pub udid: bool,
+}
+
+impl SupportedApp {
+    /// The name the app expects its pairing file to have, e.g. `ALTPairingFile.mobiledevicepairing`
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    /// The pairing file the way the app reads it, `udid` is added if the app needs it
+    pub fn serialize(&self, pairing_file: &PairingFile, udid: &str) -> Result<Vec<u8>, Error> {
+        if self.udid {
+            pairing::serialize(&pairing::with_udid(pairing_file, udid), self.format)
+        } else {
+            pairing::serialize(pairing_file, self.format)
+        }
+    }
+
+    /// Rewrites any pairing file the way the app reads it, without needing the device
+    ///
+    /// `udid` is only needed when the app wants one and the file doesn't have it.
+    pub fn convert(
+        &self,
+        bytes: &[u8],
+        passphrase: Option<&str>,
+        udid: Option<&str>,
+    ) -> Result<Vec<u8>, Error> {
+        let pairing_file = pairing::decode(bytes, passphrase)?;
+        match pairing_file.udid.as_deref().or(udid) {
+            Some(udid) => self.serialize(&pairing_file, udid),
+            None if self.udid => Err(Error::UdidRequired(self.name.clone())),
+            None => pairing::serialize(&pairing_file, self.format),
+        }
+    }
+
+    /// Where the pairing file goes on the device, in the container house_arrest vends
+    pub fn device_path(&self) -> String {
+        format!("{}/{}", container::DOCUMENTS, self.path)
+    }
+}
+
+/// The apps pairing files can be installed into, bundled ones overridden or extended by the user's
+#[derive(Debug, Clone)]
+pub struct AppRegistry {
+    bundled: Vec<SupportedApp>,
+    user: Vec<SupportedApp>,
+    /// Where the user's apps are saved, None if there's no config directory
+    user_path: Option<PathBuf>,
+}
+
+impl AppRegistry {
+    /// The bundled apps plus the user's from the app's config directory
+    pub fn load() -> Self {
+        Self::load_from(settings::config_dir().map(|x| x.join(USER_APPS_FILE)))
+    }
+
+    /// The bundled apps plus the user's kept at `user_path`, which doesn't have to exist yet
+    pub fn load_from(user_path: Option<PathBuf>) -> Self {
+        let bundled = serde_json::from_str(BUNDLED_APPS).expect("bundled apps.json is invalid");
+        let user = match &user_path {
+            Some(path) => match std::fs::read(path) {
+                Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                    warn!("Ignoring unreadable apps at {}: {e}", path.display());
+                    Vec::new()
+                }),
+                Err(_) => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+        Self {
+            bundled,
+            user,
+            user_path,
+        }
+    }
+
+    /// Every app by name, with the user's entries in place of bundled ones of the same name
+    pub fn apps(&self) -> Vec<&SupportedApp> {
+        let mut apps: Vec<&SupportedApp> = self
+            .bundled
+            .iter()
+            .filter(|x| !self.is_user(&x.name))
+            .chain(self.user.iter())
+            .collect();
+        apps.sort_by_key(|x| x.name.to_lowercase());
+        apps
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SupportedApp> {
+        self.user
+            .iter()
+            .chain(self.bundled.iter())
+            .find(|x| x.name == name)
+    }
+
+    /// Whether the app was added or changed by the user
+    pub fn is_user(&self, name: &str) -> bool {
+        self.user.iter().any(|x| x.name == name)
+    }
+
+    pub fn is_bundled(&self, name: &str) -> bool {
+        self.bundled.iter().any(|x| x.name == name)
+    }
+
+    /// Adds or replaces one of the user's apps and saves them
+    pub fn set_user(&mut self, app: SupportedApp) -> Result<(), Error> {
+        match self.user.iter_mut().find(|x| x.name == app.name) {
+            Some(existing) => *existing = app,
+            None => self.user.push(app),
+        }
+        self.save()
+    }
+
+    /// Removes one of the user's apps, which brings back the bundled one if it was overridden
+    pub fn remove_user(&mut self, name: &str) -> Result<(), Error> {
+        self.user.retain(|x| x.name != name);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let path = self.user_path.as_ref().ok_or(Error::NoConfigDir)?;
+        settings::write_file(path, &serde_json::to_vec_pretty(&self.user).unwrap())
+    }
+}
+
+/// A user app on the device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstalledApp {
+    pub bundle_id: String,
+    /// CFBundleDisplayName, or CFBundleName when the app has no display name
+    pub display_name: Option<String>,
+}
+
+/// Whether `bundle_id` is the one `pattern` describes
+///
+/// `*` in a pattern matches anything. A pattern without one also matches the
+/// bundle ID with a single segment added before or after it, which is how
+/// re-signing tools make them unique, e.g. `com.SideStore.SideStore.ABCDE12345`
+/// or `ABCDE12345.com.SideStore.SideStore`. Case is ignored.
+pub fn bundle_id_matches(pattern: &str, bundle_id: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let bundle_id = bundle_id.to_ascii_lowercase();
+    if pattern.contains('*') {
+        return glob_matches(&pattern, &bundle_id);
+    }
+    if pattern == bundle_id {
+        return true;
+    }
+    let single_segment = |x: &str| !x.is_empty() && !x.contains('.');
+    bundle_id
+        .strip_prefix(&pattern)
+        .and_then(|x| x.strip_prefix('.'))
+        .is_some_and(single_segment)
+        || bundle_id
+            .strip_suffix(&pattern)
+            .and_then(|x| x.strip_suffix('.'))
+            .is_some_and(single_segment)
+}
+
+fn glob_matches(pattern: &str, s: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // There's always a first part, empty if the pattern starts with *
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = s.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+/// Whether the display name is `name`, or a copy of it like "SideStore (1)"
+fn display_name_matches(name: &str, display_name: &str) -> bool {
+    let display_name = display_name.trim();
+    let base = match display_name.rsplit_once(" (") {
+        Some((base, n))
+            if n.strip_suffix(')')
+                .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit())) =>
+        {
+            base
+        }
+        _ => display_name,
+    };
+    base.eq_ignore_ascii_case(name)
+}
+
+/// Every installed app that could be each of `apps`, by app name
+///
+/// Apps with no candidates are left out.
+pub fn match_apps(
+    apps: &[SupportedApp],
+    installed: &[InstalledApp],
+) -> HashMap<String, Vec<InstalledApp>> {
+    let mut matched = HashMap::new();
+    for app in apps {
+        let mut candidates: Vec<InstalledApp> = installed
+            .iter()
+            .filter(|x| {
+                app.bundle_ids
+                    .iter()
+                    .any(|p| bundle_id_matches(p, &x.bundle_id))
+                    || x.display_name
+                        .as_deref()
+                        .is_some_and(|n| display_name_matches(&app.name, n))
+            })
+            .cloned()
+            .collect();
+        if !candidates.is_empty() {
+            candidates.sort_by(|a, b| a.bundle_id.cmp(&b.bundle_id));
+            matched.insert(app.name.clone(), candidates);
+        }
+    }
+    matched
+}
+
+/// Finds the installed apps that could be each of `apps`, see [`match_apps`]
+pub async fn installed_apps(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    apps: &[SupportedApp],
+) -> Result<HashMap<String, Vec<InstalledApp>>, Error> {
+    let p = dev.to_provider(addr.clone(), LABEL);
+    let mut ic = InstallationProxyClient::connect(&p).await?;
+    let installed: Vec<InstalledApp> = ic
+        .get_apps(Some("User"), None)
+        .await?
+        .into_iter()
+        .map(|(bundle_id, info)| {
+            let info = info.as_dictionary();
+            let display_name = ["CFBundleDisplayName", "CFBundleName"]
+                .into_iter()
+                .find_map(|k| info.and_then(|x| x.get(k)).and_then(|x| x.as_string()))
+                .map(|x| x.to_string());
+            InstalledApp {
+                bundle_id,
+                display_name,
+            }
+        })
+        .collect();
+    Ok(match_apps(apps, &installed))
+}
+
+/// A step of [`install_pairing_file`], for saying which one failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallStep {
+    /// Making the folders the pairing file goes in, when the app hasn't yet
+    CreateFolder,
+    /// Reading the pairing file the app already has
+    Read,
+    /// Copying that file aside before it's replaced
+    Backup,
+    Write,
+    /// Reading the written file back to compare it
+    Verify,
+    /// Moving a backup back in place
+    Restore,
+}
+
+impl std::fmt::Display for InstallStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            InstallStep::CreateFolder => "create the folder",
+            InstallStep::Read => "read",
+            InstallStep::Backup => "back up",
+            InstallStep::Write => "write",
+            InstallStep::Verify => "verify",
+            InstallStep::Restore => "restore",
+        })
+    }
+}
+
+/// What [`install_pairing_file`] or [`restore_pairing_file`] did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstallReport {
+    /// Where the pairing file now is on the device
+    pub path: String,
+    /// Where the app's previous pairing file was copied to, None if it had none or it was the same
+    pub backup: Option<String>,
+}
+
+/// An installed copy of a supported app
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallTarget {
+    pub app: SupportedApp,
+    pub bundle_id: String,
+}
+
+/// Every candidate for each of `apps` in `installed`, as returned by [`installed_apps`]
+pub fn install_targets(
+    apps: &[SupportedApp],
+    installed: &HashMap<String, Vec<InstalledApp>>,
+) -> Vec<InstallTarget> {
+    apps.iter()
+        .flat_map(|app| {
+            installed
+                .get(&app.name)
+                .into_iter()
+                .flatten()
+                .map(|x| InstallTarget {
+                    app: app.clone(),
+                    bundle_id: x.bundle_id.clone(),
+                })
+        })
+        .collect()
+}
+
+/// Installs the pairing file into every target at once, see [`install_pairing_file`]
+///
+/// Each result is yielded as soon as its install finishes, so progress can be shown.
+pub fn install_all<'a>(
+    addr: &'a UsbmuxdAddr,
+    dev: &'a UsbmuxdDevice,
+    targets: Vec<InstallTarget>,
+    pairing_file: &'a PairingFile,
+) -> impl Stream<Item = (InstallTarget, Result<InstallReport, Error>)> + 'a {
+    targets
+        .into_iter()
+        .map(|target| async move {
+            let res =
+                install_pairing_file(addr, dev, &target.bundle_id, &target.app, pairing_file).await;
+            (target, res)
+        })
+        .collect::<FuturesUnordered<_>>()
+}
+
+/// Writes the pairing file where `app` expects it inside its Documents folder
+///
+/// A different file already there is first copied next to it with a timestamp
+/// added to its name. Both files are read back after writing, so a success
+/// means the app has exactly the new pairing file.
+pub async fn install_pairing_file(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    bundle_id: &str,
+    app: &SupportedApp,
+    pairing_file: &PairingFile,
+) -> Result<InstallReport, Error> {
+    let bytes = app.serialize(pairing_file, &dev.udid)?;
+
+    let mut ac = container::vend_documents(addr, dev, bundle_id).await?;
+    let path = app.device_path();
+    create_folders(&mut ac, bundle_id, &path).await?;
+    let existing = read_file(&mut ac, &path)
+        .await
+        .map_err(install_error(InstallStep::Read, &path))?;
+    let backup = match existing {
+        Some(existing) if existing != bytes => {
+            let backup = free_backup_path(&mut ac, &path)
+                .await
+                .map_err(install_error(InstallStep::Backup, &path))?;
+            write_verified(&mut ac, &backup, &existing, InstallStep::Backup).await?;
+            Some(backup)
+        }
+        _ => None,
+    };
+    write_verified(&mut ac, &path, &bytes, InstallStep::Write).await?;
+
+    Ok(InstallReport { path, backup })
+}
+
+/// A copy of an app's pairing file, kept when it was replaced or removed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Backup {
+    /// Where the copy is on the device
+    pub path: String,
+    pub taken: DateTime<Utc>,
+    pub size: usize,
+}
+
+/// The backups of the app's pairing file, newest first
+pub async fn list_backups(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    bundle_id: &str,
+    app: &SupportedApp,
+) -> Result<Vec<Backup>, Error> {
+    let mut ac = container::vend_documents(addr, dev, bundle_id).await?;
+    backups(&mut ac, &app.device_path()).await
+}
+
+/// Moves the app's pairing file aside as a backup, returning where it went
+///
+/// Returns None if the app has no pairing file.
+pub async fn remove_pairing_file(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    bundle_id: &str,
+    app: &SupportedApp,
+) -> Result<Option<String>, Error> {
+    let mut ac = container::vend_documents(addr, dev, bundle_id).await?;
+    move_aside(&mut ac, &app.device_path()).await
+}
+
+/// Puts a backup back in place of the app's pairing file, the newest if `backup` is None
+///
+/// The file it replaces is kept as a backup in turn, so a restore can be undone.
+pub async fn restore_pairing_file(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    bundle_id: &str,
+    app: &SupportedApp,
+    backup: Option<&str>,
+) -> Result<InstallReport, Error> {
+    let mut ac = container::vend_documents(addr, dev, bundle_id).await?;
+    let path = app.device_path();
+    let restored = match backup {
+        Some(backup) if backup_time(&path, backup).is_some() => {
+            // Checked first so the current file isn't moved aside for nothing
+            match ac.get_file_info(backup).await {
+                Ok(_) => backup.to_string(),
+                Err(IdeviceError::Afc(AfcError::ObjectNotFound)) => {
+                    return Err(Error::MissingBackup(backup.to_string()));
+                }
+                Err(e) => return Err(install_error(InstallStep::Restore, backup)(e)),
+            }
+        }
+        Some(backup) => return Err(Error::NotABackup(backup.to_string())),
+        None => {
+            backups(&mut ac, &path)
+                .await?
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::NoBackup(path.clone()))?
+                .path
+        }
+    };
+
+    let backup = move_aside(&mut ac, &path).await?;
+    if let Err(e) = ac.rename(&restored, &path).await {
+        // Put the file back rather than leave the app without one
+        if let Some(backup) = &backup
+            && let Err(e) = ac.rename(backup, &path).await
+        {
+            warn!("Failed to put {backup} back at {path}: {e}");
+        }
+        return Err(install_error(InstallStep::Restore, &restored)(e));
+    }
+    Ok(InstallReport { path, backup })
+}
+
+async fn backups(ac: &mut AfcClient, path: &str) -> Result<Vec<Backup>, Error> {
+    let (dir, _) = path.rsplit_once('/').unwrap_or_default();
+    let names = match ac.list_dir(dir).await {
+        Ok(names) => names,
+        Err(IdeviceError::Afc(AfcError::ObjectNotFound)) => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut backups = Vec::new();
+    for name in names {
+        let backup = format!("{dir}/{name}");
+        if let Some(taken) = backup_time(path, &backup) {
+            let size = ac.get_file_info(&backup).await?.size;
+            backups.push(Backup {
+                path: backup,
+                taken,
+                size,
+            });
+        }
+    }
+    backups.sort_by_key(|x| std::cmp::Reverse(x.taken));
+    Ok(backups)
+}
+
+/// Renames `path` to a new backup, None if it doesn't exist
+async fn move_aside(ac: &mut AfcClient, path: &str) -> Result<Option<String>, Error> {
+    let res = async {
+        let backup = free_backup_path(ac, path).await?;
+        ac.rename(path, &backup).await.map(|_| backup)
+    }
+    .await;
+    match res {
+        Ok(backup) => Ok(Some(backup)),
+        Err(IdeviceError::Afc(AfcError::ObjectNotFound)) => Ok(None),
+        Err(e) => Err(install_error(InstallStep::Backup, path)(e)),
+    }
+}
+
+/// Makes the folders `path` is in below Documents, like LiveContainer's `SideStore/Documents`
+async fn create_folders(ac: &mut AfcClient, bundle_id: &str, path: &str) -> Result<(), Error> {
+    let (dir, _) = path.rsplit_once('/').unwrap_or_default();
+    let mut folder = String::new();
+    for name in dir.split('/').filter(|x| !x.is_empty()) {
+        folder = format!("{folder}/{name}");
+        match ac.get_file_info(&folder).await {
+            Ok(info) if info.st_ifmt == "S_IFDIR" => continue,
+            Ok(_) => return Err(Error::NotAFolder(folder)),
+            Err(IdeviceError::Afc(AfcError::ObjectNotFound)) => {}
+            Err(e) => return Err(install_error(InstallStep::CreateFolder, &folder)(e)),
+        }
+        // house_arrest vends Documents with the container, one that lacks it isn't laid out as expected
+        if folder == container::DOCUMENTS {
+            return Err(Error::NoDocuments(bundle_id.to_string()));
+        }
+        ac.mk_dir(&folder)
+            .await
+            .map_err(install_error(InstallStep::CreateFolder, &folder))?;
+    }
+    Ok(())
+}
+
+/// A backup name for `path` that isn't taken yet
+async fn free_backup_path(ac: &mut AfcClient, path: &str) -> Result<String, IdeviceError> {
+    // Backups are named by the second, so one made just before would be overwritten
+    let mut at = Utc::now();
+    loop {
+        let backup = backup_path(path, at);
+        match ac.get_file_info(&backup).await {
+            Err(IdeviceError::Afc(AfcError::ObjectNotFound)) => return Ok(backup),
+            Err(e) => return Err(e),
+            Ok(_) => at += TimeDelta::seconds(1),
+        }
+    }
+}
+
+/// Where a copy of `path` made at `at` is kept, e.g. `pairingFile.plist.20260101T120000Z.bak`
+fn backup_path(path: &str, at: DateTime<Utc>) -> String {
+    format!("{path}.{}.bak", at.format(BACKUP_TIME_FORMAT))
+}
+
+/// When `backup` was made, None if it isn't a backup of `path`
+fn backup_time(path: &str, backup: &str) -> Option<DateTime<Utc>> {
+    let time = backup
+        .strip_prefix(path)?
+        .strip_prefix('.')?
+        .strip_suffix(".bak")?;
+    NaiveDateTime::parse_from_str(time, BACKUP_TIME_FORMAT)
+        .ok()
+        .map(|x| x.and_utc())
+}
+
+fn install_error(step: InstallStep, path: &str) -> impl FnOnce(IdeviceError) -> Error + '_ {
+    move |source| Error::Install {
+        step,
+        path: path.to_string(),
+        source,
+    }
+}
+
+/// Reads a whole file, None if it doesn't exist
+async fn read_file(ac: &mut AfcClient, path: &str) -> Result<Option<Vec<u8>>, IdeviceError> {
+    let mut f = match ac.open(path, AfcFopenMode::RdOnly).await {
+        Ok(f) => f,
+        Err(IdeviceError::Afc(AfcError::ObjectNotFound)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let bytes = f.read_entire().await?;
+    f.close().await?;
+    Ok(Some(bytes))
+}
+
+/// Replaces a file with `bytes` and checks that reading it back gives the same bytes
+async fn write_verified(
+    ac: &mut AfcClient,
+    path: &str,
+    bytes: &[u8],
+    step: InstallStep,
+) -> Result<(), Error> {
+    async {
+        let mut f = ac.open(path, AfcFopenMode::Wr).await?;
+        f.write_entire(bytes).await?;
+        f.close().await
+    }
+    .await
+    .map_err(install_error(step, path))?;
+
+    let written = read_file(ac, path)
+        .await
+        .map_err(install_error(InstallStep::Verify, path))?
+        .unwrap_or_default();
+    if written != bytes {
+        return Err(Error::InstallMismatch {
+            path: path.to_string(),
+            expected: bytes.len(),
+            actual: written.len(),
+        });
+    }
+    Ok(())
+}
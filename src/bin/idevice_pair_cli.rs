@@ -0,0 +1,1268 @@
+// Jackson Coxson
+//! Headless front end for the idevice_pair workflows, for build agents and
+//! provisioning scripts that have no display.
+
+use std::{net::IpAddr, path::PathBuf, process::ExitCode, time::Duration};
+
+use clap::{Args, Parser, Subcommand};
+use futures_util::StreamExt;
+use idevice::{
+    IdeviceError,
+    pairing_file::PairingFile,
+    usbmuxd::{UsbmuxdAddr, UsbmuxdDevice},
+};
+use idevice_pair::{
+    Error,
+    apps::{self, SupportedApp},
+    container,
+    ddi::{self, DdiCache},
+    device::{self, Device},
+    discover,
+    inspect::{self, CertificateSummary},
+    mount, pairing, settings,
+    vault::{self, Vault, VaultEntry},
+};
+use log::warn;
+use serde_json::{Value, json};
+
+// Exit codes are part of the interface scripts rely on, don't renumber them.
+// 2 is used by clap for usage errors.
+const EXIT_FAILURE: u8 = 1;
+const EXIT_NO_USBMUXD: u8 = 3;
+const EXIT_NO_DEVICE: u8 = 4;
+const EXIT_VALIDATION_FAILED: u8 = 5;
+const EXIT_INSTALL_FAILED: u8 = 6;
+
+/// Passphrase for encrypted pairing files when --passphrase-file isn't given
+const PASSPHRASE_VAR: &str = "IDEVICE_PAIR_PASSPHRASE";
+
+#[derive(Parser)]
+#[command(version, about = "Manage iOS pairing files without the GUI")]
+struct Cli {
+    /// Print results as JSON instead of human readable text
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// UDID or name of the device to use, required when several are connected
+    #[arg(short, long, global = true)]
+    device: Option<String>,
+
+    /// usbmuxd socket path or host:port, defaults to the app's setting, $USBMUXD_SOCKET_ADDRESS or the platform default
+    #[arg(long, global = true, value_name = "ADDRESS")]
+    usbmuxd: Option<String>,
+
+    /// File holding the passphrase for encrypted pairing files, instead of $IDEVICE_PAIR_PASSPHRASE
+    #[arg(long, global = true, value_name = "PATH")]
+    passphrase_file: Option<PathBuf>,
+
+    /// Keep loaded and generated pairing files in the vault
+    #[arg(long, global = true)]
+    vault: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List connected devices
+    List,
+    /// Show device details and Developer Mode status
+    Info,
+    /// Load the pairing file usbmuxd holds for the device
+    Load {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Pair with the device again to create a new pairing file
+    #[cfg(feature = "generate")]
+    Generate {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Show what a pairing file contains and check its certificates
+    Inspect {
+        /// Pairing file to inspect
+        pairing_file: PathBuf,
+    },
+    /// Convert a pairing file to another format or app's layout, without a device
+    Convert {
+        /// Pairing file to convert, as an XML, binary or encrypted plist
+        input: PathBuf,
+        /// Where to write the converted file
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Plist format to write, xml or binary
+        #[arg(long, conflicts_with = "for_app")]
+        format: Option<pairing::Format>,
+        /// Write the file the way a supported app reads it, e.g. StikDebug
+        #[arg(long, value_name = "APP")]
+        for_app: Option<String>,
+        /// UDID to add when the pairing file doesn't have one
+        #[arg(long)]
+        udid: Option<String>,
+    },
+    /// Check that a pairing file works over the network
+    Validate {
+        /// Pairing file to validate
+        pairing_file: PathBuf,
+        /// Device IP address. When omitted, the address usbmuxd has for a network --device is used,
+        /// otherwise the device is searched for over mDNS
+        #[arg(long)]
+        ip: Option<IpAddr>,
+        /// Seconds to search for the device over mDNS
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+    },
+    /// List the apps pairing files can be installed into
+    Apps,
+    /// Install a pairing file into a supported app
+    Install {
+        #[command(flatten)]
+        target: AppArgs,
+        /// Pairing file to install, loaded from the device when omitted
+        #[arg(long)]
+        pairing_file: Option<PathBuf>,
+    },
+    /// Install a pairing file into every supported app on the device at once
+    InstallAll {
+        /// Pairing file to install, loaded from the device when omitted
+        #[arg(long)]
+        pairing_file: Option<PathBuf>,
+    },
+    /// List the backups of an app's pairing file kept on the device
+    Backups {
+        #[command(flatten)]
+        target: AppArgs,
+    },
+    /// Remove the pairing file from a supported app, keeping it as a backup
+    Remove {
+        #[command(flatten)]
+        target: AppArgs,
+    },
+    /// Put a backup back in place of an app's pairing file
+    Restore {
+        #[command(flatten)]
+        target: AppArgs,
+        /// Path of the backup on the device, as listed by `backups`. Defaults to the newest
+        #[arg(long)]
+        backup: Option<String>,
+    },
+    /// List a directory in an app's container
+    Files {
+        #[command(flatten)]
+        target: AppArgs,
+        /// Directory to list
+        #[arg(default_value = container::DOCUMENTS)]
+        dir: String,
+    },
+    /// Copy a file out of an app's container, or preview it when it's a plist
+    Download {
+        #[command(flatten)]
+        target: AppArgs,
+        /// Path of the file in the container, as listed by `files`
+        path: String,
+        /// Write the file here instead of previewing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Mount the developer disk image
+    Mount,
+    /// List the disk images mounted on the device
+    Mounted,
+    /// Unmount a mounted image, or every one when no path is given
+    Unmount {
+        /// Where the image is mounted, as listed by `mounted`
+        mount_path: Option<String>,
+    },
+    /// Unmount the mounted images and mount the one for the device's iOS version
+    Remount,
+    /// Manage the developer disk images kept in the local cache
+    Ddi {
+        #[command(subcommand)]
+        action: DdiAction,
+    },
+    /// Manage wireless debugging
+    Wireless {
+        #[command(subcommand)]
+        action: WirelessAction,
+    },
+    /// Browse the pairing files kept in the local vault
+    Vault {
+        #[command(subcommand)]
+        action: VaultAction,
+    },
+}
+
+/// Where a command that produces a pairing file writes it
+#[derive(Args)]
+struct OutputArgs {
+    /// Write the pairing file here instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Print the private keys instead of redacting them on stdout
+    #[arg(long)]
+    show_keys: bool,
+    /// Encrypt the written file with the passphrase from --passphrase-file or $IDEVICE_PAIR_PASSPHRASE
+    #[arg(long, requires = "output")]
+    encrypt: bool,
+    /// Plist format of the written file, xml or binary
+    #[arg(long, requires = "output", conflicts_with_all = ["encrypt", "for_app"])]
+    format: Option<pairing::Format>,
+    /// Add the device's UDID when the pairing file doesn't have it
+    #[arg(long, conflicts_with = "encrypt")]
+    udid: bool,
+    /// Write the file the way a supported app reads it, with its format and UDID
+    #[arg(
+        long,
+        value_name = "APP",
+        requires = "output",
+        conflicts_with = "encrypt"
+    )]
+    for_app: Option<String>,
+}
+
+/// Which installed app a command works on
+#[derive(Args)]
+struct AppArgs {
+    /// Name of the app, e.g. StikDebug
+    #[arg(long)]
+    app: String,
+    /// Bundle ID of the copy to use, needed when several installed apps match
+    #[arg(long)]
+    bundle_id: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum WirelessAction {
+    /// Show whether wireless debugging is on
+    Status,
+    /// Enable wireless debugging
+    Enable,
+    /// Disable wireless debugging
+    Disable,
+}
+
+#[derive(Subcommand)]
+enum DdiAction {
+    /// List the cached images
+    List,
+    /// Import images from a folder, .zip, .tar or .tar.gz, e.g. a copy of Xcode's DeveloperDiskImages
+    Import { path: PathBuf },
+    /// Check that the cached images' files are unchanged since they were imported
+    Check,
+    /// Delete a cached image, by the name `list` shows
+    Remove { image: String },
+}
+
+#[derive(Subcommand)]
+enum VaultAction {
+    /// List stored pairing files, newest first
+    List {
+        /// Only list pairing files for this UDID or HostID
+        query: Option<String>,
+    },
+    /// Show the history of the newest pairing file matching a UDID or HostID
+    Show { query: String },
+    /// Write out the newest pairing file matching a UDID or HostID
+    Export {
+        query: String,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Delete a pairing file from the vault
+    Remove { host_id: String },
+}
+
+/// What a successful command prints, in both output modes
+struct Report {
+    human: String,
+    json: Value,
+}
+
+/// A failed command, with the exit code it maps to
+struct Failure {
+    code: u8,
+    message: String,
+    /// What did succeed, for commands that do several things
+    results: Option<Value>,
+}
+
+impl Failure {
+    fn new(code: u8, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            results: None,
+        }
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(e: Error) -> Self {
+        let code = match &e {
+            Error::NoUsbmuxd(_) | Error::InvalidUsbmuxdAddress { .. } => EXIT_NO_USBMUXD,
+            Error::Idevice(IdeviceError::DeviceNotFound) => EXIT_NO_DEVICE,
+            _ => EXIT_FAILURE,
+        };
+
+        // Include the sources, the top level message alone is rarely enough on a build log
+        let mut message = e.to_string();
+        let mut source = std::error::Error::source(&e);
+        while let Some(s) = source {
+            message.push_str(&format!(": {s}"));
+            source = s.source();
+        }
+        Self {
+            code,
+            message,
+            results: None,
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    let cli = Cli::parse();
+
+    match run(&cli).await {
+        Ok(report) => {
+            if cli.json {
+                println!("{:#}", report.json);
+            } else {
+                println!("{}", report.human);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(failure) => {
+            if cli.json {
+                let mut out = json!({ "error": failure.message, "code": failure.code });
+                if let Some(results) = failure.results {
+                    out["results"] = results;
+                }
+                println!("{out:#}");
+            } else {
+                eprintln!("Error: {}", failure.message);
+            }
+            ExitCode::from(failure.code)
+        }
+    }
+}
+
+async fn run(cli: &Cli) -> Result<Report, Failure> {
+    match &cli.command {
+        Command::List => {
+            let devices = device::get_devices(&usbmuxd_addr(cli)?).await?;
+            let mut human = Vec::new();
+            let mut list = Vec::new();
+            for (udid, dev) in devices {
+                human.push(format!("{udid}\t{}\t{}", dev.model, dev.name));
+                list.push(json!({
+                    "name": dev.name,
+                    "model": dev.model,
+                    "udid": udid,
+                    "connection": format!("{:?}", dev.usbmuxd.connection_type),
+                }));
+            }
+            if human.is_empty() {
+                human.push("No devices connected".to_string());
+            }
+            Ok(Report {
+                human: human.join("\n"),
+                json: Value::Array(list),
+            })
+        }
+        Command::Info => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let info = device::device_info(&addr, &dev).await?;
+            let dev_mode = device::dev_mode_status(&addr, &dev).await;
+
+            let mut human: Vec<String> = info.iter().map(|(k, v)| format!("{k}: {v}")).collect();
+            human.push(match &dev_mode {
+                Ok(true) => "Developer Mode: Enabled".to_string(),
+                Ok(false) => "Developer Mode: Disabled".to_string(),
+                Err(e) => format!("Developer Mode: Failed: {e}"),
+            });
+
+            let info: serde_json::Map<String, Value> = info
+                .into_iter()
+                .map(|(k, v)| (k, Value::String(v)))
+                .collect();
+            Ok(Report {
+                human: human.join("\n"),
+                json: json!({
+                    "udid": dev.udid,
+                    "info": info,
+                    "developer_mode": dev_mode.ok(),
+                }),
+            })
+        }
+        Command::Load { output } => {
+            let addr = usbmuxd_addr(cli)?;
+            let device = select_device(&addr, cli.device.as_deref()).await?;
+            let dev = device.usbmuxd;
+            let pairing_file = pairing::load(&addr, &dev).await?;
+            if cli.vault {
+                remember(|v| {
+                    v.add(&pairing_file, vault::Source::Loaded, Some(device.name))
+                        .map(|_| ())
+                });
+            }
+            write_pairing_file(cli, pairing_file, output, Some(&dev.udid))
+        }
+        #[cfg(feature = "generate")]
+        Command::Generate { output } => {
+            let addr = usbmuxd_addr(cli)?;
+            let device = select_device(&addr, cli.device.as_deref()).await?;
+            let dev = device.usbmuxd;
+            let pairing_file = pairing::generate(&addr, &dev).await?;
+            if cli.vault {
+                remember(|v| {
+                    v.add(&pairing_file, vault::Source::Generated, Some(device.name))
+                        .map(|_| ())
+                });
+            }
+            write_pairing_file(cli, pairing_file, output, Some(&dev.udid))
+        }
+        Command::Convert {
+            input,
+            output,
+            format,
+            for_app,
+            udid,
+        } => {
+            let bytes = std::fs::read(input).map_err(|source| Error::Io {
+                path: input.clone(),
+                source,
+            })?;
+            let passphrase = passphrase(cli)?;
+            let (converted, target) = match for_app {
+                Some(name) => {
+                    let app = apps::AppRegistry::load()
+                        .get(name)
+                        .cloned()
+                        .ok_or_else(|| {
+                            Failure::new(EXIT_FAILURE, format!("{name} is not a supported app"))
+                        })?;
+                    let converted = app.convert(&bytes, passphrase.as_deref(), udid.as_deref())?;
+                    (converted, format!("{name}'s {} plist", app.format))
+                }
+                None => {
+                    let format = format.unwrap_or_default();
+                    let converted =
+                        pairing::convert(&bytes, passphrase.as_deref(), format, udid.as_deref())?;
+                    (converted, format!("a {format} plist"))
+                }
+            };
+            pairing::write_to_file(output, &converted)?;
+            let udid = pairing::from_bytes(&converted)?.udid;
+            Ok(Report {
+                human: format!(
+                    "Converted {} to {target} at {}",
+                    input.display(),
+                    output.display()
+                ),
+                json: json!({
+                    "input": input,
+                    "output": output,
+                    "app": for_app,
+                    "udid": udid,
+                }),
+            })
+        }
+        Command::Inspect { pairing_file } => {
+            let report = inspect::inspect(&pairing::read_from_file(
+                pairing_file,
+                passphrase(cli)?.as_deref(),
+            )?);
+
+            let mut human = vec![
+                format!("HostID: {}", report.host_id),
+                format!("SystemBUID: {}", report.system_buid),
+                format!("WiFiMACAddress: {}", report.wifi_mac_address),
+                format!("UDID: {}", report.udid.as_deref().unwrap_or("(missing)")),
+                format!(
+                    "EscrowBag: {}",
+                    if report.has_escrow_bag {
+                        "present"
+                    } else {
+                        "missing"
+                    }
+                ),
+            ];
+            for (name, cert) in [
+                ("DeviceCertificate", &report.device_certificate),
+                ("HostCertificate", &report.host_certificate),
+                ("RootCertificate", &report.root_certificate),
+            ] {
+                human.push(format!("{name}:"));
+                match cert {
+                    Some(c) => human.extend(describe_certificate(c)),
+                    None => human.push("  (unreadable)".to_string()),
+                }
+            }
+            if !report.warnings.is_empty() {
+                human.push("Warnings:".to_string());
+                human.extend(report.warnings.iter().map(|w| format!("  - {w}")));
+            }
+
+            Ok(Report {
+                human: human.join("\n"),
+                json: serde_json::to_value(&report).unwrap_or_default(),
+            })
+        }
+        Command::Validate {
+            pairing_file,
+            ip,
+            timeout,
+        } => {
+            let pairing_file = pairing::read_from_file(pairing_file, passphrase(cli)?.as_deref())?;
+            // A device usbmuxd reaches over the network already has a known address
+            let network_address = match &cli.device {
+                Some(s) if ip.is_none() => select_device(&usbmuxd_addr(cli)?, Some(s))
+                    .await?
+                    .network_address(),
+                _ => None,
+            };
+            let ip = match ip.or(network_address) {
+                Some(ip) => ip,
+                None => discover::find_device(
+                    &pairing_file.wifi_mac_address,
+                    Duration::from_secs(*timeout),
+                )
+                .await?
+                .ok_or_else(|| {
+                    Failure::new(
+                        EXIT_NO_DEVICE,
+                        format!(
+                            "{} was not found on the network, pass --ip",
+                            pairing_file.wifi_mac_address
+                        ),
+                    )
+                })?,
+            };
+
+            let res = pairing::validate(ip, &pairing_file).await;
+            remember(|v| v.record_validation(&pairing_file.host_id, Some(ip), &res));
+            res.map_err(|e| Failure {
+                code: EXIT_VALIDATION_FAILED,
+                ..e.into()
+            })?;
+            Ok(Report {
+                human: format!("Pairing file is valid for {ip}"),
+                json: json!({ "valid": true, "ip": ip }),
+            })
+        }
+        Command::Apps => {
+            let registry = apps::AppRegistry::load();
+            let apps = registry.apps();
+            let human = apps
+                .iter()
+                .map(|x| {
+                    format!(
+                        "{}\t{}\t{}{}\t{}",
+                        x.name,
+                        x.path,
+                        x.format,
+                        if x.udid { "+udid" } else { "" },
+                        x.bundle_ids.join(",")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            Ok(Report {
+                human,
+                json: serde_json::to_value(apps).unwrap_or_default(),
+            })
+        }
+        Command::Install {
+            target,
+            pairing_file,
+        } => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let (supported, bundle_id) = select_app(&addr, &dev, target).await?;
+            let app = &supported.name;
+            let pairing_file = match pairing_file {
+                Some(p) => pairing::read_from_file(p, passphrase(cli)?.as_deref())?,
+                None => pairing::load(&addr, &dev).await?,
+            };
+
+            let installed =
+                apps::install_pairing_file(&addr, &dev, &bundle_id, &supported, &pairing_file)
+                    .await?;
+            remember(|v| v.record_install(&pairing_file.host_id, app, Some(&bundle_id)));
+            let mut human = format!("Installed and verified pairing file in {app} ({bundle_id})");
+            if let Some(backup) = &installed.backup {
+                human.push_str(&format!("\nPrevious pairing file kept at {backup}"));
+            }
+            Ok(Report {
+                human,
+                json: json!({
+                    "app": app,
+                    "bundle_id": bundle_id,
+                    "path": installed.path,
+                    "backup": installed.backup,
+                    "verified": true,
+                }),
+            })
+        }
+        Command::InstallAll { pairing_file } => {
+            let supported: Vec<SupportedApp> = apps::AppRegistry::load()
+                .apps()
+                .into_iter()
+                .cloned()
+                .collect();
+
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let pairing_file = match pairing_file {
+                Some(p) => pairing::read_from_file(p, passphrase(cli)?.as_deref())?,
+                None => pairing::load(&addr, &dev).await?,
+            };
+
+            let installed = apps::installed_apps(&addr, &dev, &supported).await?;
+            let targets = apps::install_targets(&supported, &installed);
+            if targets.is_empty() {
+                return Err(Failure::new(
+                    EXIT_FAILURE,
+                    "None of the supported apps are installed on the device",
+                ));
+            }
+
+            let total = targets.len();
+            let mut results = Vec::new();
+            let mut failed = 0;
+            let mut installs = apps::install_all(&addr, &dev, targets, &pairing_file);
+            while let Some((target, res)) = installs.next().await {
+                let (app, bundle_id) = (&target.app.name, &target.bundle_id);
+                let line = match res {
+                    Ok(installed) => {
+                        remember(|v| v.record_install(&pairing_file.host_id, app, Some(bundle_id)));
+                        results.push(json!({
+                            "app": app,
+                            "bundle_id": bundle_id,
+                            "path": installed.path,
+                            "backup": installed.backup,
+                            "verified": true,
+                        }));
+                        match installed.backup {
+                            Some(backup) => {
+                                format!("installed and verified, previous file kept at {backup}")
+                            }
+                            None => "installed and verified".to_string(),
+                        }
+                    }
+                    Err(e) => {
+                        failed += 1;
+                        let message = Failure::from(e).message;
+                        results.push(json!({
+                            "app": app,
+                            "bundle_id": bundle_id,
+                            "error": message,
+                        }));
+                        format!("failed: {message}")
+                    }
+                };
+                // Progress goes to stderr as each install finishes, the summary to stdout
+                if !cli.json {
+                    eprintln!("[{}/{total}] {app} ({bundle_id}): {line}", results.len());
+                }
+            }
+
+            if failed > 0 {
+                return Err(Failure {
+                    code: EXIT_INSTALL_FAILED,
+                    message: format!("{failed} of {total} installs failed"),
+                    results: Some(Value::Array(results)),
+                });
+            }
+            Ok(Report {
+                human: format!(
+                    "Installed and verified pairing file in {total} app{}",
+                    if total == 1 { "" } else { "s" }
+                ),
+                json: Value::Array(results),
+            })
+        }
+        Command::Backups { target } => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let (supported, bundle_id) = select_app(&addr, &dev, target).await?;
+            let backups = apps::list_backups(&addr, &dev, &bundle_id, &supported).await?;
+
+            let mut human: Vec<String> = backups
+                .iter()
+                .map(|x| format!("{}\t{}\t{} bytes", x.taken, x.path, x.size))
+                .collect();
+            if human.is_empty() {
+                human.push(format!("No backups in {} ({bundle_id})", supported.name));
+            }
+            Ok(Report {
+                human: human.join("\n"),
+                json: json!(backups),
+            })
+        }
+        Command::Remove { target } => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let (supported, bundle_id) = select_app(&addr, &dev, target).await?;
+            let app = &supported.name;
+            let backup = apps::remove_pairing_file(&addr, &dev, &bundle_id, &supported)
+                .await?
+                .ok_or_else(|| {
+                    Failure::new(
+                        EXIT_FAILURE,
+                        format!("{app} ({bundle_id}) has no pairing file to remove"),
+                    )
+                })?;
+            Ok(Report {
+                human: format!("Removed pairing file from {app} ({bundle_id}), kept at {backup}"),
+                json: json!({ "app": app, "bundle_id": bundle_id, "backup": backup }),
+            })
+        }
+        Command::Restore { target, backup } => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let (supported, bundle_id) = select_app(&addr, &dev, target).await?;
+            let app = &supported.name;
+            let restored =
+                apps::restore_pairing_file(&addr, &dev, &bundle_id, &supported, backup.as_deref())
+                    .await?;
+
+            let mut human = format!("Restored pairing file in {app} ({bundle_id})");
+            if let Some(backup) = &restored.backup {
+                human.push_str(&format!("\nReplaced pairing file kept at {backup}"));
+            }
+            Ok(Report {
+                human,
+                json: json!({
+                    "app": app,
+                    "bundle_id": bundle_id,
+                    "path": restored.path,
+                    "backup": restored.backup,
+                }),
+            })
+        }
+        Command::Files { target, dir } => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let (_, bundle_id) = select_app(&addr, &dev, target).await?;
+            let entries = container::list_dir(&addr, &dev, &bundle_id, dir).await?;
+
+            let mut human: Vec<String> = entries
+                .iter()
+                .map(|x| {
+                    let size = if x.is_dir {
+                        "-".to_string()
+                    } else {
+                        x.size.to_string()
+                    };
+                    format!("{}\t{size}\t{}", x.modified, x.path)
+                })
+                .collect();
+            if human.is_empty() {
+                human.push(format!("{dir} is empty"));
+            }
+            Ok(Report {
+                human: human.join("\n"),
+                json: json!(entries),
+            })
+        }
+        Command::Download {
+            target,
+            path,
+            output,
+        } => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let (_, bundle_id) = select_app(&addr, &dev, target).await?;
+            let bytes = container::read_file(&addr, &dev, &bundle_id, path).await?;
+
+            match output {
+                Some(output) => {
+                    // Usually a pairing file, so keep its keys private
+                    pairing::write_to_file(output, &bytes)?;
+                    Ok(Report {
+                        human: format!("Saved {path} to {}", output.display()),
+                        json: json!({ "path": path, "output": output, "size": bytes.len() }),
+                    })
+                }
+                None => {
+                    let preview = container::preview(&bytes).ok_or_else(|| {
+                        Failure::new(
+                            EXIT_FAILURE,
+                            format!("{path} isn't a plist, pass --output to save it"),
+                        )
+                    })?;
+                    Ok(Report {
+                        human: preview.trim_end().to_string(),
+                        json: json!({ "path": path, "size": bytes.len(), "plist": preview }),
+                    })
+                }
+            }
+        }
+        Command::Mount => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            mount::auto_mount(&addr, &dev).await?;
+            Ok(Report {
+                human: "Developer disk image mounted".to_string(),
+                json: json!({ "mounted": true }),
+            })
+        }
+        Command::Mounted => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let images = mount::mounted_images(&addr, &dev).await?;
+            let mut human: Vec<String> = images
+                .iter()
+                .map(|x| {
+                    format!(
+                        "{}\t{}\t{}",
+                        x.image_type,
+                        x.mount_path.as_deref().unwrap_or("-"),
+                        x.signature.as_deref().unwrap_or("-")
+                    )
+                })
+                .collect();
+            if human.is_empty() {
+                human.push("No images mounted".to_string());
+            }
+            Ok(Report {
+                human: human.join("\n"),
+                json: serde_json::to_value(images).unwrap_or_default(),
+            })
+        }
+        Command::Unmount { mount_path } => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let unmounted = match mount_path {
+                Some(path) => {
+                    mount::unmount(&addr, &dev, path).await?;
+                    vec![path.clone()]
+                }
+                None => mount::unmount_all(&addr, &dev).await?,
+            };
+            Ok(Report {
+                human: if unmounted.is_empty() {
+                    "No images mounted".to_string()
+                } else {
+                    format!("Unmounted {}", unmounted.join(", "))
+                },
+                json: json!({ "unmounted": unmounted }),
+            })
+        }
+        Command::Remount => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            mount::remount(&addr, &dev).await?;
+            Ok(Report {
+                human: "Developer disk image remounted".to_string(),
+                json: json!({ "mounted": true }),
+            })
+        }
+        Command::Ddi { action } => {
+            let mut cache = DdiCache::open()?;
+            match action {
+                DdiAction::List => {
+                    let mut human: Vec<String> = cache
+                        .images()
+                        .iter()
+                        .map(|x| {
+                            format!(
+                                "{}\t{}\t{}\t{}",
+                                x.kind.id(),
+                                x.kind,
+                                x.imported.format("%Y-%m-%d %H:%M"),
+                                x.source.display()
+                            )
+                        })
+                        .collect();
+                    if human.is_empty() {
+                        human.push("No developer disk images in the cache".to_string());
+                    }
+                    if ddi::EMBEDDED {
+                        human.push("The personalized image built into this binary is used when none is cached".to_string());
+                    }
+                    Ok(Report {
+                        human: human.join("\n"),
+                        json: json!({
+                            "dir": cache.dir(),
+                            "images": cache.images(),
+                            "embedded": ddi::EMBEDDED,
+                        }),
+                    })
+                }
+                DdiAction::Import { path } => {
+                    let kinds = cache.import(path)?;
+                    Ok(Report {
+                        human: kinds
+                            .iter()
+                            .map(|x| format!("Imported the {x} image"))
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                        json: json!({ "imported": kinds }),
+                    })
+                }
+                DdiAction::Check => {
+                    let mut human = Vec::new();
+                    let mut results = Vec::new();
+                    let mut failed = false;
+                    for image in cache.images() {
+                        let result = cache.check_unchanged(&image.kind);
+                        human.push(match &result {
+                            Ok(()) => format!("{}: unchanged since import", image.kind),
+                            Err(e) => format!("{}: {e}", image.kind),
+                        });
+                        failed |= result.is_err();
+                        results.push(json!({
+                            "image": image.kind,
+                            "error": result.err().map(|e| e.to_string()),
+                        }));
+                    }
+                    if failed {
+                        return Err(Failure {
+                            code: EXIT_FAILURE,
+                            message: human.join("\n"),
+                            results: Some(Value::Array(results)),
+                        });
+                    }
+                    if human.is_empty() {
+                        human.push("No developer disk images in the cache".to_string());
+                    }
+                    Ok(Report {
+                        human: human.join("\n"),
+                        json: Value::Array(results),
+                    })
+                }
+                DdiAction::Remove { image } => {
+                    let kind = cache.find(image).map(|x| x.kind.clone()).ok_or_else(|| {
+                        Failure::new(EXIT_FAILURE, format!("{image} is not in the cache"))
+                    })?;
+                    cache.remove(&kind)?;
+                    Ok(Report {
+                        human: format!("Removed the {kind} image"),
+                        json: json!({ "removed": kind }),
+                    })
+                }
+            }
+        }
+        Command::Wireless { action } => {
+            let addr = usbmuxd_addr(cli)?;
+            let dev = select_device(&addr, cli.device.as_deref()).await?.usbmuxd;
+            let enabled = match action {
+                WirelessAction::Status => device::wireless_status(&addr, &dev).await?,
+                WirelessAction::Enable => device::set_wireless(&addr, &dev, true).await?,
+                WirelessAction::Disable => device::set_wireless(&addr, &dev, false).await?,
+            };
+            Ok(Report {
+                human: format!(
+                    "Wireless debugging {}",
+                    if enabled { "enabled" } else { "disabled" }
+                ),
+                json: json!({ "wireless_debugging": enabled }),
+            })
+        }
+        Command::Vault { action } => {
+            let mut vault = Vault::open()?;
+            match action {
+                VaultAction::List { query } => {
+                    let entries: Vec<&VaultEntry> = match query {
+                        Some(q) => vault.find(q).collect(),
+                        None => vault.entries().iter().collect(),
+                    };
+                    let mut human: Vec<String> = entries
+                        .iter()
+                        .map(|x| {
+                            format!(
+                                "{}\t{}\t{}\t{}",
+                                x.host_id,
+                                x.udid.as_deref().unwrap_or("-"),
+                                x.created.format("%Y-%m-%d %H:%M"),
+                                x.source
+                            )
+                        })
+                        .collect();
+                    if human.is_empty() {
+                        human.push("No pairing files in the vault".to_string());
+                    }
+                    Ok(Report {
+                        human: human.join("\n"),
+                        json: serde_json::to_value(entries).unwrap_or_default(),
+                    })
+                }
+                VaultAction::Show { query } => {
+                    let entry = find_in_vault(&vault, query)?;
+                    let mut human = vec![
+                        format!("HostID: {}", entry.host_id),
+                        format!("UDID: {}", entry.udid.as_deref().unwrap_or("(missing)")),
+                        format!(
+                            "Device: {}",
+                            entry.device_name.as_deref().unwrap_or("(unknown)")
+                        ),
+                        format!("Created: {}", entry.created),
+                        format!("Source: {}", entry.source),
+                        format!("Path: {}", vault.file_path(&entry.host_id).display()),
+                    ];
+                    human.push(match &entry.last_validation {
+                        Some(v) => match &v.error {
+                            None => format!("Last validation: valid at {}", v.at),
+                            Some(e) => format!("Last validation: failed at {}: {e}", v.at),
+                        },
+                        None => "Last validation: never".to_string(),
+                    });
+                    if entry.installs.is_empty() {
+                        human.push("Installed into: nothing".to_string());
+                    } else {
+                        human.push("Installed into:".to_string());
+                        human.extend(
+                            entry
+                                .installs
+                                .iter()
+                                .map(|x| format!("  - {} at {}", x.app, x.at)),
+                        );
+                    }
+                    Ok(Report {
+                        human: human.join("\n"),
+                        json: serde_json::to_value(entry).unwrap_or_default(),
+                    })
+                }
+                VaultAction::Export { query, output } => {
+                    let entry = find_in_vault(&vault, query)?;
+                    let pairing_file = vault.pairing_file(&entry.host_id)?;
+                    write_pairing_file(cli, pairing_file, output, entry.udid.as_deref())
+                }
+                VaultAction::Remove { host_id } => {
+                    if vault.get(host_id).is_none() {
+                        return Err(Failure::new(
+                            EXIT_FAILURE,
+                            format!("{host_id} is not in the vault"),
+                        ));
+                    }
+                    vault.remove(host_id)?;
+                    Ok(Report {
+                        human: format!("Removed {host_id} from the vault"),
+                        json: json!({ "removed": host_id }),
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Updates the vault, only warning on failure since it's a record and not the operation itself
+///
+/// Installs and validations are only recorded for files already in the vault.
+fn remember(f: impl FnOnce(&mut Vault) -> Result<(), Error>) {
+    if let Err(e) = Vault::open().and_then(|mut v| f(&mut v)) {
+        warn!("Failed to update the pairing file vault: {e}");
+    }
+}
+
+/// The newest vault entry for a UDID or HostID
+fn find_in_vault<'a>(vault: &'a Vault, query: &'a str) -> Result<&'a VaultEntry, Failure> {
+    vault.find(query).next().ok_or_else(|| {
+        Failure::new(
+            EXIT_FAILURE,
+            format!("No pairing file in the vault matches {query}"),
+        )
+    })
+}
+
+/// Resolves --usbmuxd, then the address in the app's settings, then the environment. Only done
+/// when a command needs usbmuxd so a bad address doesn't break offline commands.
+fn usbmuxd_addr(cli: &Cli) -> Result<UsbmuxdAddr, Failure> {
+    let settings = settings::Settings::load();
+    let flag = cli.usbmuxd.as_deref().filter(|x| !x.trim().is_empty());
+    Ok(device::resolve_usbmuxd_addr(
+        flag.or(settings.usbmuxd_address()),
+    )?)
+}
+
+/// The supported app named by `target` and the bundle ID of its installed copy to use
+async fn select_app(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    target: &AppArgs,
+) -> Result<(SupportedApp, String), Failure> {
+    let app = &target.app;
+    let supported = apps::AppRegistry::load()
+        .get(app)
+        .cloned()
+        .ok_or_else(|| Failure::new(EXIT_FAILURE, format!("{app} is not a supported app")))?;
+
+    let candidates = apps::installed_apps(addr, dev, std::slice::from_ref(&supported))
+        .await?
+        .remove(app)
+        .unwrap_or_default();
+    let bundle_id = match (&target.bundle_id, candidates.as_slice()) {
+        (_, []) => {
+            return Err(Failure::new(
+                EXIT_FAILURE,
+                format!("{app} is not installed on the device"),
+            ));
+        }
+        (Some(b), _) => candidates
+            .iter()
+            .find(|x| x.bundle_id.eq_ignore_ascii_case(b))
+            .map(|x| x.bundle_id.clone())
+            .ok_or_else(|| {
+                Failure::new(
+                    EXIT_FAILURE,
+                    format!("{b} is not an installed copy of {app}"),
+                )
+            })?,
+        (None, [only]) => only.bundle_id.clone(),
+        (None, _) => {
+            let ids: Vec<&str> = candidates.iter().map(|x| x.bundle_id.as_str()).collect();
+            return Err(Failure::new(
+                EXIT_FAILURE,
+                format!(
+                    "Several copies of {app} are installed, choose one with --bundle-id: {}",
+                    ids.join(", ")
+                ),
+            ));
+        }
+    };
+    Ok((supported, bundle_id))
+}
+
+/// Picks the device matching `selector` by UDID or name, or the only connected device
+async fn select_device(addr: &UsbmuxdAddr, selector: Option<&str>) -> Result<Device, Failure> {
+    let devices = device::get_devices(addr).await?;
+    match selector {
+        Some(s) => {
+            if let Some(dev) = devices.get(s) {
+                return Ok(dev.clone());
+            }
+            let mut named = devices.into_values().filter(|dev| dev.name == s);
+            match (named.next(), named.next()) {
+                (Some(dev), None) => Ok(dev),
+                (Some(_), Some(_)) => Err(Failure::new(
+                    EXIT_NO_DEVICE,
+                    format!("Multiple devices are named {s}, choose one by UDID"),
+                )),
+                (None, _) => Err(Failure::new(
+                    EXIT_NO_DEVICE,
+                    format!("No device matching {s}"),
+                )),
+            }
+        }
+        None => {
+            if devices.len() > 1 {
+                return Err(Failure::new(
+                    EXIT_NO_DEVICE,
+                    "Multiple devices connected, choose one with --device",
+                ));
+            }
+            devices
+                .into_values()
+                .next()
+                .ok_or_else(|| Failure::new(EXIT_NO_DEVICE, "No devices connected"))
+        }
+    }
+}
+
+fn describe_certificate(cert: &CertificateSummary) -> Vec<String> {
+    vec![
+        format!("  Subject: {}", cert.subject),
+        format!("  Issuer: {}", cert.issuer),
+        format!("  Serial: {}", cert.serial),
+        format!("  Valid: {} to {}", cert.not_before, cert.not_after),
+        format!("  Key: {}", cert.key_type),
+    ]
+}
+
+/// The passphrase for encrypted pairing files, from --passphrase-file or the environment
+fn passphrase(cli: &Cli) -> Result<Option<String>, Failure> {
+    if let Some(path) = &cli.passphrase_file {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Failure::new(
+                EXIT_FAILURE,
+                format!("Failed to read {}: {e}", path.display()),
+            )
+        })?;
+        return Ok(Some(contents.trim_end_matches(['\r', '\n']).to_string()));
+    }
+    Ok(std::env::var(PASSPHRASE_VAR).ok())
+}
+
+/// Saves the pairing file to `output`, or prints it with the keys redacted unless `show_keys`
+///
+/// `udid` is the device's, added to the pairing file when asked to and it doesn't have one.
+fn write_pairing_file(
+    cli: &Cli,
+    pairing_file: PairingFile,
+    args: &OutputArgs,
+    udid: Option<&str>,
+) -> Result<Report, Failure> {
+    let app = match &args.for_app {
+        Some(name) => Some(
+            apps::AppRegistry::load()
+                .get(name)
+                .cloned()
+                .ok_or_else(|| {
+                    Failure::new(EXIT_FAILURE, format!("{name} is not a supported app"))
+                })?,
+        ),
+        None => None,
+    };
+    let pairing_file = if args.udid || app.as_ref().is_some_and(|x| x.udid) {
+        let udid = pairing_file.udid.as_deref().or(udid).ok_or_else(|| {
+            Failure::new(
+                EXIT_FAILURE,
+                "The pairing file doesn't say which device it's for, so its UDID can't be added",
+            )
+        })?;
+        pairing::with_udid(&pairing_file, udid)
+    } else {
+        pairing_file
+    };
+    let format = app
+        .as_ref()
+        .map(|x| x.format)
+        .or(args.format)
+        .unwrap_or_default();
+
+    let udid = pairing_file.udid.clone();
+    match &args.output {
+        Some(path) => {
+            let passphrase = if args.encrypt {
+                Some(passphrase(cli)?.ok_or_else(|| {
+                    Failure::new(
+                        EXIT_FAILURE,
+                        format!("--encrypt needs --passphrase-file or ${PASSPHRASE_VAR}"),
+                    )
+                })?)
+            } else {
+                None
+            };
+            let bytes = pairing::encode(&pairing_file, format, passphrase.as_deref())?;
+            pairing::write_to_file(path, &bytes)?;
+            Ok(Report {
+                human: format!("Saved pairing file to {}", path.display()),
+                json: json!({
+                    "udid": udid,
+                    "path": path,
+                    "encrypted": args.encrypt,
+                    "format": (!args.encrypt).then_some(format),
+                }),
+            })
+        }
+        None => {
+            let contents = if args.show_keys {
+                let bytes = pairing_file.serialize().map_err(Error::from)?;
+                String::from_utf8_lossy(&bytes).to_string()
+            } else {
+                pairing::redacted_xml(&pairing_file)?
+            };
+            Ok(Report {
+                human: contents.trim_end().to_string(),
+                json: json!({ "udid": udid, "pairing_file": contents, "redacted": !args.show_keys }),
+            })
+        }
+    }
+}
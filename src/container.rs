@@ -0,0 +1,100 @@
+// Jackson Coxson
+//! Read only access to an app's container over house_arrest, for seeing where
+//! an app keeps its files when an install doesn't work.
+
+use chrono::{DateTime, Utc};
+use idevice::{
+    IdeviceError, IdeviceService,
+    afc::{AfcClient, opcode::AfcFopenMode},
+    house_arrest::HouseArrestClient,
+    usbmuxd::{UsbmuxdAddr, UsbmuxdDevice},
+};
+use serde::Serialize;
+
+use crate::{Error, LABEL, pairing};
+
+/// Where house_arrest puts the app's Documents folder
+pub const DOCUMENTS: &str = "/Documents";
+
+/// A file or directory in an app's container
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Entry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: usize,
+    pub modified: DateTime<Utc>,
+}
+
+/// What's in `dir`, directories first and then by name
+pub async fn list_dir(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    bundle_id: &str,
+    dir: &str,
+) -> Result<Vec<Entry>, Error> {
+    let mut ac = vend_documents(addr, dev, bundle_id).await?;
+    let mut entries = Vec::new();
+    for name in ac.list_dir(dir).await? {
+        if name == "." || name == ".." {
+            continue;
+        }
+        let path = join(dir, &name);
+        let info = ac.get_file_info(&path).await?;
+        entries.push(Entry {
+            name,
+            path,
+            is_dir: info.st_ifmt == "S_IFDIR",
+            size: info.size,
+            modified: info.modified.and_utc(),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+/// Reads a whole file from the app's container
+pub async fn read_file(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    bundle_id: &str,
+    path: &str,
+) -> Result<Vec<u8>, Error> {
+    let mut ac = vend_documents(addr, dev, bundle_id).await?;
+    let mut f = ac.open(path, AfcFopenMode::RdOnly).await?;
+    let bytes = f.read_entire().await?;
+    f.close().await?;
+    Ok(bytes)
+}
+
+/// The file as XML if it's a plist, with any pairing file private keys masked
+pub fn preview(bytes: &[u8]) -> Option<String> {
+    let mut value = plist::Value::from_reader(std::io::Cursor::new(bytes)).ok()?;
+    pairing::redact(&mut value);
+    let mut buf = Vec::new();
+    value.to_writer_xml(&mut buf).ok()?;
+    Some(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// The directory `path` is in, the container root stays where it is
+pub fn parent(path: &str) -> &str {
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    }
+}
+
+fn join(dir: &str, name: &str) -> String {
+    format!("{}/{name}", dir.trim_end_matches('/'))
+}
+
+/// Connects to AFC in the app's container, as house_arrest vends it for Documents
+pub(crate) async fn vend_documents(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    bundle_id: &str,
+) -> Result<AfcClient, IdeviceError> {
+    let p = dev.to_provider(addr.clone(), LABEL);
+    let hc = HouseArrestClient::connect(&p).await?;
+    hc.vend_documents(bundle_id).await
+}
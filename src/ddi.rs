@@ -0,0 +1,544 @@
+// Jackson Coxson
+//! Developer disk images kept in a local cache, so mounting doesn't depend on
+//! the copy built into the binary.
+
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{Error, settings};
+
+const INDEX_FILE: &str = "index.json";
+pub const IMAGE: &str = "Image.dmg";
+pub const TRUSTCACHE: &str = "Image.dmg.trustcache";
+pub const BUILD_MANIFEST: &str = "BuildManifest.plist";
+pub const LEGACY_IMAGE: &str = "DeveloperDiskImage.dmg";
+pub const LEGACY_SIGNATURE: &str = "DeveloperDiskImage.dmg.signature";
+
+/// Whether this build has the personalized image built in to fall back to
+pub const EMBEDDED: bool = cfg!(embedded_ddi);
+
+/// The files mounted on iOS 17 and later
+#[derive(Debug, Clone)]
+pub struct PersonalizedImage {
+    pub image: Vec<u8>,
+    pub trustcache: Vec<u8>,
+    pub build_manifest: Vec<u8>,
+}
+
+/// The image and signature mounted before iOS 17, made for one iOS version
+#[derive(Debug, Clone)]
+pub struct LegacyImage {
+    /// The iOS version the image is for, which can be older than the device's
+    pub version: String,
+    pub image: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/// Which image a cache entry holds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageKind {
+    Personalized,
+    /// A version specific image, from a folder named after the iOS version like Xcode's DeviceSupport
+    Legacy {
+        version: String,
+    },
+}
+
+impl ImageKind {
+    /// The files making up the image
+    pub fn files(&self) -> &'static [&'static str] {
+        match self {
+            ImageKind::Personalized => &[IMAGE, TRUSTCACHE, BUILD_MANIFEST],
+            ImageKind::Legacy { .. } => &[LEGACY_IMAGE, LEGACY_SIGNATURE],
+        }
+    }
+
+    /// Short name for the kind, also the folder its files are kept in
+    pub fn id(&self) -> String {
+        match self {
+            ImageKind::Personalized => "personalized".to_string(),
+            ImageKind::Legacy { version } => version.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for ImageKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageKind::Personalized => f.write_str("personalized (iOS 17+)"),
+            ImageKind::Legacy { version } => write!(f, "iOS {version}"),
+        }
+    }
+}
+
+/// An image in the cache, with the hashes its files are checked against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedImage {
+    pub kind: ImageKind,
+    pub imported: DateTime<Utc>,
+    /// The folder or archive it was imported from
+    pub source: PathBuf,
+    /// SHA-256 of each file, in hex
+    pub files: BTreeMap<String, String>,
+}
+
+/// Developer disk images kept on disk, one per kind
+#[derive(Debug)]
+pub struct DdiCache {
+    dir: PathBuf,
+    images: Vec<CachedImage>,
+}
+
+impl DdiCache {
+    /// Opens the cache in the app's config directory
+    pub fn open() -> Result<Self, Error> {
+        let dir = settings::config_dir().ok_or(Error::NoConfigDir)?;
+        Self::open_at(dir.join("ddi"))
+    }
+
+    /// Opens the cache kept in `dir`, which doesn't have to exist yet
+    pub fn open_at(dir: impl Into<PathBuf>) -> Result<Self, Error> {
+        let dir = dir.into();
+        let index = dir.join(INDEX_FILE);
+        let images = match std::fs::read(&index) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| Error::Io {
+                path: index,
+                source: e.into(),
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(Error::Io {
+                    path: index,
+                    source: e,
+                });
+            }
+        };
+        Ok(Self { dir, images })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn images(&self) -> &[CachedImage] {
+        &self.images
+    }
+
+    pub fn get(&self, kind: &ImageKind) -> Option<&CachedImage> {
+        self.images.iter().find(|x| &x.kind == kind)
+    }
+
+    /// The cached image with the given [`ImageKind::id`]
+    pub fn find(&self, id: &str) -> Option<&CachedImage> {
+        self.images
+            .iter()
+            .find(|x| x.kind.id().eq_ignore_ascii_case(id))
+    }
+
+    /// Imports the images found in a folder, a .zip or a .tar(.gz), replacing cached ones of the same kind
+    pub fn import(&mut self, path: &Path) -> Result<Vec<ImageKind>, Error> {
+        let found = if path.is_dir() {
+            let mut found = Vec::new();
+            read_folder(path, path, &mut found)?;
+            // Read order depends on the file system, pick the same files everywhere
+            found.sort_by(|a, b| a.0.cmp(&b.0));
+            found
+        } else {
+            read_archive(path)?
+        };
+
+        let mut images = Vec::new();
+        if let Some(files) = personalized_files(&found) {
+            images.push((ImageKind::Personalized, files));
+        }
+        images.extend(legacy_files(&found));
+        if images.is_empty() {
+            return Err(invalid(
+                path,
+                format!(
+                    "no {IMAGE}, {TRUSTCACHE} and {BUILD_MANIFEST}, or <iOS version>/{LEGACY_IMAGE} and its signature found in it"
+                ),
+            ));
+        }
+        // Check everything first so a bad image doesn't leave the others half imported
+        for (_, files) in &images {
+            validate(files)?;
+        }
+        let mut imported = Vec::new();
+        for (kind, files) in images {
+            self.store(kind.clone(), path, &files)?;
+            imported.push(kind);
+        }
+        Ok(imported)
+    }
+
+    /// Reads a cached image's files, checking them against the hashes taken when it was imported
+    pub fn read(&self, kind: &ImageKind) -> Result<Option<BTreeMap<String, Vec<u8>>>, Error> {
+        let Some(entry) = self.get(kind) else {
+            return Ok(None);
+        };
+        let mut files = BTreeMap::new();
+        for (name, hash) in &entry.files {
+            let path = self.file_path(kind, name);
+            let bytes = std::fs::read(&path).map_err(|e| Error::Io {
+                path: path.clone(),
+                source: e,
+            })?;
+            if &sha256(&bytes) != hash {
+                return Err(invalid(&path, "changed since it was imported".to_string()));
+            }
+            files.insert(name.clone(), bytes);
+        }
+        Ok(Some(files))
+    }
+
+    /// Checks that a cached image's files are unchanged since they were imported
+    ///
+    /// Only the hashes recorded at import are compared, whether Apple signed the image is
+    /// up to the device when it's mounted.
+    pub fn check_unchanged(&self, kind: &ImageKind) -> Result<(), Error> {
+        self.read(kind).map(|_| ())
+    }
+
+    /// The personalized image, if one was imported
+    pub fn personalized(&self) -> Result<Option<PersonalizedImage>, Error> {
+        let Some(mut files) = self.read(&ImageKind::Personalized)? else {
+            return Ok(None);
+        };
+        let mut take = |name| files.remove(name).unwrap_or_default();
+        Ok(Some(PersonalizedImage {
+            image: take(IMAGE),
+            trustcache: take(TRUSTCACHE),
+            build_manifest: take(BUILD_MANIFEST),
+        }))
+    }
+
+    /// The image for an iOS version before 17, or the closest older one of the same major version
+    pub fn legacy(&self, product_version: &str) -> Result<Option<LegacyImage>, Error> {
+        let wanted = version_parts(product_version);
+        let Some(version) = self
+            .images
+            .iter()
+            .filter_map(|x| match &x.kind {
+                ImageKind::Legacy { version } => Some(version),
+                ImageKind::Personalized => None,
+            })
+            .filter(|x| {
+                let parts = version_parts(x);
+                parts.first() == wanted.first() && parts <= wanted
+            })
+            .max_by_key(|x| version_parts(x))
+        else {
+            return Ok(None);
+        };
+        let kind = ImageKind::Legacy {
+            version: version.clone(),
+        };
+        let Some(mut files) = self.read(&kind)? else {
+            return Ok(None);
+        };
+        let mut take = |name| files.remove(name).unwrap_or_default();
+        Ok(Some(LegacyImage {
+            version: version.clone(),
+            image: take(LEGACY_IMAGE),
+            signature: take(LEGACY_SIGNATURE),
+        }))
+    }
+
+    pub fn remove(&mut self, kind: &ImageKind) -> Result<(), Error> {
+        let path = self.dir.join(kind.id());
+        match std::fs::remove_dir_all(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Error::Io { path, source: e }),
+        }
+        self.images.retain(|x| &x.kind != kind);
+        self.save()
+    }
+
+    /// Where a file of a cached image is kept
+    pub fn file_path(&self, kind: &ImageKind, name: &str) -> PathBuf {
+        self.dir.join(kind.id()).join(name)
+    }
+
+    fn store(
+        &mut self,
+        kind: ImageKind,
+        source: &Path,
+        files: &[(&str, &[u8])],
+    ) -> Result<(), Error> {
+        let mut hashes = BTreeMap::new();
+        for (name, bytes) in files {
+            settings::write_file(&self.file_path(&kind, name), bytes)?;
+            hashes.insert(name.to_string(), sha256(bytes));
+        }
+        self.images.retain(|x| x.kind != kind);
+        self.images.push(CachedImage {
+            kind,
+            imported: Utc::now(),
+            source: source.to_path_buf(),
+            files: hashes,
+        });
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let index = serde_json::to_vec_pretty(&self.images).unwrap();
+        settings::write_file(&self.dir.join(INDEX_FILE), &index)
+    }
+}
+
+/// The personalized image to mount, from the cache or else the copy built into the binary
+pub fn personalized_image() -> Result<PersonalizedImage, Error> {
+    match DdiCache::open() {
+        Ok(cache) => {
+            if let Some(image) = cache.personalized()? {
+                return Ok(image);
+            }
+        }
+        Err(e) => log::warn!("Failed to open the developer disk image cache: {e}"),
+    }
+    embedded().ok_or(Error::NoDdi)
+}
+
+/// The legacy image to mount on a device running `product_version`, which has to be imported
+pub fn legacy_image(product_version: &str) -> Result<LegacyImage, Error> {
+    DdiCache::open()?
+        .legacy(product_version)?
+        .ok_or_else(|| Error::NoLegacyDdi(product_version.to_string()))
+}
+
+/// Whether a device running `product_version` mounts the personalized image
+pub fn is_personalized(product_version: &str) -> bool {
+    version_parts(product_version)
+        .first()
+        .is_some_and(|&major| major >= 17)
+}
+
+fn version_parts(version: &str) -> Vec<u32> {
+    version.split('.').map_while(|x| x.parse().ok()).collect()
+}
+
+/// The iOS version a folder is named after, like `16.4` or Xcode's `16.4 (20E247)`
+fn folder_version(folder: &str) -> Option<String> {
+    let version: String = folder
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let version = version.trim_end_matches('.');
+    (version_parts(version).len() >= 2).then(|| version.to_string())
+}
+
+#[cfg(embedded_ddi)]
+fn embedded() -> Option<PersonalizedImage> {
+    Some(PersonalizedImage {
+        image: include_bytes!("../DDI/Image.dmg").to_vec(),
+        trustcache: include_bytes!("../DDI/Image.dmg.trustcache").to_vec(),
+        build_manifest: include_bytes!("../DDI/BuildManifest.plist").to_vec(),
+    })
+}
+
+#[cfg(not(embedded_ddi))]
+fn embedded() -> Option<PersonalizedImage> {
+    None
+}
+
+/// An image's files by name, borrowed from everything an import found
+type Files<'a> = Vec<(&'static str, &'a [u8])>;
+
+/// Whether a file is part of an image, going by its name
+fn wanted(path: &str) -> bool {
+    [
+        IMAGE,
+        TRUSTCACHE,
+        BUILD_MANIFEST,
+        LEGACY_IMAGE,
+        LEGACY_SIGNATURE,
+    ]
+    .contains(&base_name(path))
+}
+
+fn base_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+/// Picks the personalized image's files out of everything found, by name
+fn personalized_files(found: &[(String, Vec<u8>)]) -> Option<Files<'_>> {
+    ImageKind::Personalized
+        .files()
+        .iter()
+        .map(|name| {
+            found
+                .iter()
+                .find(|(path, _)| base_name(path) == *name)
+                .map(|(_, bytes)| (*name, bytes.as_slice()))
+        })
+        .collect()
+}
+
+/// Pairs each legacy image found with its signature, by the folder they're in
+fn legacy_files(found: &[(String, Vec<u8>)]) -> Vec<(ImageKind, Files<'_>)> {
+    let mut images = Vec::new();
+    for (path, image) in found {
+        let Some(folder) = path.strip_suffix(LEGACY_IMAGE) else {
+            continue;
+        };
+        let Some(version) = folder_version(base_name(folder.trim_end_matches('/'))) else {
+            log::warn!("Skipping {path}, it's not in a folder named after its iOS version");
+            continue;
+        };
+        let signature = format!("{path}.signature");
+        let Some((_, signature)) = found.iter().find(|(x, _)| *x == signature) else {
+            log::warn!("Skipping {path}, it has no signature next to it");
+            continue;
+        };
+        // The same version can be in an archive twice, keep the last one like the cache would
+        images.retain(|(kind, _)| {
+            kind != &ImageKind::Legacy {
+                version: version.clone(),
+            }
+        });
+        images.push((
+            ImageKind::Legacy { version },
+            vec![
+                (LEGACY_IMAGE, image.as_slice()),
+                (LEGACY_SIGNATURE, signature.as_slice()),
+            ],
+        ));
+    }
+    images
+}
+
+/// Rejects files that can't be an image, this doesn't check their signatures
+fn validate(files: &[(&str, &[u8])]) -> Result<(), Error> {
+    for (name, bytes) in files {
+        if bytes.is_empty() {
+            return Err(invalid(name, "it's empty".to_string()));
+        }
+        if *name == BUILD_MANIFEST {
+            let manifest: plist::Value =
+                plist::from_bytes(bytes).map_err(|e| invalid(name, format!("not a plist: {e}")))?;
+            if manifest
+                .as_dictionary()
+                .and_then(|x| x.get("BuildIdentities"))
+                .and_then(|x| x.as_array())
+                .is_none_or(|x| x.is_empty())
+            {
+                return Err(invalid(name, "it has no BuildIdentities".to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn invalid(name: impl AsRef<Path>, reason: String) -> Error {
+    Error::InvalidDdi {
+        name: name.as_ref().display().to_string(),
+        reason,
+    }
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect()
+}
+
+fn read_folder(root: &Path, dir: &Path, found: &mut Vec<(String, Vec<u8>)>) -> Result<(), Error> {
+    let io = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| Error::Io { path, source }
+    };
+    for entry in std::fs::read_dir(dir).map_err(io(dir))? {
+        let path = entry.map_err(io(dir))?.path();
+        if path.is_dir() {
+            read_folder(root, &path, found)?;
+            continue;
+        }
+        // Keep the folder's own name, it can be the iOS version of a legacy image
+        let relative = path
+            .strip_prefix(root.parent().unwrap_or(root))
+            .unwrap_or(&path)
+            .to_string_lossy();
+        if wanted(&relative) {
+            let bytes = std::fs::read(&path).map_err(io(&path))?;
+            found.push((relative.replace('\\', "/"), bytes));
+        }
+    }
+    Ok(())
+}
+
+/// Reads the image files out of a .zip, .tar or .tar.gz
+fn read_archive(path: &Path) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let file = std::fs::File::open(path).map_err(|source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let result = if name.ends_with(".zip") {
+        read_zip(file)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        read_tar(flate2::read::GzDecoder::new(file))
+    } else if name.ends_with(".tar") {
+        read_tar(file)
+    } else {
+        Err("expected a folder, .zip, .tar or .tar.gz".to_string())
+    };
+    result.map_err(|reason| invalid(path, reason))
+}
+
+fn read_zip(file: std::fs::File) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut zip = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+    let mut found = Vec::new();
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i).map_err(|e| e.to_string())?;
+        let name = entry.name().to_string();
+        if !entry.is_file() || !wanted(&name) {
+            continue;
+        }
+        // Reading to the end also checks the entry's checksum
+        let mut bytes = Vec::new();
+        entry
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("failed to read {name}: {e}"))?;
+        found.push((name, bytes));
+    }
+    Ok(found)
+}
+
+fn read_tar(reader: impl Read) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut tar = tar::Archive::new(reader);
+    let mut found = Vec::new();
+    for entry in tar.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(|e| e.to_string())?
+            .to_string_lossy()
+            .replace('\\', "/");
+        if !wanted(&name) {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        entry
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("failed to read {name}: {e}"))?;
+        found.push((name, bytes));
+    }
+    Ok(found)
+}
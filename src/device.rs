@@ -0,0 +1,265 @@
+// Jackson Coxson
+
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+use idevice::{
+    IdeviceError, IdeviceService,
+    lockdown::LockdownClient,
+    usbmuxd::{Connection, UsbmuxdAddr, UsbmuxdConnection, UsbmuxdDevice},
+};
+use log::error;
+
+use crate::{Error, LABEL};
+
+/// Environment variable read when no usbmuxd address is configured
+pub const USBMUXD_ADDRESS_VAR: &str = "USBMUXD_SOCKET_ADDRESS";
+
+const WIRELESS_DOMAIN: &str = "com.apple.mobile.wireless_lockdown";
+const WIRELESS_KEY: &str = "EnableWifiDebugging";
+
+/// Parses a usbmuxd address, either `host:port` for TCP or a Unix socket path
+pub fn parse_usbmuxd_addr(address: &str) -> Result<UsbmuxdAddr, Error> {
+    let invalid = |source| Error::InvalidUsbmuxdAddress {
+        address: address.to_string(),
+        source,
+    };
+    #[cfg(unix)]
+    if !address.contains(':') {
+        return Ok(UsbmuxdAddr::UnixSocket(address.to_string()));
+    }
+    Ok(UsbmuxdAddr::TcpSocket(
+        SocketAddr::from_str(address).map_err(invalid)?,
+    ))
+}
+
+/// Picks the usbmuxd address to use
+///
+/// A configured address wins, then [`USBMUXD_ADDRESS_VAR`], then the platform default.
+pub fn resolve_usbmuxd_addr(configured: Option<&str>) -> Result<UsbmuxdAddr, Error> {
+    match configured.map(str::trim).filter(|x| !x.is_empty()) {
+        Some(address) => parse_usbmuxd_addr(address),
+        None => match std::env::var(USBMUXD_ADDRESS_VAR) {
+            Ok(address) if !address.is_empty() => parse_usbmuxd_addr(&address),
+            _ => Ok(UsbmuxdAddr::default()),
+        },
+    }
+}
+
+/// Formats an address the way [`parse_usbmuxd_addr`] accepts it
+pub fn describe_usbmuxd_addr(addr: &UsbmuxdAddr) -> String {
+    match addr {
+        #[cfg(unix)]
+        UsbmuxdAddr::UnixSocket(path) => path.clone(),
+        UsbmuxdAddr::TcpSocket(addr) => addr.to_string(),
+    }
+}
+
+/// Connects to usbmuxd, mapping a failure to [`Error::NoUsbmuxd`]
+pub async fn connect_usbmuxd(addr: &UsbmuxdAddr) -> Result<UsbmuxdConnection, Error> {
+    addr.connect(0).await.map_err(Error::NoUsbmuxd)
+}
+
+/// A device known to usbmuxd, with the lockdown values used to tell it apart from others
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub name: String,
+    pub model: String,
+    pub usbmuxd: UsbmuxdDevice,
+}
+
+impl Device {
+    pub fn udid(&self) -> &str {
+        &self.usbmuxd.udid
+    }
+
+    pub fn is_network(&self) -> bool {
+        self.network_address().is_some()
+    }
+
+    /// The IP usbmuxd reaches the device at, if it's connected over the network
+    pub fn network_address(&self) -> Option<IpAddr> {
+        match self.usbmuxd.connection_type {
+            Connection::Network(ip) => Some(ip),
+            _ => None,
+        }
+    }
+
+    /// The last 8 characters of the UDID, enough to tell devices of the same model apart
+    pub fn short_udid(&self) -> &str {
+        let udid = self.udid();
+        udid.get(udid.len().saturating_sub(8)..).unwrap_or(udid)
+    }
+
+    /// Name, model and short UDID, for showing in device selectors
+    pub fn label(&self) -> String {
+        let label = format!("{} ({}, …{})", self.name, self.model, self.short_udid());
+        if self.is_network() {
+            format!("{label} [Network]")
+        } else {
+            label
+        }
+    }
+}
+
+/// Lists the devices known to usbmuxd over USB or the network, keyed by UDID
+///
+/// A device connected both ways is listed once, using its USB connection.
+pub async fn get_devices(addr: &UsbmuxdAddr) -> Result<BTreeMap<String, Device>, Error> {
+    let mut uc = connect_usbmuxd(addr).await?;
+
+    let mut devs: Vec<UsbmuxdDevice> = uc
+        .get_devices()
+        .await?
+        .into_iter()
+        .filter(|x| !matches!(x.connection_type, Connection::Unknown(_)))
+        .collect();
+    devs.sort_by_key(|x| x.connection_type != Connection::Usb);
+
+    // We have to manually iterate to use async
+    let mut selections = BTreeMap::new();
+    for dev in devs {
+        if selections.contains_key(&dev.udid) {
+            continue;
+        }
+        let p = dev.to_provider(addr.clone(), LABEL);
+        let mut lc = match LockdownClient::connect(&p).await {
+            Ok(l) => l,
+            Err(e) => {
+                error!("Failed to connect to lockdown: {e:?}");
+                continue;
+            }
+        };
+        let values = match lc.get_value(None, None).await {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to get lockdown values: {e:?}");
+                continue;
+            }
+        };
+        let values = values.as_dictionary();
+        let value = |key: &str| {
+            values
+                .and_then(|x| x.get(key))
+                .and_then(|x| x.as_string())
+                .unwrap_or("Unknown")
+                .to_string()
+        };
+
+        selections.insert(
+            dev.udid.clone(),
+            Device {
+                name: value("DeviceName"),
+                model: value("ProductType"),
+                usbmuxd: dev,
+            },
+        );
+    }
+
+    Ok(selections)
+}
+
+/// Reads the human readable details shown next to the device selector
+pub async fn device_info(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+) -> Result<Vec<(String, String)>, Error> {
+    let p = dev.to_provider(addr.clone(), LABEL);
+    let mut lc = LockdownClient::connect(&p).await?;
+
+    let values = lc.get_value(None, None).await?;
+    let values = values
+        .as_dictionary()
+        .ok_or(IdeviceError::UnexpectedResponse)?;
+
+    let mut device_info = Vec::with_capacity(5);
+
+    // Fixed order of fields in reverse order
+    let fields = [
+        ("Device Name", "DeviceName"),
+        ("Model", "ProductType"),
+        ("iOS Version", "ProductVersion"),
+        ("Build Number", "BuildVersion"),
+        ("UDID", "UniqueDeviceID"),
+    ];
+
+    for (display_name, key) in fields.iter() {
+        if let Some(plist::Value::String(value)) = values.get(key) {
+            device_info.push((display_name.to_string(), value.clone()));
+        }
+    }
+
+    Ok(device_info)
+}
+
+/// Opens a lockdown session using the pairing record usbmuxd holds for the device
+async fn lockdown_session(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+) -> Result<LockdownClient, Error> {
+    let mut uc = connect_usbmuxd(addr).await?;
+
+    let p = dev.to_provider(addr.clone(), LABEL);
+    let pairing_file = uc.get_pair_record(&p.udid).await?;
+
+    let mut lc = LockdownClient::connect(&p).await?;
+    lc.start_session(&pairing_file).await?;
+    Ok(lc)
+}
+
+/// Fails with [`Error::UsbRequired`] unless the device is plugged in
+pub fn require_usb(dev: &UsbmuxdDevice) -> Result<(), Error> {
+    match dev.connection_type {
+        Connection::Usb => Ok(()),
+        _ => Err(Error::UsbRequired),
+    }
+}
+
+/// Whether wireless debugging is on, so the device accepts lockdown over the network
+pub async fn wireless_status(addr: &UsbmuxdAddr, dev: &UsbmuxdDevice) -> Result<bool, Error> {
+    let mut lc = lockdown_session(addr, dev).await?;
+    read_wireless(&mut lc).await
+}
+
+/// Turns wireless debugging on or off, returning what the device reports afterwards
+pub async fn set_wireless(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    enabled: bool,
+) -> Result<bool, Error> {
+    let mut lc = lockdown_session(addr, dev).await?;
+    lc.set_value(WIRELESS_KEY, enabled.into(), Some(WIRELESS_DOMAIN))
+        .await?;
+    read_wireless(&mut lc).await
+}
+
+async fn read_wireless(lc: &mut LockdownClient) -> Result<bool, Error> {
+    match lc
+        .get_value(Some(WIRELESS_KEY), Some(WIRELESS_DOMAIN))
+        .await
+    {
+        Ok(v) => v
+            .as_boolean()
+            .ok_or(Error::Idevice(IdeviceError::UnexpectedResponse)),
+        // Devices that were never set up for it don't have the value at all
+        Err(IdeviceError::UnknownErrorType(e)) if e == "MissingValue" => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Checks whether Developer Mode is enabled on the device
+pub async fn dev_mode_status(addr: &UsbmuxdAddr, dev: &UsbmuxdDevice) -> Result<bool, Error> {
+    let mut lc = lockdown_session(addr, dev).await?;
+    let v = lc
+        .get_value(
+            Some("DeveloperModeStatus"),
+            Some("com.apple.security.mac.amfi"),
+        )
+        .await?;
+
+    v.as_boolean()
+        .ok_or(Error::Idevice(IdeviceError::UnexpectedResponse))
+}
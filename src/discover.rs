@@ -1,22 +1,23 @@
 // Jackson Coxson
 
 use futures_util::{StreamExt, pin_mut};
-use log::{debug, warn};
+use log::{debug, info, warn};
 use mdns::{Record, RecordKind};
 use std::{net::IpAddr, time::Duration};
-use tokio::sync::mpsc::UnboundedSender;
 
-use crate::IdeviceCommands;
+use crate::Error;
 
 const SERVICE_NAME: &str = "apple-mobdev2";
 const SERVICE_PROTOCOL: &str = "tcp";
 
-pub async fn start_discover(sender: UnboundedSender<IdeviceCommands>) {
+/// Browses mDNS for devices with wireless debugging enabled, calling
+/// `on_discovered` with each device's address and Wi-Fi MAC address
+pub async fn start_discover(mut on_discovered: impl FnMut(IpAddr, String)) -> Result<(), Error> {
     let service_name = format!("_{}._{}.local", SERVICE_NAME, SERVICE_PROTOCOL);
-    println!("Starting mDNS discovery for {} with mdns", service_name);
+    info!("Starting mDNS discovery for {} with mdns", service_name);
 
     let stream = mdns::discover::all(&service_name, Duration::from_secs(1))
-        .expect("Unable to start mDNS discover stream")
+        .map_err(Error::Discover)?
         .listen();
     pin_mut!(stream);
 
@@ -44,14 +45,34 @@ pub async fn start_discover(sender: UnboundedSender<IdeviceCommands>) {
             };
 
             debug!("Discovered {mac_addr} at {addr}");
-            sender
-                .send(IdeviceCommands::DiscoveredDevice((
-                    addr,
-                    mac_addr.to_string(),
-                )))
-                .unwrap();
+            on_discovered(addr, mac_addr.to_string());
         }
     }
+    Ok(())
+}
+
+/// Browses mDNS until the device with the given Wi-Fi MAC address shows up,
+/// giving up after `timeout`
+pub async fn find_device(mac_addr: &str, timeout: Duration) -> Result<Option<IpAddr>, Error> {
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let discover = start_discover(|addr, mac| {
+        if mac == mac_addr {
+            let _ = sender.send(addr);
+        }
+    });
+    pin_mut!(discover);
+
+    let found = tokio::time::timeout(timeout, async {
+        tokio::select! {
+            res = &mut discover => res.map(|_| None),
+            addr = receiver.recv() => Ok(addr),
+        }
+    })
+    .await;
+    match found {
+        Ok(res) => res,
+        Err(_) => Ok(None),
+    }
 }
 
 fn to_ip_addr(record: &Record) -> Option<IpAddr> {
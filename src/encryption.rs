@@ -0,0 +1,138 @@
+// Jackson Coxson
+//! Passphrase protected pairing files
+//!
+//! The container is a small header followed by the serialized pairing file,
+//! encrypted with XChaCha20-Poly1305 under a key derived from the passphrase
+//! with Argon2id. The header is authenticated along with the contents, so any
+//! change to the file is reported the same way as a wrong passphrase.
+//!
+//! ```text
+//! magic (8) | memory KiB (u32 LE) | iterations (u32 LE) | lanes (u32 LE) | salt (16) | nonce (24) | ciphertext
+//! ```
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    AeadCore, KeyInit, XChaCha20Poly1305, XNonce,
+    aead::{Aead, OsRng, Payload, rand_core::RngCore},
+};
+use idevice::pairing_file::PairingFile;
+
+use crate::{Error, pairing};
+
+/// Extension used for encrypted pairing files
+pub const EXTENSION: &str = "idevicepairing";
+
+const MAGIC: &[u8; 8] = b"IDPENC01";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 12 + SALT_LEN + NONCE_LEN;
+
+// Argon2id with the OWASP recommended cost, stored in the header so it can be raised later
+const MEMORY_KIB: u32 = 19 * 1024;
+const ITERATIONS: u32 = 2;
+const LANES: u32 = 1;
+// Refuse headers asking for more than this, a crafted file shouldn't exhaust RAM or hang
+// before the contents can be checked
+const MAX_MEMORY_KIB: u32 = 4 * MEMORY_KIB;
+const MAX_ITERATIONS: u32 = 10;
+const MAX_LANES: u32 = 8;
+
+/// Whether the bytes are an encrypted container rather than a plain pairing file
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Encrypts the pairing file with a key derived from `passphrase`
+pub fn encrypt(pairing_file: &PairingFile, passphrase: &str) -> Result<Vec<u8>, Error> {
+    if passphrase.is_empty() {
+        return Err(Error::PassphraseRequired);
+    }
+    let plaintext = pairing_file.clone().serialize()?;
+
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + plaintext.len() + 16);
+    out.extend_from_slice(MAGIC);
+    for x in [MEMORY_KIB, ITERATIONS, LANES] {
+        out.extend_from_slice(&x.to_le_bytes());
+    }
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+
+    let cipher = cipher(passphrase, &salt, MEMORY_KIB, ITERATIONS, LANES)?;
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: &plaintext,
+                aad: &out,
+            },
+        )
+        .map_err(|_| Error::InvalidPairingFile("encryption failed".to_string()))?;
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypts a container made by [`encrypt`]
+pub fn decrypt(bytes: &[u8], passphrase: &str) -> Result<PairingFile, Error> {
+    if !is_encrypted(bytes) {
+        return Err(Error::InvalidPairingFile(
+            "not an encrypted pairing file".to_string(),
+        ));
+    }
+    if bytes.len() < HEADER_LEN {
+        return Err(Error::InvalidPairingFile(
+            "encrypted pairing file is truncated".to_string(),
+        ));
+    }
+    let (header, ciphertext) = bytes.split_at(HEADER_LEN);
+
+    let param = |i: usize| {
+        let start = MAGIC.len() + i * 4;
+        u32::from_le_bytes(header[start..start + 4].try_into().unwrap())
+    };
+    let (memory, iterations, lanes) = (param(0), param(1), param(2));
+    for (value, max, what) in [
+        (memory, MAX_MEMORY_KIB, "KiB of memory"),
+        (iterations, MAX_ITERATIONS, "iterations"),
+        (lanes, MAX_LANES, "lanes"),
+    ] {
+        if value > max {
+            return Err(Error::InvalidPairingFile(format!(
+                "encrypted pairing file asks for {value} {what} to decrypt, at most {max} are allowed"
+            )));
+        }
+    }
+    let salt = &header[MAGIC.len() + 12..MAGIC.len() + 12 + SALT_LEN];
+    let nonce = XNonce::from_slice(&header[HEADER_LEN - NONCE_LEN..]);
+
+    let cipher = cipher(passphrase, salt, memory, iterations, lanes)?;
+    let plaintext = cipher
+        .decrypt(
+            nonce,
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| Error::WrongPassphrase)?;
+    pairing::from_bytes(&plaintext)
+}
+
+fn cipher(
+    passphrase: &str,
+    salt: &[u8],
+    memory: u32,
+    iterations: u32,
+    lanes: u32,
+) -> Result<XChaCha20Poly1305, Error> {
+    let params = Params::new(memory, iterations, lanes, Some(32))
+        .map_err(|e| Error::InvalidPairingFile(format!("bad key derivation parameters ({e})")))?;
+    let mut key = [0; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::InvalidPairingFile(format!("key derivation failed ({e})")))?;
+    Ok(XChaCha20Poly1305::new(&key.into()))
+}
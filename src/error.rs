@@ -0,0 +1,94 @@
+// Jackson Coxson
+
+use std::{net::AddrParseError, path::PathBuf};
+
+use idevice::IdeviceError;
+
+use crate::apps::InstallStep;
+
+/// Errors returned by the device operations in this crate
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// usbmuxd could not be reached at all
+    #[error("failed to connect to usbmuxd")]
+    NoUsbmuxd(#[source] IdeviceError),
+    #[error(transparent)]
+    Idevice(#[from] IdeviceError),
+    #[error("failed to access {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The bytes aren't a usable pairing file, with the reason why
+    #[error("invalid pairing file: {0}")]
+    InvalidPairingFile(String),
+    #[error("invalid usbmuxd address {address}, expected a socket path or host:port")]
+    InvalidUsbmuxdAddress {
+        address: String,
+        #[source]
+        source: AddrParseError,
+    },
+    /// mDNS browsing for devices on the network couldn't be started
+    #[error("failed to look for devices on the network")]
+    Discover(#[source] mdns::Error),
+    /// No config directory could be found, e.g. because HOME isn't set
+    #[error("could not find a directory to store app data in")]
+    NoConfigDir,
+    /// usbmuxd has no system BUID to base a new pairing file's on
+    #[error("usbmuxd returned an empty system BUID")]
+    EmptyBuid,
+    /// The operation can't be done while the device is connected over the network
+    #[error("this needs the device to be connected over USB")]
+    UsbRequired,
+    /// The pairing file is encrypted, or is being encrypted, and no passphrase was given
+    #[error("a passphrase is needed for encrypted pairing files")]
+    PassphraseRequired,
+    /// Decryption failed, which also happens if the encrypted file was tampered with
+    #[error("wrong passphrase, or the encrypted pairing file was modified")]
+    WrongPassphrase,
+    /// A step of installing a pairing file into an app failed, the path is on the device
+    #[error("failed to {step} {path}")]
+    Install {
+        step: InstallStep,
+        path: String,
+        #[source]
+        source: IdeviceError,
+    },
+    /// A file written while installing a pairing file read back differently
+    #[error("{path} changed after it was written ({actual} bytes read back, {expected} written)")]
+    InstallMismatch {
+        path: String,
+        expected: usize,
+        actual: usize,
+    },
+    /// There is no backup of the pairing file on the device to restore, the path is on the device
+    #[error("there is no backup of {0} to restore")]
+    NoBackup(String),
+    #[error("{0} is not a backup of the app's pairing file")]
+    NotABackup(String),
+    /// The backup asked for is named like one but isn't on the device
+    #[error("backup {0} does not exist")]
+    MissingBackup(String),
+    /// The app's container has no Documents folder, the bundle ID is given
+    #[error("{0} has no Documents folder, check that it's the app its entry is for")]
+    NoDocuments(String),
+    /// Something that isn't a folder is where the pairing file's path needs one, the path is on the device
+    #[error("{0} is a file, but the app's pairing file path needs a folder there")]
+    NotAFolder(String),
+    /// The app reads the UDID from its pairing file, but it isn't known
+    #[error("{0} needs the device's UDID, and the pairing file doesn't have it")]
+    UdidRequired(String),
+    /// No developer disk image was imported, and none is built into the binary
+    #[error("no developer disk image, import one first")]
+    NoDdi,
+    /// A developer disk image file, or the archive it's in, can't be used
+    #[error("invalid developer disk image {name}: {reason}")]
+    InvalidDdi { name: String, reason: String },
+    /// Work handed to a background task panicked or was cancelled before it finished
+    #[error("a background task stopped before finishing")]
+    Task(#[from] tokio::task::JoinError),
+    /// No imported legacy image fits the device's iOS version, which is given
+    #[error("no developer disk image for iOS {0}, import the DeveloperDiskImage.dmg for it")]
+    NoLegacyDdi(String),
+}
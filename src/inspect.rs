@@ -0,0 +1,168 @@
+// Jackson Coxson
+
+use chrono::{DateTime, Utc};
+use idevice::pairing_file::PairingFile;
+use rsa::{
+    RsaPrivateKey, pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, traits::PublicKeyParts,
+};
+use serde::Serialize;
+use x509_parser::{certificate::X509Certificate, prelude::FromDer, public_key::PublicKey};
+
+/// The parts of a certificate worth showing to a person
+#[derive(Debug, Clone, Serialize)]
+pub struct CertificateSummary {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    pub key_type: String,
+    /// Big endian RSA modulus without leading zeros, used to match private keys
+    #[serde(skip)]
+    rsa_modulus: Option<Vec<u8>>,
+}
+
+/// Everything in a pairing file, plus anything about it that looks wrong
+#[derive(Debug, Clone, Serialize)]
+pub struct PairingFileReport {
+    pub host_id: String,
+    pub system_buid: String,
+    pub wifi_mac_address: String,
+    pub udid: Option<String>,
+    pub has_escrow_bag: bool,
+    pub device_certificate: Option<CertificateSummary>,
+    pub host_certificate: Option<CertificateSummary>,
+    pub root_certificate: Option<CertificateSummary>,
+    pub warnings: Vec<String>,
+}
+
+/// Parses the certificates in a pairing file and checks that they fit together
+pub fn inspect(pairing_file: &PairingFile) -> PairingFileReport {
+    let mut warnings = Vec::new();
+    let now = Utc::now();
+
+    let mut summarize = |name: &str, der: &[u8]| match summarize_certificate(der) {
+        Ok(c) => {
+            if c.not_after < now {
+                warnings.push(format!("{name} expired on {}", c.not_after));
+            }
+            if c.not_before > now {
+                warnings.push(format!("{name} is not valid until {}", c.not_before));
+            }
+            Some(c)
+        }
+        Err(e) => {
+            warnings.push(format!("{name} could not be parsed: {e}"));
+            None
+        }
+    };
+    let device_certificate = summarize("DeviceCertificate", &pairing_file.device_certificate);
+    let host_certificate = summarize("HostCertificate", &pairing_file.host_certificate);
+    let root_certificate = summarize("RootCertificate", &pairing_file.root_certificate);
+
+    if let Some(root) = &root_certificate {
+        for (name, cert) in [
+            ("DeviceCertificate", &device_certificate),
+            ("HostCertificate", &host_certificate),
+        ] {
+            if let Some(cert) = cert
+                && cert.issuer != root.subject
+            {
+                warnings.push(format!(
+                    "{name} was not issued by RootCertificate ({} != {})",
+                    cert.issuer, root.subject
+                ));
+            }
+        }
+    }
+
+    for (key_name, key, cert_name, cert) in [
+        (
+            "HostPrivateKey",
+            &pairing_file.host_private_key,
+            "HostCertificate",
+            &host_certificate,
+        ),
+        (
+            "RootPrivateKey",
+            &pairing_file.root_private_key,
+            "RootCertificate",
+            &root_certificate,
+        ),
+    ] {
+        let Some(modulus) = cert.as_ref().and_then(|c| c.rsa_modulus.as_ref()) else {
+            continue;
+        };
+        match private_key_modulus(key) {
+            Some(m) if &m != modulus => {
+                warnings.push(format!("{key_name} does not belong to {cert_name}"))
+            }
+            Some(_) => {}
+            None => warnings.push(format!("{key_name} is not a readable RSA key")),
+        }
+    }
+
+    if pairing_file.udid.is_none() {
+        warnings.push(
+            "UDID is missing, some apps need it to know which device the file is for".to_string(),
+        );
+    }
+    if pairing_file.escrow_bag.is_empty() {
+        warnings.push("EscrowBag is empty, the device must be unlocked to connect".to_string());
+    }
+
+    PairingFileReport {
+        host_id: pairing_file.host_id.clone(),
+        system_buid: pairing_file.system_buid.clone(),
+        wifi_mac_address: pairing_file.wifi_mac_address.clone(),
+        udid: pairing_file.udid.clone(),
+        has_escrow_bag: !pairing_file.escrow_bag.is_empty(),
+        device_certificate,
+        host_certificate,
+        root_certificate,
+        warnings,
+    }
+}
+
+fn summarize_certificate(der: &[u8]) -> Result<CertificateSummary, String> {
+    let (_, cert) = X509Certificate::from_der(der).map_err(|e| e.to_string())?;
+    let validity = cert.validity();
+
+    let (key_type, rsa_modulus) = match cert.public_key().parsed() {
+        Ok(PublicKey::RSA(rsa)) => (
+            format!("RSA {} bits", rsa.key_size()),
+            Some(strip_leading_zeros(rsa.modulus).to_vec()),
+        ),
+        Ok(PublicKey::EC(ec)) => (format!("EC {} bits", ec.key_size()), None),
+        Ok(PublicKey::DSA(_)) => ("DSA".to_string(), None),
+        _ => (cert.public_key().algorithm.algorithm.to_id_string(), None),
+    };
+
+    Ok(CertificateSummary {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        serial: cert.raw_serial_as_string(),
+        not_before: timestamp(validity.not_before.timestamp()),
+        not_after: timestamp(validity.not_after.timestamp()),
+        key_type,
+        rsa_modulus,
+    })
+}
+
+/// Reads the modulus out of a PEM private key, which may be PKCS#8 or PKCS#1
+fn private_key_modulus(pem: &[u8]) -> Option<Vec<u8>> {
+    let pem = std::str::from_utf8(pem).ok()?;
+    let key = RsaPrivateKey::from_pkcs8_pem(pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+        .ok()?;
+    Some(key.n().to_bytes_be())
+}
+
+fn strip_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+fn timestamp(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs, 0).unwrap_or_default()
+}
@@ -0,0 +1,22 @@
+// Jackson Coxson
+//! Device operations used by idevice_pair, exposed so other tools can drive
+//! the same pairing workflows without the GUI.
+
+pub mod apps;
+pub mod container;
+pub mod ddi;
+pub mod device;
+pub mod discover;
+pub mod encryption;
+mod error;
+pub mod inspect;
+pub mod mount;
+pub mod pairing;
+pub mod settings;
+pub mod vault;
+
+pub use error::Error;
+pub use idevice;
+
+/// Label sent to the device for every connection this crate makes
+pub const LABEL: &str = "idevice_pair";
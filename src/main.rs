@@ -2,31 +2,41 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 use std::{
-    collections::HashMap,
-    net::{IpAddr, SocketAddr},
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+    path::PathBuf,
     str::FromStr,
     thread,
 };
 
 use egui::{Color32, ComboBox, RichText};
 use futures_util::StreamExt;
-use log::error;
-use tokio::io::AsyncWriteExt;
+use log::{error, warn};
 use tokio::sync::mpsc::unbounded_channel;
 
 use idevice::{
-    Idevice, IdeviceError, IdeviceService,
-    house_arrest::HouseArrestClient,
-    installation_proxy::InstallationProxyClient,
-    lockdown::LockdownClient,
+    IdeviceError,
     pairing_file::PairingFile,
-    usbmuxd::{Connection, UsbmuxdAddr, UsbmuxdConnection, UsbmuxdDevice, UsbmuxdListenEvent},
+    usbmuxd::{UsbmuxdAddr, UsbmuxdDevice, UsbmuxdListenEvent},
+};
+use idevice_pair::{
+    Error,
+    apps::{self, AppRegistry, Backup, InstallReport, InstallTarget, InstalledApp, SupportedApp},
+    container::{self, Entry},
+    ddi::{self, DdiCache, ImageKind},
+    device::{self, Device},
+    discover, encryption,
+    inspect::{self, CertificateSummary, PairingFileReport},
+    mount::{self, MountedImage},
+    pairing,
+    settings::{DeviceAccess, Settings},
+    vault::{self, Vault},
 };
 use rfd::FileDialog;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-
-mod discover;
-mod mount;
+use tokio::sync::{
+    mpsc::{UnboundedReceiver, UnboundedSender},
+    watch,
+};
 
 fn main() {
     println!("Startup");
@@ -35,19 +45,27 @@ fn main() {
     let (idevice_sender, mut idevice_receiver) = unbounded_channel();
     idevice_sender.send(IdeviceCommands::GetDevices).unwrap();
 
-    let mut supported_apps = HashMap::new();
-    supported_apps.insert(
-        "SideStore".to_string(),
-        "ALTPairingFile.mobiledevicepairing".to_string(),
-    );
-    supported_apps.insert(
-        "LiveContainer".to_string(),
-        "SideStore/Documents/ALTPairingFile.mobiledevicepairing".to_string(),
-    );
-    supported_apps.insert("Feather".to_string(), "pairingFile.plist".to_string());
-    supported_apps.insert("StikDebug".to_string(), "pairingFile.plist".to_string());
-    supported_apps.insert("Protokolle".to_string(), "pairingFile.plist".to_string());
-    supported_apps.insert("Antrag".to_string(), "pairingFile.plist".to_string());
+    let apps = AppRegistry::load();
+
+    let settings = Settings::load();
+    let (usbmuxd_addr, settings_message) =
+        match device::resolve_usbmuxd_addr(settings.usbmuxd_address()) {
+            Ok(addr) => (addr, None),
+            Err(e) => (
+                UsbmuxdAddr::default(),
+                Some(Err(format!("{e}, using the default"))),
+            ),
+        };
+    // Every task reads the address from here so a change in settings reaches all of them
+    let (addr_sender, addr_receiver) = watch::channel(usbmuxd_addr);
+
+    let vault = Vault::open()
+        .inspect_err(|e| warn!("Pairing files won't be kept in the vault: {e}"))
+        .ok();
+
+    let ddi = DdiCache::open()
+        .inspect_err(|e| warn!("Developer disk images can't be imported: {e}"))
+        .ok();
 
     let app = MyApp {
         devices: None,
@@ -56,20 +74,64 @@ fn main() {
         device_info: None,
         wireless_enabled: None,
         dev_mode_enabled: None,
-        ddi_mounted: None,
+        mounted_images: None,
+        confirm_action: None,
         pairing_file: None,
         pairing_file_message: None,
         pairing_file_string: None,
+        pairing_file_path: None,
+        pairing_file_device: None,
+        vault_source: None,
+        pairing_file_report: None,
+        reveal_private_keys: false,
+        confirm_reveal: false,
         save_error: None,
+        save_encrypted: false,
+        save_as: SaveAs::Format(pairing::Format::Xml),
+        save_udid: false,
+        save_udid_input: "".to_string(),
+        save_passphrase: "".to_string(),
+        save_passphrase_confirm: "".to_string(),
+        locked_file: None,
+        unlock_passphrase: "".to_string(),
+        unlock_error: None,
         installed_apps: None,
         install_res: HashMap::new(),
-        supported_apps,
+        install_batch: Vec::new(),
+        backups: HashMap::new(),
+        container_res: HashMap::new(),
+        browser: None,
+        apps,
+        show_apps: false,
+        show_convert: false,
+        convert_input: None,
+        convert_passphrase: "".to_string(),
+        convert_target: SaveAs::Format(pairing::Format::Xml),
+        convert_udid: "".to_string(),
+        convert_message: None,
+        app_form: AppForm::default(),
+        apps_message: None,
         validate_res: None,
         validating: false,
         validation_ip_input: "".to_string(),
         gui_recv,
         idevice_sender: idevice_sender.clone(),
         show_logs: false,
+        usbmuxd_address_input: settings.usbmuxd_address.clone(),
+        settings,
+        settings_message,
+        show_settings: false,
+        addr_sender,
+        vault,
+        show_vault: false,
+        vault_filter: "".to_string(),
+        vault_message: None,
+        confirm_vault_remove: None,
+        ddi,
+        show_ddi: false,
+        ddi_importing: false,
+        ddi_checking: false,
+        ddi_message: None,
     };
 
     let mut options = eframe::NativeOptions::default();
@@ -96,8 +158,7 @@ fn main() {
     #[cfg(not(target_os = "macos"))]
     {
         let icon_bytes: &[u8] = include_bytes!("../icon.png");
-        let d = eframe::icon_data::from_png_bytes(icon_bytes)
-            .expect("The icon data must be valid");
+        let d = eframe::icon_data::from_png_bytes(icon_bytes).expect("The icon data must be valid");
         options.viewport.icon = Some(std::sync::Arc::new(d));
     }
 
@@ -109,10 +170,17 @@ fn main() {
 
     let discover_sender = idevice_sender.clone();
     rt.spawn(async move {
-        discover::start_discover(discover_sender).await;
+        if let Err(e) = discover::start_discover(|ip, mac| {
+            let _ = discover_sender.send(IdeviceCommands::DiscoveredDevice((ip, mac)));
+        })
+        .await
+        {
+            warn!("Devices on the network won't be found: {e}");
+        }
     });
 
     let idevice_sender_listen = idevice_sender.clone();
+    let mut addr_listen = addr_receiver.clone();
     thread::spawn(move || {
         let rt_local = tokio::runtime::Builder::new_current_thread()
             .enable_all()
@@ -120,23 +188,27 @@ fn main() {
             .unwrap();
         rt_local.block_on(async move {
             loop {
-                match UsbmuxdConnection::default().await {
+                let addr = addr_listen.borrow_and_update().clone();
+                match addr.connect(0).await {
                     Ok(mut uc) => match uc.listen().await {
-                        Ok(mut stream) => {
-                            while let Some(evt) = stream.next().await {
-                                match evt {
-                                    Ok(UsbmuxdListenEvent::Connected(_))
-                                    | Ok(UsbmuxdListenEvent::Disconnected(_)) => {
-                                        let _ = idevice_sender_listen
-                                            .send(IdeviceCommands::GetDevices);
-                                    }
-                                    Err(e) => {
-                                        log::warn!("usbmuxd listen error: {e:?}");
-                                        break;
-                                    }
+                        Ok(mut stream) => loop {
+                            let evt = tokio::select! {
+                                evt = stream.next() => evt,
+                                // Start over on the new address
+                                _ = addr_listen.changed() => break,
+                            };
+                            match evt {
+                                Some(Ok(UsbmuxdListenEvent::Connected(_)))
+                                | Some(Ok(UsbmuxdListenEvent::Disconnected(_))) => {
+                                    let _ = idevice_sender_listen.send(IdeviceCommands::GetDevices);
                                 }
+                                Some(Err(e)) => {
+                                    log::warn!("usbmuxd listen error: {e:?}");
+                                    break;
+                                }
+                                None => break,
                             }
-                        }
+                        },
                         Err(e) => {
                             log::warn!("Failed to start usbmuxd listen: {e:?}");
                         }
@@ -153,502 +225,1631 @@ fn main() {
     rt.spawn(async move {
         let gui_sender = gui_sender.clone();
         let mut discovered_devices: HashMap<String, IpAddr> = HashMap::new(); // mac, IP
-        'main: while let Some(command) = idevice_receiver.recv().await {
+        while let Some(command) = idevice_receiver.recv().await {
+            let addr = addr_receiver.borrow().clone();
             match command {
-                IdeviceCommands::GetDevices => {
-                    // Connect to usbmuxd
-                    let mut uc = match UsbmuxdConnection::default().await {
-                        Ok(u) => u,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap();
-                            continue;
-                        }
-                    };
-
-                    match uc.get_devices().await {
-                        Ok(devs) => {
-                            let devs: Vec<UsbmuxdDevice> = devs
-                                .into_iter()
-                                .filter(|x| x.connection_type == Connection::Usb)
-                                .collect();
-
-                            // We have to manually iterate to use async
-                            let mut selections = HashMap::new();
-                            for dev in devs {
-                                let p = dev.to_provider(UsbmuxdAddr::default(), "idevice_pair");
-                                let mut lc = match LockdownClient::connect(&p).await {
-                                    Ok(l) => l,
-                                    Err(e) => {
-                                        error!("Failed to connect to lockdown: {e:?}");
-                                        continue;
-                                    }
-                                };
-                                let values = match lc.get_value(None, None).await {
-                                    Ok(v) => v,
-                                    Err(e) => {
-                                        error!("Failed to get lockdown values: {e:?}");
-                                        continue;
-                                    }
-                                };
-
-                                // Get device name for selection
-                                let device_name = match values.as_dictionary().and_then(|x|x.get("DeviceName")).and_then(|x|x.as_string()) {
-                                    Some(n) => n.to_string(),
-                                    _ => {
-                                        continue;
-                                    }
-                                };
-                                selections.insert(device_name, dev);
-                            }
-
-                            gui_sender.send(GuiCommands::Devices(selections)).unwrap();
-                        }
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::GetDevicesFailure(e)).unwrap();
+                IdeviceCommands::GetDevices => match device::get_devices(&addr).await {
+                    Ok(selections) => gui_sender.send(GuiCommands::Devices(selections)).unwrap(),
+                    Err(Error::NoUsbmuxd(e)) => gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap(),
+                    Err(e) => gui_sender.send(GuiCommands::GetDevicesFailure(e)).unwrap(),
+                },
+                IdeviceCommands::CheckWireless(dev) => {
+                    match device::wireless_status(&addr, &dev).await {
+                        Err(Error::NoUsbmuxd(e)) => {
+                            gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap()
                         }
+                        res => gui_sender.send(GuiCommands::Wireless(res)).unwrap(),
                     }
                 }
-                IdeviceCommands::EnableWireless(dev) => {
-                    // Connect to usbmuxd
-                    let mut uc = match UsbmuxdConnection::default().await {
-                        Ok(u) => u,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap();
-                            continue;
-                        }
-                    };
-
-                    let p = dev.to_provider(UsbmuxdAddr::default(), "idevice_pair");
-                    let mut lc = match LockdownClient::connect(&p).await {
-                        Ok(l) => l,
-                        Err(e) => {
-                            gui_sender
-                                .send(GuiCommands::EnableWirelessFailure(e))
-                                .unwrap();
-                            continue;
-                        }
-                    };
-
-                    let pairing_file = match uc.get_pair_record(&p.udid).await {
-                        Ok(p) => p,
-                        Err(e) => {
-                            gui_sender
-                                .send(GuiCommands::EnableWirelessFailure(e))
-                                .unwrap();
-                            continue;
+                IdeviceCommands::SetWireless((dev, enabled)) => {
+                    match device::set_wireless(&addr, &dev, enabled).await {
+                        Err(Error::NoUsbmuxd(e)) => {
+                            gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap()
                         }
-                    };
-
-                    if let Err(e) = lc.start_session(&pairing_file).await {
-                        gui_sender
-                            .send(GuiCommands::EnableWirelessFailure(e))
-                            .unwrap();
-                        continue;
-                    }
-
-                    // Set the value
-                    if let Err(e) = lc
-                        .set_value(
-                            "EnableWifiDebugging",
-                            true.into(),
-                            Some("com.apple.mobile.wireless_lockdown"),
-                        )
-                        .await
-                    {
-                        gui_sender
-                            .send(GuiCommands::EnableWirelessFailure(e))
-                            .unwrap();
-                    } else {
-                        gui_sender.send(GuiCommands::EnabledWireless).unwrap();
+                        res => gui_sender.send(GuiCommands::Wireless(res)).unwrap(),
                     }
                 }
-                IdeviceCommands::CheckDevMode(dev) => {
-                    // Connect to usbmuxd
-                    let mut uc = match UsbmuxdConnection::default().await {
-                        Ok(u) => u,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap();
-                            continue;
-                        }
+                IdeviceCommands::CheckDevMode(dev) => match device::dev_mode_status(&addr, &dev)
+                    .await
+                {
+                    Err(Error::NoUsbmuxd(e)) => gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap(),
+                    res => gui_sender.send(GuiCommands::DevMode(res)).unwrap(),
+                },
+                IdeviceCommands::CheckMount(dev) => {
+                    gui_sender
+                        .send(GuiCommands::MountRes(
+                            mount::mounted_images(&addr, &dev).await,
+                        ))
+                        .unwrap();
+                }
+                IdeviceCommands::AutoMount(dev) => {
+                    let res = match mount::auto_mount(&addr, &dev).await {
+                        Ok(()) => mount::mounted_images(&addr, &dev).await,
+                        Err(e) => Err(e),
                     };
-
-                    let p = dev.to_provider(UsbmuxdAddr::default(), "idevice_pair");
-                    let pairing_file = match uc.get_pair_record(&p.udid).await {
-                        Ok(p) => p,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::DevMode(Err(e))).unwrap();
-                            continue;
-                        }
+                    gui_sender.send(GuiCommands::MountRes(res)).unwrap();
+                }
+                IdeviceCommands::Unmount(dev) => {
+                    let res = match mount::unmount_all(&addr, &dev).await {
+                        Ok(_) => mount::mounted_images(&addr, &dev).await,
+                        Err(e) => Err(e),
                     };
-
-                    let mut lc = match LockdownClient::connect(&p).await {
-                        Ok(l) => l,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::DevMode(Err(e))).unwrap();
-                            continue;
-                        }
+                    gui_sender.send(GuiCommands::MountRes(res)).unwrap();
+                }
+                IdeviceCommands::Remount(dev) => {
+                    let res = match mount::remount(&addr, &dev).await {
+                        Ok(()) => mount::mounted_images(&addr, &dev).await,
+                        Err(e) => Err(e),
                     };
-
-                    if let Err(e) = lc.start_session(&pairing_file).await {
-                        gui_sender.send(GuiCommands::DevMode(Err(e))).unwrap();
-                        continue;
-                    }
-
-                    let v = match lc
-                        .get_value(
-                            Some("DeveloperModeStatus"),
-                            Some("com.apple.security.mac.amfi"),
-                        )
-                        .await
-                    {
-                        Ok(v) => v,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::DevMode(Err(e))).unwrap();
-                            continue;
+                    gui_sender.send(GuiCommands::MountRes(res)).unwrap();
+                }
+                IdeviceCommands::LoadPairingFile((dev, name)) => {
+                    match pairing::load(&addr, &dev).await {
+                        Err(Error::NoUsbmuxd(e)) => {
+                            gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap()
                         }
-                    };
-
-                    match v.as_boolean() {
-                        Some(b) => {
-                            gui_sender.send(GuiCommands::DevMode(Ok(b))).unwrap();
-                            continue;
+                        res => gui_sender
+                            .send(GuiCommands::PairingFile((
+                                vault::Source::Loaded,
+                                dev.udid,
+                                name,
+                                res,
+                            )))
+                            .unwrap(),
+                    }
+                }
+                IdeviceCommands::GeneratePairingFile((dev, name)) => {
+                    match pairing::generate(&addr, &dev).await {
+                        Err(Error::NoUsbmuxd(e)) => {
+                            gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap()
                         }
+                        res => gui_sender
+                            .send(GuiCommands::PairingFile((
+                                vault::Source::Generated,
+                                dev.udid,
+                                name,
+                                res,
+                            )))
+                            .unwrap(),
+                    }
+                }
+                IdeviceCommands::Validate((ip, pairing_file)) => {
+                    let ip = match ip.or_else(|| {
+                        discovered_devices
+                            .get(&pairing_file.wifi_mac_address)
+                            .copied()
+                    }) {
+                        Some(i) => i,
                         None => {
                             gui_sender
-                                .send(GuiCommands::DevMode(Err(IdeviceError::UnexpectedResponse)))
+                                .send(GuiCommands::Validated((
+                                    pairing_file.host_id,
+                                    None,
+                                    Err(IdeviceError::DeviceNotFound.into()),
+                                )))
                                 .unwrap();
                             continue;
                         }
-                    }
-                }
-                IdeviceCommands::AutoMount(dev) => match mount::auto_mount(dev).await {
-                    Ok(_) => gui_sender.send(GuiCommands::MountRes(Ok(()))).unwrap(),
-                    Err(e) => gui_sender.send(GuiCommands::MountRes(Err(e))).unwrap(),
-                },
-                IdeviceCommands::LoadPairingFile(dev) => {
-                    // Connect to usbmuxd
-                    let mut uc = match UsbmuxdConnection::default().await {
-                        Ok(u) => u,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap();
-                            continue;
-                        }
                     };
 
-                    let mut pairing_file = match uc.get_pair_record(&dev.udid).await {
-                        Ok(p) => p,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::PairingFile(Err(e))).unwrap();
-                            continue;
-                        }
-                    };
-                    pairing_file.udid = Some(dev.udid);
-
                     gui_sender
-                        .send(GuiCommands::PairingFile(Ok(pairing_file)))
+                        .send(GuiCommands::Validated((
+                            pairing_file.host_id.clone(),
+                            Some(ip),
+                            pairing::validate(ip, &pairing_file).await,
+                        )))
                         .unwrap();
                 }
-                IdeviceCommands::GeneratePairingFile(dev) => {
-                    // Connect to usbmuxd
-                    let mut uc = match UsbmuxdConnection::default().await {
-                        Ok(u) => u,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::NoUsbmuxd(e)).unwrap();
-                            continue;
-                        }
-                    };
-
-                    let p = dev.to_provider(UsbmuxdAddr::default(), "idevice_pair");
-
-                    let mut lc = match LockdownClient::connect(&p).await {
-                        Ok(l) => l,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::PairingFile(Err(e))).unwrap();
-                            continue;
-                        }
-                    };
-
-                    let buid = match uc.get_buid().await {
-                        Ok(b) => b,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::PairingFile(Err(e))).unwrap();
-                            continue;
-                        }
-                    };
-
-                    // Modify it slightly so iOS doesn't invalidate the one connected right now.
-                    let mut buid: Vec<char> = buid.chars().collect();
-                    buid[0] = if buid[0] == 'F' { 'A' } else { 'F' };
-                    let buid: String = buid.into_iter().collect();
-
-                    let id = uuid::Uuid::new_v4().to_string().to_uppercase();
-                    let mut pairing_file = match lc.pair(id, buid).await {
-                        Ok(p) => p,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::PairingFile(Err(e))).unwrap();
-                            continue;
-                        }
-                    };
-
-                    pairing_file.udid = Some(dev.udid.clone());
-
+                IdeviceCommands::InstalledApps((dev, supported_apps)) => {
                     gui_sender
-                        .send(GuiCommands::PairingFile(Ok(pairing_file)))
+                        .send(GuiCommands::InstalledApps(
+                            apps::installed_apps(&addr, &dev, &supported_apps).await,
+                        ))
                         .unwrap();
                 }
-                IdeviceCommands::Validate((ip, pairing_file)) => {
-                    let ip: IpAddr = match ip {
-                        Some(i) => i,
-                        None => {
-                            if let Some(ip) = discovered_devices.get(&pairing_file.wifi_mac_address)
-                            {
-                                *ip
-                            } else {
-                                gui_sender
-                                    .send(GuiCommands::Validated(Err(IdeviceError::DeviceNotFound)))
-                                    .unwrap();
-                                continue;
-                            }
-                        }
-                    };
-
-                    let stream =
-                        match tokio::net::TcpStream::connect(SocketAddr::new(ip, 62078)).await {
-                            Ok(s) => s,
-                            Err(e) => {
-                                gui_sender
-                                    .send(GuiCommands::Validated(Err(IdeviceError::Socket(e))))
-                                    .unwrap();
-                                continue;
-                            }
-                        };
-
-                    let mut lc =
-                        LockdownClient::new(Idevice::new(Box::new(stream), "idevice_pair"));
-                    match lc.start_session(&pairing_file).await {
-                        Ok(_) => gui_sender.send(GuiCommands::Validated(Ok(()))).unwrap(),
-                        Err(e) => gui_sender.send(GuiCommands::Validated(Err(e))).unwrap(),
-                    }
+                IdeviceCommands::InstallPairingFile((dev, bundle_id, app, pairing_file)) => {
+                    let res =
+                        apps::install_pairing_file(&addr, &dev, &bundle_id, &app, &pairing_file)
+                            .await;
+                    gui_sender
+                        .send(GuiCommands::InstallPairingFile((
+                            pairing_file.host_id,
+                            app.name,
+                            bundle_id,
+                            res,
+                        )))
+                        .unwrap();
                 }
-                IdeviceCommands::InstalledApps((dev, desired_apps)) => {
-                    let p = dev.to_provider(UsbmuxdAddr::default(), "idevice_pair");
-                    let mut ic = match InstallationProxyClient::connect(&p).await {
-                        Ok(i) => i,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::InstalledApps(Err(e))).unwrap();
-                            continue;
-                        }
-                    };
-                    let installed_apps = match ic.get_apps(Some("User"), None).await {
-                        Ok(a) => a,
-                        Err(e) => {
-                            gui_sender.send(GuiCommands::InstalledApps(Err(e))).unwrap();
-                            continue;
-                        }
-                    };
-
-                    let mut installed = HashMap::new();
-                    for (bundle_id, app) in installed_apps {
-                        match app
-                            .as_dictionary()
-                            .and_then(|x| x.get("CFBundleDisplayName").and_then(|x| x.as_string()))
-                        {
-                            Some(n) => {
-                                if desired_apps.contains(&n.to_string()) {
-                                    installed.insert(n.to_string(), bundle_id);
-                                }
-                            }
-                            None => {
-                                gui_sender
-                                    .send(GuiCommands::InstalledApps(Err(
-                                        IdeviceError::UnexpectedResponse,
-                                    )))
-                                    .unwrap();
-                                continue 'main;
-                            }
-                        };
-                    }
+                IdeviceCommands::ListBackups((dev, bundle_id, app)) => {
+                    let res = apps::list_backups(&addr, &dev, &bundle_id, &app).await;
                     gui_sender
-                        .send(GuiCommands::InstalledApps(Ok(installed)))
+                        .send(GuiCommands::Backups((bundle_id, res)))
                         .unwrap();
                 }
-                IdeviceCommands::InstallPairingFile((dev, name, bundle_id, path, pairing_file)) => {
-                    let p = dev.to_provider(UsbmuxdAddr::default(), "idevice_pair");
-                    let hc = match HouseArrestClient::connect(&p).await {
-                        Ok(h) => h,
-                        Err(e) => {
-                            gui_sender
-                                .send(GuiCommands::InstallPairingFile((name, Err(e))))
-                                .unwrap();
-                            continue;
-                        }
-                    };
-
-                    let mut ac = match hc.vend_documents(bundle_id).await {
-                        Ok(a) => a,
-                        Err(e) => {
-                            gui_sender
-                                .send(GuiCommands::InstallPairingFile((name, Err(e))))
-                                .unwrap();
-                            continue;
-                        }
-                    };
-
-                    let mut f = match ac
-                        .open(
-                            format!("/Documents/{path}"),
-                            idevice::afc::opcode::AfcFopenMode::Wr,
-                        )
+                IdeviceCommands::RemovePairingFile((dev, bundle_id, app)) => {
+                    let res = apps::remove_pairing_file(&addr, &dev, &bundle_id, &app)
                         .await
-                    {
-                        Ok(f) => f,
-                        Err(e) => {
-                            gui_sender
-                                .send(GuiCommands::InstallPairingFile((name, Err(e))))
-                                .unwrap();
-                            continue;
-                        }
-                    };
-
-                    match f.write(&pairing_file.serialize().unwrap()).await {
-                        Ok(_) => {
-                            gui_sender
-                                .send(GuiCommands::InstallPairingFile((name, Ok(()))))
-                                .unwrap();
-                            continue;
-                        }
-                        Err(e) => {
+                        .map(|backup| match backup {
+                            Some(backup) => format!("Removed, kept at {backup}"),
+                            None => "There was no pairing file to remove".to_string(),
+                        });
+                    gui_sender
+                        .send(GuiCommands::ContainerChanged((bundle_id.clone(), res)))
+                        .unwrap();
+                    let res = apps::list_backups(&addr, &dev, &bundle_id, &app).await;
+                    gui_sender
+                        .send(GuiCommands::Backups((bundle_id, res)))
+                        .unwrap();
+                }
+                IdeviceCommands::RestorePairingFile((dev, bundle_id, app, backup)) => {
+                    let res = apps::restore_pairing_file(
+                        &addr,
+                        &dev,
+                        &bundle_id,
+                        &app,
+                        backup.as_deref(),
+                    )
+                    .await
+                    .map(|report| match report.backup {
+                        Some(backup) => format!("Restored, replaced file kept at {backup}"),
+                        None => "Restored".to_string(),
+                    });
+                    gui_sender
+                        .send(GuiCommands::ContainerChanged((bundle_id.clone(), res)))
+                        .unwrap();
+                    let res = apps::list_backups(&addr, &dev, &bundle_id, &app).await;
+                    gui_sender
+                        .send(GuiCommands::Backups((bundle_id, res)))
+                        .unwrap();
+                }
+                IdeviceCommands::ListContainer((dev, bundle_id, dir)) => {
+                    let res = container::list_dir(&addr, &dev, &bundle_id, &dir).await;
+                    gui_sender
+                        .send(GuiCommands::ContainerDir((bundle_id, dir, res)))
+                        .unwrap();
+                }
+                IdeviceCommands::ReadContainerFile((dev, bundle_id, path)) => {
+                    let res = container::read_file(&addr, &dev, &bundle_id, &path).await;
+                    gui_sender
+                        .send(GuiCommands::ContainerFile((bundle_id, path, res)))
+                        .unwrap();
+                }
+                IdeviceCommands::InstallToAll((dev, targets, pairing_file)) => {
+                    // A batch takes a while, so other commands keep being handled meanwhile
+                    let gui_sender = gui_sender.clone();
+                    tokio::spawn(async move {
+                        let mut installs = apps::install_all(&addr, &dev, targets, &pairing_file);
+                        while let Some((target, res)) = installs.next().await {
                             gui_sender
-                                .send(GuiCommands::InstallPairingFile((name, Err(IdeviceError::Socket(e)))))
+                                .send(GuiCommands::InstallPairingFile((
+                                    pairing_file.host_id.clone(),
+                                    target.app.name,
+                                    target.bundle_id,
+                                    res,
+                                )))
                                 .unwrap();
-                            continue;
                         }
-                    }
-                }                IdeviceCommands::DiscoveredDevice((ip, mac)) => {
+                    });
+                }
+                IdeviceCommands::DiscoveredDevice((ip, mac)) => {
                     discovered_devices.insert(mac, ip);
+                }
+                IdeviceCommands::GetDeviceInfo(dev) => match device::device_info(&addr, &dev).await
+                {
+                    Ok(device_info) => gui_sender
+                        .send(GuiCommands::DeviceInfo(device_info))
+                        .unwrap(),
+                    Err(e) => error!("Failed to get device info: {e:?}"),
                 },
-                IdeviceCommands::GetDeviceInfo(dev) => {
-                    let p = dev.to_provider(UsbmuxdAddr::default(), "idevice_pair");
-                    let mut lc = match LockdownClient::connect(&p).await {
-                        Ok(l) => l,
-                        Err(e) => {
-                            error!("Failed to connect to lockdown: {e:?}");
-                            continue;
-                        }
-                    };
-                    
-                    let values = match lc.get_value(None, None).await {
-                        Ok(v) => v,
-                        Err(e) => {
-                            error!("Failed to get lockdown values: {e:?}");
-                            continue;
-                        }
-                    };
-
-                    let values = match values.as_dictionary() {
-                        Some(v) => v,
-                        None => {
-                            error!("Values was not a dictionary");
-                            continue;
-                        }
-                    };
-
-                    let mut device_info = Vec::with_capacity(5);
-
-                    // Fixed order of fields in reverse order
-                    let fields = [
-                        ("Device Name", "DeviceName"),
-                        ("Model", "ProductType"),
-                        ("iOS Version", "ProductVersion"),
-                        ("Build Number", "BuildVersion"), 
-                        ("UDID", "UniqueDeviceID"),
-                    ];
-
-                    for (display_name, key) in fields.iter() {
-                        if let Some(plist::Value::String(value)) = values.get(key) {
-                            device_info.push((display_name.to_string(), value.clone()));
-                        }
-                    }
-
-                    gui_sender.send(GuiCommands::DeviceInfo(device_info)).unwrap();
+                IdeviceCommands::ImportDdi((cache_dir, path)) => {
+                    // Reading and hashing a whole image takes a while, so it's kept off the async
+                    // threads and doesn't hold up other commands
+                    let gui_sender = gui_sender.clone();
+                    tokio::spawn(async move {
+                        let res = tokio::task::spawn_blocking(move || {
+                            DdiCache::open_at(cache_dir)?.import(&path)
+                        })
+                        .await
+                        .unwrap_or_else(|e| Err(e.into()));
+                        gui_sender.send(GuiCommands::DdiImported(res)).unwrap();
+                    });
+                }
+                IdeviceCommands::CheckDdi((cache_dir, kind)) => {
+                    // Hashes the whole image, like an import
+                    let gui_sender = gui_sender.clone();
+                    tokio::spawn(async move {
+                        let checked = kind.clone();
+                        let res = tokio::task::spawn_blocking(move || {
+                            DdiCache::open_at(cache_dir)?.check_unchanged(&checked)
+                        })
+                        .await
+                        .unwrap_or_else(|e| Err(e.into()));
+                        gui_sender
+                            .send(GuiCommands::DdiChecked((kind, res)))
+                            .unwrap();
+                    });
                 }
             };
         }
         eprintln!("Exited idevice loop!!");
     });
 
-    eframe::run_native(&format!("idevice pair v{}", env!("CARGO_PKG_VERSION")), options, Box::new(|_| Ok(Box::new(app)))).unwrap();
+    eframe::run_native(
+        &format!("idevice pair v{}", env!("CARGO_PKG_VERSION")),
+        options,
+        Box::new(|_| Ok(Box::new(app))),
+    )
+    .unwrap();
 }
 
 enum GuiCommands {
     NoUsbmuxd(IdeviceError),
-    GetDevicesFailure(IdeviceError),
-    Devices(HashMap<String, UsbmuxdDevice>),
+    GetDevicesFailure(Error),
+    Devices(BTreeMap<String, Device>),
     DeviceInfo(Vec<(String, String)>),
-    EnabledWireless,
-    EnableWirelessFailure(IdeviceError),
-    DevMode(Result<bool, IdeviceError>),
-    MountRes(Result<(), IdeviceError>),
-    PairingFile(Result<PairingFile, IdeviceError>),
-    Validated(Result<(), IdeviceError>),
-    InstalledApps(Result<HashMap<String, String>, IdeviceError>),
-    InstallPairingFile((String, Result<(), IdeviceError>)), // name
+    Wireless(Result<bool, Error>), // whether it's on
+    DevMode(Result<bool, Error>),
+    MountRes(Result<Vec<MountedImage>, Error>),
+    // how it was made, and the UDID and name of the device it's from
+    PairingFile(
+        (
+            vault::Source,
+            String,
+            Option<String>,
+            Result<PairingFile, Error>,
+        ),
+    ),
+    Validated((String, Option<IpAddr>, Result<(), Error>)), // host id, ip
+    InstalledApps(Result<HashMap<String, Vec<InstalledApp>>, Error>),
+    InstallPairingFile((String, String, String, Result<InstallReport, Error>)), // host id, name, bundle id
+    Backups((String, Result<Vec<Backup>, Error>)),                              // bundle id
+    ContainerChanged((String, Result<String, Error>)), // bundle id, what was done
+    ContainerDir((String, String, Result<Vec<Entry>, Error>)), // bundle id, dir
+    ContainerFile((String, String, Result<Vec<u8>, Error>)), // bundle id, path
+    DdiImported(Result<Vec<ImageKind>, Error>),
+    DdiChecked((ImageKind, Result<(), Error>)),
 }
 
 enum IdeviceCommands {
     GetDevices,
-    EnableWireless(UsbmuxdDevice),
+    CheckWireless(UsbmuxdDevice),
+    SetWireless((UsbmuxdDevice, bool)),
     CheckDevMode(UsbmuxdDevice),
+    CheckMount(UsbmuxdDevice),
     AutoMount(UsbmuxdDevice),
-    LoadPairingFile(UsbmuxdDevice),
-    GeneratePairingFile(UsbmuxdDevice),
+    Unmount(UsbmuxdDevice),
+    Remount(UsbmuxdDevice),
+    LoadPairingFile((UsbmuxdDevice, Option<String>)), // dev, name
+    GeneratePairingFile((UsbmuxdDevice, Option<String>)),
     GetDeviceInfo(UsbmuxdDevice),
     Validate((Option<IpAddr>, PairingFile)),
-    InstalledApps((UsbmuxdDevice, Vec<String>)),
-    InstallPairingFile((UsbmuxdDevice, String, String, String, PairingFile)), // dev, name, b_id, install path, pf
-    DiscoveredDevice((IpAddr, String)),                                       // ip, mac
+    InstalledApps((UsbmuxdDevice, Vec<SupportedApp>)),
+    InstallPairingFile((UsbmuxdDevice, String, SupportedApp, PairingFile)), // dev, b_id, app, pf
+    InstallToAll((UsbmuxdDevice, Vec<InstallTarget>, PairingFile)),
+    ListBackups((UsbmuxdDevice, String, SupportedApp)), // dev, bundle id, app
+    RemovePairingFile((UsbmuxdDevice, String, SupportedApp)), // dev, bundle id, app
+    RestorePairingFile((UsbmuxdDevice, String, SupportedApp, Option<String>)), // None is the newest
+    ListContainer((UsbmuxdDevice, String, String)),     // dev, bundle id, dir
+    ReadContainerFile((UsbmuxdDevice, String, String)), // dev, bundle id, path
+    DiscoveredDevice((IpAddr, String)),                 // ip, mac
+    ImportDdi((PathBuf, PathBuf)),                      // cache dir, folder or archive
+    CheckDdi((PathBuf, ImageKind)),                     // cache dir, image
+}
+
+/// A change to the selected device, made once confirmed
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DeviceAction {
+    EnableWireless,
+    DisableWireless,
+    Mount,
+    Unmount,
+    Remount,
+    #[cfg(feature = "generate")]
+    Generate,
+    Install((String, String)), // app name, bundle id
+    InstallToAll(Vec<InstallTarget>),
+    Remove((String, String)),                  // app name, bundle id
+    Restore((String, String, Option<String>)), // app name, bundle id, backup, None is the newest
+}
+
+impl DeviceAction {
+    fn label(&self) -> &'static str {
+        match self {
+            DeviceAction::EnableWireless => "Turn On Wireless Debugging",
+            DeviceAction::DisableWireless => "Turn Off Wireless Debugging",
+            DeviceAction::Mount => "Mount Developer Disk Image",
+            DeviceAction::Unmount => "Unmount Developer Disk Image",
+            DeviceAction::Remount => "Remount Developer Disk Image",
+            #[cfg(feature = "generate")]
+            DeviceAction::Generate => "Generate Pairing File",
+            DeviceAction::Install(_) => "Install Pairing File",
+            DeviceAction::InstallToAll(_) => "Install Pairing File to All Apps",
+            DeviceAction::Remove(_) => "Remove Pairing File",
+            DeviceAction::Restore(_) => "Restore Pairing File",
+        }
+    }
+
+    /// What the change means for the device, shown before it's confirmed
+    fn consequence(&self) -> String {
+        match self {
+            DeviceAction::EnableWireless => {
+                "The device will accept connections over the network from computers it's paired with.".to_string()
+            }
+            DeviceAction::DisableWireless => {
+                "Apps using a pairing file over Wi-Fi, like StikDebug, won't reach the device until it's turned on again.".to_string()
+            }
+            DeviceAction::Mount => {
+                "The developer disk image stays mounted until the device restarts.".to_string()
+            }
+            DeviceAction::Unmount => {
+                "Debugging, and apps that need the image like StikDebug, stop working until it's mounted again.".to_string()
+            }
+            DeviceAction::Remount => {
+                "The mounted image is replaced with the one for the device's iOS version, which fixes a stale or mismatched image without a restart.".to_string()
+            }
+            #[cfg(feature = "generate")]
+            DeviceAction::Generate => {
+                "The device pairs with this computer again, which may stop older pairing files for it from working.".to_string()
+            }
+            DeviceAction::Install((name, bundle_id)) => format!(
+                "The pairing file in {name} ({bundle_id}) is replaced, the one it had is kept as a backup."
+            ),
+            DeviceAction::InstallToAll(targets) => format!(
+                "The pairing file is installed into {} apps, the files they had are kept as backups.",
+                targets.len()
+            ),
+            DeviceAction::Remove((name, bundle_id)) => format!(
+                "{name} ({bundle_id}) has no pairing file until one is installed or restored. The removed one is kept as a backup."
+            ),
+            DeviceAction::Restore((name, bundle_id, backup)) => format!(
+                "The pairing file in {name} ({bundle_id}) is replaced with {}, the one it had is kept as a backup in turn.",
+                backup.as_deref().unwrap_or("the newest backup")
+            ),
+        }
+    }
+}
+
+/// How "Save to File" writes the pairing file when it isn't encrypted
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SaveAs {
+    Format(pairing::Format),
+    /// The way the app with this name in the registry reads it
+    App(String),
+}
+
+impl std::fmt::Display for SaveAs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveAs::Format(format) => write!(f, "{format} plist"),
+            SaveAs::App(name) => write!(f, "For {name}"),
+        }
+    }
 }
 
+/// The app being added or edited in the apps window
+#[derive(Default)]
+struct AppForm {
+    name: String,
+    bundle_ids: String, // comma separated
+    path: String,
+    format: pairing::Format,
+    udid: bool,
+}
+
+impl From<&SupportedApp> for AppForm {
+    fn from(app: &SupportedApp) -> Self {
+        Self {
+            name: app.name.clone(),
+            bundle_ids: app.bundle_ids.join(", "),
+            path: app.path.clone(),
+            format: app.format,
+            udid: app.udid,
+        }
+    }
+}
+
+impl AppForm {
+    fn to_app(&self) -> Result<SupportedApp, String> {
+        let name = self.name.trim();
+        let path = self.path.trim().trim_start_matches('/');
+        if name.is_empty() {
+            return Err("Enter the app's name".to_string());
+        }
+        if path.is_empty() {
+            return Err("Enter where the pairing file goes".to_string());
+        }
+        Ok(SupportedApp {
+            name: name.to_string(),
+            bundle_ids: self
+                .bundle_ids
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect(),
+            path: path.to_string(),
+            format: self.format,
+            udid: self.udid,
+        })
+    }
+}
+
+/// The app container being looked through in the files window
+struct Browser {
+    dev: UsbmuxdDevice,
+    bundle_id: String,
+    app: String,
+    dir: String,
+    entries: Option<Result<Vec<Entry>, Error>>,
+    reading: Option<(String, bool)>, // path, whether it's being downloaded rather than previewed
+    preview: Option<(String, Option<String>)>, // path, the plist as XML if it is one
+    message: Option<Result<String, String>>,
+}
+
+impl Browser {
+    fn new(dev: UsbmuxdDevice, bundle_id: String, app: String) -> Self {
+        Self {
+            dev,
+            bundle_id,
+            app,
+            dir: container::DOCUMENTS.to_string(),
+            entries: None,
+            reading: None,
+            preview: None,
+            message: None,
+        }
+    }
+
+    fn open_dir(&mut self, sender: &UnboundedSender<IdeviceCommands>, dir: String) {
+        self.dir = dir;
+        self.entries = None;
+        sender
+            .send(IdeviceCommands::ListContainer((
+                self.dev.clone(),
+                self.bundle_id.clone(),
+                self.dir.clone(),
+            )))
+            .unwrap();
+    }
+
+    fn read(&mut self, sender: &UnboundedSender<IdeviceCommands>, path: String, download: bool) {
+        self.reading = Some((path.clone(), download));
+        self.message = None;
+        sender
+            .send(IdeviceCommands::ReadContainerFile((
+                self.dev.clone(),
+                self.bundle_id.clone(),
+                path,
+            )))
+            .unwrap();
+    }
+
+    /// Previews or saves a file that was read, if it's still the one wanted
+    fn file_read(&mut self, path: String, res: Result<Vec<u8>, Error>) {
+        let Some((_, download)) = self.reading.take_if(|x| x.0 == path) else {
+            return;
+        };
+        let bytes = match res {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.message = Some(Err(format!("Failed to read {path}: {}", error_chain(&e))));
+                return;
+            }
+        };
+        if !download {
+            self.preview = Some((path, container::preview(&bytes)));
+            return;
+        }
+        let name = path.rsplit('/').next().unwrap_or_default();
+        if let Some(p) = FileDialog::new()
+            .set_can_create_directories(true)
+            .set_title("Save File")
+            .set_file_name(name)
+            .save_file()
+        {
+            // Usually a pairing file, so keep its keys private
+            self.message = Some(match pairing::write_to_file(&p, &bytes) {
+                Ok(()) => Ok(format!("Saved to {}", p.display())),
+                Err(e) => Err(e.to_string()),
+            });
+        }
+    }
+}
+
+/// Install results by app name and bundle id, the same copy can be installed as more than one app
+type InstallResults = HashMap<(String, String), Option<Result<InstallReport, Error>>>;
+
 struct MyApp {
     // Selector
-    devices: Option<HashMap<String, UsbmuxdDevice>>,
+    /// Connected devices keyed by UDID
+    devices: Option<BTreeMap<String, Device>>,
     devices_placeholder: String,
     selected_device: String,
-      // Device details
+    // Device details
     device_info: Option<Vec<(String, String)>>,
 
-    // Device info
-    wireless_enabled: Option<Result<(), IdeviceError>>,
-    dev_mode_enabled: Option<Result<bool, IdeviceError>>,
-    ddi_mounted: Option<Result<(), IdeviceError>>,
+    // Device info
+    wireless_enabled: Option<Result<bool, Error>>,
+    dev_mode_enabled: Option<Result<bool, Error>>,
+    mounted_images: Option<Result<Vec<MountedImage>, Error>>,
+    confirm_action: Option<(DeviceAction, UsbmuxdDevice)>,
+
+    // Pairing info
+    pairing_file: Option<PairingFile>,
+    pairing_file_string: Option<String>, // redacted, the keys are only shown once revealed
+    pairing_file_message: Option<String>,
+    pairing_file_path: Option<PathBuf>, // set when opened from disk rather than a device
+    pairing_file_device: Option<String>, // UDID of the device it was loaded or generated from
+    vault_source: Option<(vault::Source, Option<String>)>, // how it was made and the device name, None once in the vault
+    pairing_file_report: Option<PairingFileReport>,
+    reveal_private_keys: bool,
+    confirm_reveal: bool,
+
+    // Save
+    save_error: Option<String>,
+    save_encrypted: bool,
+    save_as: SaveAs,
+    save_udid: bool,         // for plain formats, apps say whether they need it
+    save_udid_input: String, // for files that didn't come from a device
+    save_passphrase: String,
+    save_passphrase_confirm: String,
+    locked_file: Option<PathBuf>, // encrypted file waiting for its passphrase
+    unlock_passphrase: String,
+    unlock_error: Option<String>,
+    installed_apps: Option<Result<HashMap<String, Vec<InstalledApp>>, Error>>, // by app name
+    apps: AppRegistry,
+    show_apps: bool,
+
+    // Converter, works on files without a device
+    show_convert: bool,
+    convert_input: Option<(PathBuf, Vec<u8>)>,
+    convert_passphrase: String,
+    convert_target: SaveAs,
+    convert_udid: String,
+    convert_message: Option<Result<String, String>>,
+    app_form: AppForm,
+    apps_message: Option<Result<String, String>>,
+    install_res: InstallResults,
+    install_batch: Vec<InstallTarget>, // the last "Install to All"
+    backups: HashMap<String, Option<Result<Vec<Backup>, Error>>>, // by bundle id, when shown
+    container_res: HashMap<String, Option<Result<String, Error>>>, // removes and restores by bundle id
+    browser: Option<Browser>,
+
+    // Validation
+    validate_res: Option<Result<(), String>>,
+    validating: bool,
+    validation_ip_input: String,
+
+    // Channel
+    gui_recv: UnboundedReceiver<GuiCommands>,
+    idevice_sender: UnboundedSender<IdeviceCommands>,
+
+    show_logs: bool,
+
+    settings: Settings,
+    show_settings: bool,
+    usbmuxd_address_input: String,
+    settings_message: Option<Result<String, String>>,
+    addr_sender: watch::Sender<UsbmuxdAddr>,
+
+    // Vault, None if it couldn't be opened
+    vault: Option<Vault>,
+    show_vault: bool,
+    vault_filter: String,
+    vault_message: Option<String>,
+    confirm_vault_remove: Option<String>, // host id
+
+    // Developer disk image cache, None if it couldn't be opened
+    ddi: Option<DdiCache>,
+    show_ddi: bool,
+    ddi_importing: bool,
+    ddi_checking: bool,
+    ddi_message: Option<Result<String, String>>,
+}
+
+impl MyApp {
+    /// Resets per-device state and requests fresh details for the newly selected device
+    fn select_device(&mut self, device: &Device) {
+        let dev = device.usbmuxd.clone();
+        self.selected_device = dev.udid.clone();
+
+        self.wireless_enabled = None;
+        self.dev_mode_enabled = None;
+        self.mounted_images = None;
+        self.device_info = None;
+
+        // Wireless is already on for network devices, and mounting needs USB. Nothing is
+        // changed on the device unless the user chose to have that done on selection
+        self.confirm_action = None;
+        if !device.is_network() {
+            let (wireless, mount) = match self.settings.device_access {
+                DeviceAccess::Automatic => (
+                    IdeviceCommands::SetWireless((dev.clone(), true)),
+                    IdeviceCommands::AutoMount(dev.clone()),
+                ),
+                DeviceAccess::Inspect | DeviceAccess::Confirm => (
+                    IdeviceCommands::CheckWireless(dev.clone()),
+                    IdeviceCommands::CheckMount(dev.clone()),
+                ),
+            };
+            self.idevice_sender.send(wireless).unwrap();
+            self.idevice_sender.send(mount).unwrap();
+        }
+        self.idevice_sender
+            .send(IdeviceCommands::CheckDevMode(dev.clone()))
+            .unwrap();
+        self.idevice_sender
+            .send(IdeviceCommands::GetDeviceInfo(dev.clone()))
+            .unwrap();
+
+        // A file opened from disk isn't tied to a device, keep it across selections
+        if self.pairing_file_path.is_none() {
+            self.pairing_file = None;
+            self.pairing_file_report = None;
+            self.pairing_file_message = None;
+            self.pairing_file_string = None;
+        }
+        self.refresh_installed_apps(dev);
+        self.validating = false;
+        self.validate_res = None;
+        self.browser = None;
+    }
+
+    fn run_action(&mut self, action: DeviceAction, dev: UsbmuxdDevice) {
+        let command = match action {
+            DeviceAction::EnableWireless | DeviceAction::DisableWireless => {
+                self.wireless_enabled = None;
+                IdeviceCommands::SetWireless((dev, action == DeviceAction::EnableWireless))
+            }
+            DeviceAction::Mount => {
+                self.mounted_images = None;
+                IdeviceCommands::AutoMount(dev)
+            }
+            DeviceAction::Unmount => {
+                self.mounted_images = None;
+                IdeviceCommands::Unmount(dev)
+            }
+            DeviceAction::Remount => {
+                self.mounted_images = None;
+                IdeviceCommands::Remount(dev)
+            }
+            #[cfg(feature = "generate")]
+            DeviceAction::Generate => {
+                self.pairing_file_message = Some("Loading...".to_string());
+                self.pairing_file_string = None;
+                let name = self.device_name(&dev.udid);
+                IdeviceCommands::GeneratePairingFile((dev, name))
+            }
+            DeviceAction::Install((name, bundle_id)) => {
+                let (Some(app), Some(pairing_file)) = (self.apps.get(&name), &self.pairing_file)
+                else {
+                    return;
+                };
+                let command = IdeviceCommands::InstallPairingFile((
+                    dev,
+                    bundle_id.clone(),
+                    app.clone(),
+                    pairing_file.clone(),
+                ));
+                self.install_res.insert((name, bundle_id), None);
+                command
+            }
+            DeviceAction::InstallToAll(targets) => {
+                let Some(pairing_file) = self.pairing_file.clone() else {
+                    return;
+                };
+                for target in &targets {
+                    self.install_res
+                        .insert((target.app.name.clone(), target.bundle_id.clone()), None);
+                }
+                self.install_batch = targets.clone();
+                IdeviceCommands::InstallToAll((dev, targets, pairing_file))
+            }
+            DeviceAction::Remove((name, bundle_id)) => {
+                let Some(app) = self.apps.get(&name).cloned() else {
+                    return;
+                };
+                self.container_res.insert(bundle_id.clone(), None);
+                IdeviceCommands::RemovePairingFile((dev, bundle_id, app))
+            }
+            DeviceAction::Restore((name, bundle_id, backup)) => {
+                let Some(app) = self.apps.get(&name).cloned() else {
+                    return;
+                };
+                self.container_res.insert(bundle_id.clone(), None);
+                IdeviceCommands::RestorePairingFile((dev, bundle_id, app, backup))
+            }
+        };
+        self.idevice_sender.send(command).unwrap();
+    }
+
+    /// Looks up which of the registry's apps are on the device
+    fn refresh_installed_apps(&mut self, dev: UsbmuxdDevice) {
+        self.installed_apps = None;
+        self.idevice_sender
+            .send(IdeviceCommands::InstalledApps((
+                dev,
+                self.apps.apps().into_iter().cloned().collect(),
+            )))
+            .unwrap();
+    }
+
+    /// The name of a connected device, for labelling what came from it
+    fn device_name(&self, udid: &str) -> Option<String> {
+        self.devices
+            .as_ref()
+            .and_then(|x| x.get(udid))
+            .map(|x| x.name.clone())
+    }
+
+    fn set_pairing_file(&mut self, pairing_file: PairingFile, path: Option<PathBuf>) {
+        self.pairing_file_string = Some(
+            pairing::redacted_xml(&pairing_file)
+                .unwrap_or_else(|e| format!("Failed to display pairing file: {e}")),
+        );
+        self.reveal_private_keys = false;
+        self.pairing_file_report = Some(inspect::inspect(&pairing_file));
+        self.pairing_file = Some(pairing_file);
+        self.pairing_file_path = path;
+        self.pairing_file_device = None;
+        self.vault_source = None;
+        self.validating = false;
+        self.validate_res = None;
+        self.install_res.clear();
+        self.install_batch.clear();
+    }
+
+    /// Lets the user pick an existing pairing file and loads it in place of one from a device
+    fn open_pairing_file(&mut self) {
+        let Some(path) = FileDialog::new()
+            .set_title("Open Pairing File")
+            .add_filter(
+                "Pairing File",
+                &["plist", "mobiledevicepairing", encryption::EXTENSION],
+            )
+            .pick_file()
+        else {
+            return;
+        };
+
+        match pairing::read_from_file(&path, None) {
+            Ok(p) => self.opened_pairing_file(p, path, false),
+            Err(Error::PassphraseRequired) => {
+                self.unlock_passphrase.clear();
+                self.unlock_error = None;
+                self.locked_file = Some(path);
+            }
+            Err(e) => {
+                self.pairing_file_message = Some(format!("Failed to open {}: {e}", path.display()))
+            }
+        }
+    }
+
+    /// Decrypts the file picked in [`Self::open_pairing_file`] with the entered passphrase
+    fn unlock_pairing_file(&mut self) {
+        let Some(path) = self.locked_file.clone() else {
+            return;
+        };
+        match pairing::read_from_file(&path, Some(&self.unlock_passphrase)) {
+            Ok(p) => {
+                self.locked_file = None;
+                self.unlock_passphrase.clear();
+                self.opened_pairing_file(p, path, true);
+            }
+            Err(e) => self.unlock_error = Some(e.to_string()),
+        }
+    }
+
+    fn opened_pairing_file(&mut self, pairing_file: PairingFile, path: PathBuf, encrypted: bool) {
+        self.pairing_file_message = Some(format!("Opened {}", path.display()));
+        self.set_pairing_file(pairing_file, Some(path));
+        self.vault_source = Some((vault::Source::Imported, None));
+        // A decrypted copy would undo the encryption, it's only stored when asked to
+        if !encrypted {
+            self.keep_in_vault();
+        }
+    }
+
+    fn save_pairing_file(&mut self) {
+        let Some(pairing_file) = self.pairing_file.clone() else {
+            return;
+        };
+        // Plain formats only get the UDID when asked, apps when they need it. It's only
+        // taken from a device the file came from, otherwise it has to be entered.
+        let udid = pairing_file
+            .udid
+            .clone()
+            .or_else(|| self.pairing_file_device.clone())
+            .unwrap_or_else(|| self.save_udid_input.trim().to_string());
+        let default_name = format!(
+            "{}.plist",
+            pairing_file.udid.as_deref().unwrap_or("pairing_file")
+        );
+        let (bytes, file_name, filter) = if self.save_encrypted {
+            if self.save_passphrase.is_empty() {
+                self.save_error = Some("Enter a passphrase to encrypt with".to_string());
+                return;
+            }
+            if self.save_passphrase != self.save_passphrase_confirm {
+                self.save_error = Some("The passphrases don't match".to_string());
+                return;
+            }
+            let file_name = format!(
+                "{}.{}",
+                pairing_file.udid.as_deref().unwrap_or("pairing_file"),
+                encryption::EXTENSION
+            );
+            (
+                pairing::encode(
+                    &pairing_file,
+                    pairing::Format::Xml,
+                    Some(&self.save_passphrase),
+                ),
+                file_name,
+                "Encrypted Pairing File".to_string(),
+            )
+        } else {
+            match &self.save_as {
+                SaveAs::Format(format) => {
+                    if self.save_udid && udid.is_empty() {
+                        self.save_error = Some("Enter the device's UDID to add it".to_string());
+                        return;
+                    }
+                    let pairing_file = if self.save_udid {
+                        pairing::with_udid(&pairing_file, &udid)
+                    } else {
+                        pairing_file
+                    };
+                    (
+                        pairing::serialize(&pairing_file, *format),
+                        default_name,
+                        format!("{format} Pairing File"),
+                    )
+                }
+                SaveAs::App(name) => {
+                    let Some(app) = self.apps.get(name) else {
+                        self.save_error = Some(format!("{name} is no longer in the apps list"));
+                        return;
+                    };
+                    if app.udid && udid.is_empty() {
+                        self.save_error =
+                            Some(format!("{name} needs the device's UDID, enter it first"));
+                        return;
+                    }
+                    (
+                        app.serialize(&pairing_file, &udid),
+                        app.file_name().to_string(),
+                        format!("{name} Pairing File"),
+                    )
+                }
+            }
+        };
+        let extension = file_name.rsplit_once('.').map(|x| x.1).unwrap_or("plist");
+        if let Some(p) = FileDialog::new()
+            .set_can_create_directories(true)
+            .set_title("Save Pairing File")
+            .add_filter(filter, &[extension])
+            .set_file_name(&file_name)
+            .save_file()
+        {
+            self.save_error = None;
+            match bytes {
+                Ok(bytes) => {
+                    if let Err(e) = pairing::write_to_file(p, &bytes) {
+                        self.save_error = Some(e.to_string());
+                    }
+                }
+                Err(e) => self.save_error = Some(e.to_string()),
+            }
+        }
+    }
+
+    fn convert_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Convert a pairing file to another format, or to what another app expects. No device is needed.");
+        ui.horizontal(|ui| {
+            if ui.button("Choose File…").clicked()
+                && let Some(path) = FileDialog::new()
+                    .set_title("Choose Pairing File")
+                    .add_filter(
+                        "Pairing File",
+                        &["plist", "mobiledevicepairing", encryption::EXTENSION],
+                    )
+                    .pick_file()
+            {
+                self.convert_message = None;
+                match std::fs::read(&path) {
+                    Ok(bytes) => self.convert_input = Some((path, bytes)),
+                    Err(e) => {
+                        self.convert_message =
+                            Some(Err(format!("Failed to read {}: {e}", path.display())))
+                    }
+                }
+            }
+            match &self.convert_input {
+                Some((path, _)) => ui.label(RichText::new(path.display().to_string()).monospace()),
+                None => ui.label(RichText::new("No file chosen").weak()),
+            };
+        });
+        egui::Grid::new("convert").num_columns(2).show(ui, |ui| {
+            if let Some((_, bytes)) = &self.convert_input
+                && encryption::is_encrypted(bytes)
+            {
+                ui.label("Passphrase");
+                ui.add(egui::TextEdit::singleline(&mut self.convert_passphrase).password(true));
+                ui.end_row();
+            }
+            ui.label("Convert to");
+            save_as_combo(ui, "convert_to", &mut self.convert_target, &self.apps);
+            ui.end_row();
+            ui.label("UDID");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.convert_udid)
+                    .hint_text("Added if the file doesn't have one"),
+            );
+            ui.end_row();
+        });
+        if ui
+            .add_enabled(self.convert_input.is_some(), egui::Button::new("Convert…"))
+            .clicked()
+        {
+            self.convert_pairing_file();
+        }
+        match &self.convert_message {
+            Some(Ok(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::GREEN));
+            }
+            Some(Err(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::RED));
+            }
+            None => {}
+        }
+    }
+
+    /// Converts the chosen file and asks where to save the result
+    fn convert_pairing_file(&mut self) {
+        let Some((path, bytes)) = &self.convert_input else {
+            return;
+        };
+        let passphrase =
+            Some(self.convert_passphrase.as_str()).filter(|_| encryption::is_encrypted(bytes));
+        let udid = Some(self.convert_udid.trim()).filter(|x| !x.is_empty());
+        let (converted, file_name) = match &self.convert_target {
+            SaveAs::Format(format) => {
+                let stem = path
+                    .file_stem()
+                    .map(|x| x.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "pairing_file".to_string());
+                (
+                    pairing::convert(bytes, passphrase, *format, udid),
+                    format!("{stem}.plist"),
+                )
+            }
+            SaveAs::App(name) => {
+                let Some(app) = self.apps.get(name) else {
+                    self.convert_message =
+                        Some(Err(format!("{name} is no longer in the apps list")));
+                    return;
+                };
+                (
+                    app.convert(bytes, passphrase, udid),
+                    app.file_name().to_string(),
+                )
+            }
+        };
+        let converted = match converted {
+            Ok(converted) => converted,
+            Err(e) => {
+                self.convert_message = Some(Err(e.to_string()));
+                return;
+            }
+        };
+        let extension = file_name.rsplit_once('.').map(|x| x.1).unwrap_or("plist");
+        if let Some(p) = FileDialog::new()
+            .set_can_create_directories(true)
+            .set_title("Save Converted Pairing File")
+            .add_filter("Pairing File", &[extension])
+            .set_file_name(&file_name)
+            .save_file()
+        {
+            self.convert_message = Some(match pairing::write_to_file(&p, &converted) {
+                Ok(()) => Ok(format!("Saved to {}", p.display())),
+                Err(e) => Err(e.to_string()),
+            });
+        }
+    }
+
+    fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("usbmuxd address");
+        ui.label(format!(
+            "A socket path or host:port, for netmuxd or a forwarded usbmuxd. Leave empty to use {} or the default.",
+            device::USBMUXD_ADDRESS_VAR
+        ));
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.usbmuxd_address_input)
+                    .hint_text(device::describe_usbmuxd_addr(&UsbmuxdAddr::default())),
+            );
+            if ui.button("Apply").clicked() {
+                self.apply_usbmuxd_address();
+            }
+        });
+        ui.label(format!(
+            "Using {}",
+            device::describe_usbmuxd_addr(&self.addr_sender.borrow())
+        ));
+        ui.separator();
+        ui.heading("Changing devices");
+        let mut changed = false;
+        for (access, text) in [
+            (
+                DeviceAccess::Inspect,
+                "Inspect only, never change anything on a device",
+            ),
+            (DeviceAccess::Confirm, "Ask before each change to a device"),
+            (
+                DeviceAccess::Automatic,
+                "Turn on wireless debugging and mount the developer disk image when a USB device is selected",
+            ),
+        ] {
+            changed |= ui
+                .radio_value(&mut self.settings.device_access, access, text)
+                .changed();
+        }
+        ui.separator();
+        ui.heading("Vault");
+        changed |= ui
+            .checkbox(
+                &mut self.settings.keep_in_vault,
+                "Keep every pairing file loaded, generated or opened unencrypted in the vault",
+            )
+            .changed();
+        ui.label(
+            RichText::new("Otherwise only files added with Add to Vault are kept. Decrypted files are never kept without asking.")
+                .weak(),
+        );
+        if changed {
+            self.settings_message = Some(match self.settings.save() {
+                Ok(()) => Ok("Saved".to_string()),
+                Err(e) => Err(format!("Failed to save: {e}")),
+            });
+        }
+        match &self.settings_message {
+            Some(Ok(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::GREEN));
+            }
+            Some(Err(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::RED));
+            }
+            None => {}
+        }
+    }
+
+    /// Stores the shown pairing file in the vault, if there is one
+    fn add_to_vault(&mut self) -> Result<(), Error> {
+        let (Some(vault), Some(pairing_file), Some((source, device_name))) =
+            (&mut self.vault, &self.pairing_file, &self.vault_source)
+        else {
+            return Ok(());
+        };
+        vault.add(pairing_file, *source, device_name.clone())?;
+        self.vault_source = None;
+        Ok(())
+    }
+
+    /// Adds the shown pairing file to the vault without asking, when the settings say to
+    fn keep_in_vault(&mut self) {
+        if self.settings.keep_in_vault
+            && let Err(e) = self.add_to_vault()
+        {
+            warn!("Failed to add the pairing file to the vault: {e}");
+        }
+    }
+
+    fn browser_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(browser) = &mut self.browser else {
+            return;
+        };
+        let sender = &self.idevice_sender;
+        ui.label(RichText::new(&browser.bundle_id).italics().weak());
+        ui.horizontal(|ui| {
+            let at_root = container::parent(&browser.dir) == browser.dir;
+            if ui.add_enabled(!at_root, egui::Button::new("Up")).clicked() {
+                let dir = container::parent(&browser.dir).to_string();
+                browser.open_dir(sender, dir);
+            }
+            if ui.button("Refresh").clicked() {
+                let dir = browser.dir.clone();
+                browser.open_dir(sender, dir);
+            }
+            ui.label(RichText::new(&browser.dir).monospace());
+        });
+        match &browser.message {
+            Some(Ok(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::GREEN));
+            }
+            Some(Err(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::RED));
+            }
+            None => {}
+        }
+        if let Some((path, _)) = &browser.reading {
+            ui.label(format!("Reading {path}..."));
+        }
+        ui.separator();
+
+        // Clicks are acted on after the grid, which borrows the entries
+        let mut open = None;
+        let mut read = None;
+        egui::ScrollArea::vertical()
+            .id_salt("files")
+            .max_height(300.0)
+            .show(ui, |ui| match &browser.entries {
+                Some(Ok(entries)) if entries.is_empty() => {
+                    ui.label(RichText::new("Empty").weak());
+                }
+                Some(Ok(entries)) => {
+                    egui::Grid::new("files")
+                        .num_columns(4)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for entry in entries {
+                                if entry.is_dir {
+                                    if ui.link(format!("{}/", entry.name)).clicked() {
+                                        open = Some(entry.path.clone());
+                                    }
+                                    ui.label("");
+                                } else {
+                                    ui.label(&entry.name);
+                                    ui.label(format!("{} bytes", entry.size));
+                                }
+                                ui.label(
+                                    entry
+                                        .modified
+                                        .with_timezone(&chrono::Local)
+                                        .format("%Y-%m-%d %H:%M:%S")
+                                        .to_string(),
+                                );
+                                ui.horizontal(|ui| {
+                                    if !entry.is_dir {
+                                        if ui.button("Preview").clicked() {
+                                            read = Some((entry.path.clone(), false));
+                                        }
+                                        if ui.button("Download").clicked() {
+                                            read = Some((entry.path.clone(), true));
+                                        }
+                                    }
+                                });
+                                ui.end_row();
+                            }
+                        });
+                }
+                Some(Err(e)) => {
+                    ui.label(
+                        RichText::new(format!(
+                            "Failed to list {}: {}",
+                            browser.dir,
+                            error_chain(e)
+                        ))
+                        .color(Color32::RED),
+                    );
+                }
+                None => {
+                    ui.label("Listing...");
+                }
+            });
+        if let Some(dir) = open {
+            browser.open_dir(sender, dir);
+        }
+        if let Some((path, download)) = read {
+            browser.read(sender, path, download);
+        }
+
+        if let Some((path, preview)) = &browser.preview {
+            ui.separator();
+            ui.label(RichText::new(path).monospace());
+            match preview {
+                Some(xml) => {
+                    egui::ScrollArea::vertical()
+                        .id_salt("preview")
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            ui.add(
+                                egui::TextEdit::multiline(&mut xml.as_str())
+                                    .code_editor()
+                                    .desired_width(f32::INFINITY),
+                            );
+                        });
+                }
+                None => {
+                    ui.label("Not a plist, download it to look at it");
+                }
+            }
+        }
+    }
+
+    fn vault_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(vault) = &mut self.vault else {
+            ui.label("The vault couldn't be opened, check the logs for why.");
+            return;
+        };
+        ui.label(format!(
+            "Pairing files are kept in {}",
+            vault.dir().display()
+        ));
+        ui.add(
+            egui::TextEdit::singleline(&mut self.vault_filter)
+                .hint_text("Filter by UDID or HostID"),
+        );
+        if let Some(msg) = &self.vault_message {
+            ui.label(msg);
+        }
+        ui.separator();
+
+        let filter = self.vault_filter.trim().to_lowercase();
+        let mut used = None;
+        let mut removed = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("vault_entries")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label(RichText::new("Device").strong());
+                    ui.label(RichText::new("HostID").strong());
+                    ui.label(RichText::new("Created").strong());
+                    ui.label(RichText::new("Source").strong());
+                    ui.label(RichText::new("Installed into").strong());
+                    ui.label(RichText::new("Last validation").strong());
+                    ui.end_row();
+
+                    for entry in vault.entries() {
+                        let udid = entry.udid.as_deref().unwrap_or("Unknown UDID");
+                        if !filter.is_empty()
+                            && !udid.to_lowercase().contains(&filter)
+                            && !entry.host_id.to_lowercase().contains(&filter)
+                        {
+                            continue;
+                        }
+                        ui.vertical(|ui| {
+                            if let Some(name) = &entry.device_name {
+                                ui.label(name);
+                            }
+                            ui.label(RichText::new(udid).monospace());
+                        });
+                        ui.label(RichText::new(&entry.host_id).monospace());
+                        ui.label(entry.created.format("%Y-%m-%d %H:%M UTC").to_string());
+                        ui.label(entry.source.to_string());
+                        if entry.installs.is_empty() {
+                            ui.label(RichText::new("Nowhere").weak());
+                        } else {
+                            ui.label(
+                                entry
+                                    .installs
+                                    .iter()
+                                    .map(|x| x.app.as_str())
+                                    .collect::<Vec<_>>()
+                                    .join(", "),
+                            );
+                        }
+                        match &entry.last_validation {
+                            Some(v) => {
+                                let at = v.at.format("%Y-%m-%d %H:%M UTC");
+                                match &v.error {
+                                    None => ui.label(
+                                        RichText::new(format!("Valid ({at})"))
+                                            .color(Color32::GREEN),
+                                    ),
+                                    Some(e) => ui.label(
+                                        RichText::new(format!("Failed ({at}): {e}"))
+                                            .color(Color32::RED),
+                                    ),
+                                };
+                            }
+                            None => {
+                                ui.label(RichText::new("Never").weak());
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.button("Use").clicked() {
+                                used = Some(entry.host_id.clone());
+                            }
+                            if self.confirm_vault_remove.as_ref() == Some(&entry.host_id) {
+                                if ui
+                                    .button(RichText::new("Really delete").color(Color32::RED))
+                                    .clicked()
+                                {
+                                    removed = Some(entry.host_id.clone());
+                                }
+                                if ui.button("Cancel").clicked() {
+                                    self.confirm_vault_remove = None;
+                                }
+                            } else if ui.button("Delete").clicked() {
+                                self.confirm_vault_remove = Some(entry.host_id.clone());
+                            }
+                        });
+                        ui.end_row();
+                    }
+                });
+        });
+
+        if let Some(host_id) = removed {
+            self.confirm_vault_remove = None;
+            self.vault_message = match vault.remove(&host_id) {
+                Ok(()) => Some(format!("Deleted {host_id}")),
+                Err(e) => Some(format!("Failed to delete {host_id}: {e}")),
+            };
+        }
+        if let Some(host_id) = used {
+            match vault.pairing_file(&host_id) {
+                Ok(p) => {
+                    let path = vault.file_path(&host_id);
+                    self.pairing_file_message = Some(format!("Using {host_id} from the vault"));
+                    self.vault_message = None;
+                    self.set_pairing_file(p, Some(path));
+                }
+                Err(e) => self.vault_message = Some(format!("Failed to read {host_id}: {e}")),
+            }
+        }
+    }
+
+    fn ddi_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(cache) = &mut self.ddi else {
+            ui.label("The image cache couldn't be opened, check the logs for why.");
+            return;
+        };
+        ui.label(format!("Images are kept in {}", cache.dir().display()));
+        if ddi::EMBEDDED {
+            ui.label("The personalized image built into this app is used when none is imported.");
+        } else {
+            ui.label("This build has no image built in, import one to mount it.");
+        }
+        ui.horizontal(|ui| {
+            let mut picked = None;
+            let enabled = !self.ddi_importing;
+            if ui
+                .add_enabled(enabled, egui::Button::new("Import Folder…"))
+                .clicked()
+            {
+                picked = FileDialog::new()
+                    .set_title("Choose Developer Disk Image Folder")
+                    .pick_folder();
+            }
+            if ui
+                .add_enabled(enabled, egui::Button::new("Import Archive…"))
+                .clicked()
+            {
+                picked = FileDialog::new()
+                    .set_title("Choose Developer Disk Image Archive")
+                    .add_filter("Archive", &["zip", "tar", "gz", "tgz"])
+                    .pick_file();
+            }
+            if let Some(path) = picked {
+                self.ddi_importing = true;
+                self.ddi_message = None;
+                self.idevice_sender
+                    .send(IdeviceCommands::ImportDdi((
+                        cache.dir().to_path_buf(),
+                        path,
+                    )))
+                    .unwrap();
+            }
+            if self.ddi_importing {
+                ui.label("Importing...");
+            }
+            if self.ddi_checking {
+                ui.label("Checking...");
+            }
+        });
+        match &self.ddi_message {
+            Some(Ok(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::GREEN));
+            }
+            Some(Err(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::RED));
+            }
+            None => {}
+        }
+        ui.separator();
+
+        if cache.images().is_empty() {
+            ui.label(RichText::new("No images imported").weak());
+            return;
+        }
+        let mut checked = None;
+        let mut removed = None;
+        egui::Grid::new("ddi_images").striped(true).show(ui, |ui| {
+            ui.label(RichText::new("Image").strong());
+            ui.label(RichText::new("Imported").strong());
+            ui.label(RichText::new("From").strong());
+            ui.end_row();
+            for image in cache.images() {
+                ui.label(image.kind.to_string());
+                ui.label(image.imported.format("%Y-%m-%d %H:%M UTC").to_string());
+                ui.label(RichText::new(image.source.display().to_string()).monospace());
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(!self.ddi_checking, egui::Button::new("Check Files"))
+                        .on_hover_text(
+                            "Compare the files with the hashes recorded when they were imported",
+                        )
+                        .clicked()
+                    {
+                        checked = Some(image.kind.clone());
+                    }
+                    // The import writes the same index, it could undo the removal
+                    if ui
+                        .add_enabled(!self.ddi_importing, egui::Button::new("Remove"))
+                        .clicked()
+                    {
+                        removed = Some(image.kind.clone());
+                    }
+                });
+                ui.end_row();
+            }
+        });
+        if let Some(kind) = checked {
+            self.ddi_checking = true;
+            self.ddi_message = None;
+            self.idevice_sender
+                .send(IdeviceCommands::CheckDdi((cache.dir().to_path_buf(), kind)))
+                .unwrap();
+        }
+        if let Some(kind) = removed {
+            self.ddi_message = Some(match cache.remove(&kind) {
+                Ok(()) => Ok(format!("Removed the {kind} image")),
+                Err(e) => Err(e.to_string()),
+            });
+        }
+    }
+
+    fn apps_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Apps pairing files can be installed into. Changes are saved to your config directory, and a bundled app can be changed by saving one with the same name.");
+        let mut edit = None;
+        let mut remove = None;
+        egui::Grid::new("supported_apps")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label(RichText::new("Name").strong());
+                ui.label(RichText::new("Bundle IDs").strong());
+                ui.label(RichText::new("Path in Documents").strong());
+                ui.label(RichText::new("Format").strong());
+                ui.label(RichText::new("UDID").strong());
+                ui.end_row();
+                for app in self.apps.apps() {
+                    ui.label(&app.name);
+                    ui.label(app.bundle_ids.join(", "));
+                    ui.label(RichText::new(&app.path).monospace());
+                    ui.label(app.format.to_string());
+                    ui.label(if app.udid { "Added" } else { "" });
+                    ui.horizontal(|ui| {
+                        if ui.button("Edit").clicked() {
+                            edit = Some(app.clone());
+                        }
+                        if self.apps.is_user(&app.name) {
+                            let label = if self.apps.is_bundled(&app.name) {
+                                "Reset"
+                            } else {
+                                "Delete"
+                            };
+                            if ui.button(label).clicked() {
+                                remove = Some(app.name.clone());
+                            }
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        if let Some(app) = edit {
+            self.app_form = AppForm::from(&app);
+        }
+        if let Some(name) = remove {
+            let res = self.apps.remove_user(&name);
+            self.apps_saved(res);
+        }
 
-    // Pairing info
-    pairing_file: Option<PairingFile>,
-    pairing_file_string: Option<String>,
-    pairing_file_message: Option<String>,
+        ui.separator();
+        ui.heading("Add or change an app");
+        egui::Grid::new("app_form").show(ui, |ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut self.app_form.name);
+            ui.end_row();
+            ui.label("Bundle IDs");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.app_form.bundle_ids)
+                    .hint_text("Comma separated, optional"),
+            );
+            ui.end_row();
+            ui.label("Path in Documents");
+            ui.text_edit_singleline(&mut self.app_form.path);
+            ui.end_row();
+            ui.label("Format");
+            ComboBox::from_id_salt("app_format")
+                .selected_text(self.app_form.format.to_string())
+                .show_ui(ui, |ui| {
+                    for format in [pairing::Format::Xml, pairing::Format::Binary] {
+                        ui.selectable_value(&mut self.app_form.format, format, format.to_string());
+                    }
+                });
+            ui.end_row();
+            ui.label("UDID");
+            ui.checkbox(
+                &mut self.app_form.udid,
+                "Add the device's UDID to the pairing file",
+            );
+            ui.end_row();
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                match self.app_form.to_app() {
+                    Ok(app) => {
+                        let res = self.apps.set_user(app);
+                        if res.is_ok() {
+                            self.app_form = AppForm::default();
+                        }
+                        self.apps_saved(res);
+                    }
+                    Err(e) => self.apps_message = Some(Err(e)),
+                }
+            }
+            if ui.button("Clear").clicked() {
+                self.app_form = AppForm::default();
+            }
+        });
+        match &self.apps_message {
+            Some(Ok(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::GREEN));
+            }
+            Some(Err(msg)) => {
+                ui.label(RichText::new(msg).color(Color32::RED));
+            }
+            None => {}
+        }
+    }
 
-    // Save
-    save_error: Option<String>,
-    installed_apps: Option<Result<HashMap<String, String>, IdeviceError>>,
-    supported_apps: HashMap<String, String>, // name, path to save pairing file to
-    install_res: HashMap<String, Option<Result<(), IdeviceError>>>,
+    /// Reports the result of changing the registry and looks the apps up again
+    fn apps_saved(&mut self, res: Result<(), Error>) {
+        self.apps_message = Some(match res {
+            Ok(()) => Ok("Saved".to_string()),
+            Err(e) => Err(format!("Failed to save: {e}")),
+        });
+        let dev = self
+            .devices
+            .as_ref()
+            .and_then(|x| x.get(&self.selected_device))
+            .map(|x| x.usbmuxd.clone());
+        if let Some(dev) = dev {
+            self.refresh_installed_apps(dev);
+        }
+    }
 
-    // Validation
-    validate_res: Option<Result<(), String>>,
-    validating: bool,
-    validation_ip_input: String,
+    /// Switches every task to the entered usbmuxd address and reloads the device list
+    fn apply_usbmuxd_address(&mut self) {
+        let input = self.usbmuxd_address_input.trim().to_string();
+        let addr = match device::resolve_usbmuxd_addr(Some(&input)) {
+            Ok(a) => a,
+            Err(e) => {
+                self.settings_message = Some(Err(e.to_string()));
+                return;
+            }
+        };
 
-    // Channel
-    gui_recv: UnboundedReceiver<GuiCommands>,
-    idevice_sender: UnboundedSender<IdeviceCommands>,
+        self.settings.usbmuxd_address = input;
+        self.settings_message = Some(match self.settings.save() {
+            Ok(()) => Ok("Saved".to_string()),
+            Err(e) => Err(format!("Applied, but failed to save: {e}")),
+        });
 
-    show_logs: bool,
+        self.addr_sender.send_replace(addr);
+        self.devices = None;
+        self.devices_placeholder = "Loading...".to_string();
+        self.idevice_sender
+            .send(IdeviceCommands::GetDevices)
+            .unwrap();
+    }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Get updates from the idevice thread
-        match self.gui_recv.try_recv() {            Ok(msg) => match msg {
+        match self.gui_recv.try_recv() {
+            Ok(msg) => match msg {
                 GuiCommands::NoUsbmuxd(idevice_error) => {
                     let install_msg = if cfg!(windows) {
                         "Make sure you have iTunes installed from Apple's website, and that it's running."
@@ -662,54 +1863,22 @@ impl eframe::App for MyApp {
                         "Failed to connect to usbmuxd! {install_msg}\n\n{idevice_error:#?}"
                     );
                 }
-                GuiCommands::Devices(vec) => {
-                    self.devices = Some(vec);
-                    if self.selected_device.is_empty() || 
-                       (self.devices.as_ref().map_or(true, |devs| !devs.contains_key(&self.selected_device))) {
-                        if let Some(devs) = &self.devices {
-                            if devs.len() == 1 {
-                                if let Some((dev_name, dev)) = devs.iter().next() {
-                                    self.selected_device = dev_name.clone();
-
-                                    self.wireless_enabled = None;
-                                    self.dev_mode_enabled = None;
-                                    self.ddi_mounted = None;
-                                    self.device_info = None;
-
-                                    let dev_clone = dev.clone();
-                                    self.idevice_sender
-                                        .send(IdeviceCommands::EnableWireless(dev_clone.clone()))
-                                        .unwrap();
-                                    self.idevice_sender
-                                        .send(IdeviceCommands::CheckDevMode(dev_clone.clone()))
-                                        .unwrap();
-                                    self.idevice_sender
-                                        .send(IdeviceCommands::AutoMount(dev_clone.clone()))
-                                        .unwrap();
-                                    self.idevice_sender
-                                        .send(IdeviceCommands::GetDeviceInfo(dev_clone))
-                                        .unwrap();
-
-                                    self.pairing_file = None;
-                                    self.pairing_file_message = None;
-                                    self.pairing_file_string = None;
-                                    self.installed_apps = None;
-                                    self.device_info = None;
-                                    self.idevice_sender
-                                        .send(IdeviceCommands::InstalledApps((
-                                            dev.clone(),
-                                            self.supported_apps
-                                                .keys()
-                                                .map(|x| x.to_owned())
-                                                .collect(),
-                                        )))
-                                        .unwrap();
-                                    self.validating = false;
-                                    self.validate_res = None;
-                                }
-                            }
+                GuiCommands::Devices(devs) => {
+                    let was_connected = self
+                        .devices
+                        .as_ref()
+                        .is_some_and(|old| old.contains_key(&self.selected_device));
+                    if let Some(dev) = devs.get(&self.selected_device) {
+                        // The selected device came back, its state may have changed while it was gone
+                        if !was_connected {
+                            self.select_device(dev);
                         }
+                    } else if devs.len() == 1
+                        && let Some(dev) = devs.values().next()
+                    {
+                        self.select_device(dev);
                     }
+                    self.devices = Some(devs);
                 }
                 GuiCommands::DeviceInfo(info) => self.device_info = Some(info),
                 GuiCommands::GetDevicesFailure(idevice_error) => {
@@ -717,35 +1886,102 @@ impl eframe::App for MyApp {
                         "Failed to get list of connected devices from usbmuxd! {idevice_error:?}"
                     );
                 }
-                GuiCommands::EnabledWireless => self.wireless_enabled = Some(Ok(())),
-                GuiCommands::EnableWirelessFailure(idevice_error) => {
-                    self.wireless_enabled = Some(Err(idevice_error))
-                }
+                GuiCommands::Wireless(res) => self.wireless_enabled = Some(res),
                 GuiCommands::DevMode(res) => {
                     self.dev_mode_enabled = Some(res);
                 }
                 GuiCommands::MountRes(res) => {
-                    self.ddi_mounted = Some(res);
+                    self.mounted_images = Some(res);
                 }
-                GuiCommands::PairingFile(pairing_file) => match pairing_file {
-                    Ok(p) => {
-                        self.pairing_file = Some(p.clone());
-                        self.pairing_file_message = None;
-                        self.pairing_file_string =
-                            Some(String::from_utf8_lossy(&p.serialize().unwrap()).to_string())
+                GuiCommands::DdiImported(res) => {
+                    self.ddi_importing = false;
+                    // The worker wrote to the cache's folder, read what it has now
+                    if let Some(cache) = &mut self.ddi {
+                        match DdiCache::open_at(cache.dir()) {
+                            Ok(reopened) => *cache = reopened,
+                            Err(e) => warn!("Failed to reread the image cache: {e}"),
+                        }
                     }
-                    Err(e) => self.pairing_file_message = Some(e.to_string()),
-                },
-                GuiCommands::Validated(res) => match res {
-                    Ok(()) => self.validate_res = Some(Ok(())),
-                    Err(e) => self.validate_res = Some(Err(e.to_string())),
-                },
+                    self.ddi_message = Some(match res {
+                        Ok(kinds) => Ok(format!(
+                            "Imported {}",
+                            kinds
+                                .iter()
+                                .map(|x| x.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )),
+                        Err(e) => Err(e.to_string()),
+                    });
+                }
+                GuiCommands::DdiChecked((kind, res)) => {
+                    self.ddi_checking = false;
+                    self.ddi_message = Some(match res {
+                        Ok(()) => Ok(format!(
+                            "The {kind} image is unchanged since it was imported"
+                        )),
+                        Err(e) => Err(e.to_string()),
+                    });
+                }
+                GuiCommands::PairingFile((source, udid, device_name, pairing_file)) => {
+                    match pairing_file {
+                        Ok(p) => {
+                            self.set_pairing_file(p, None);
+                            self.pairing_file_device = Some(udid);
+                            self.vault_source = Some((source, device_name));
+                            self.keep_in_vault();
+                            self.pairing_file_message = None;
+                        }
+                        Err(e) => self.pairing_file_message = Some(e.to_string()),
+                    }
+                }
+                GuiCommands::Validated((host_id, ip, res)) => {
+                    if let Some(vault) = &mut self.vault
+                        && let Err(e) = vault.record_validation(&host_id, ip, &res)
+                    {
+                        warn!("Failed to record validation in the vault: {e}");
+                    }
+                    // Ignore results for a pairing file that has since been replaced
+                    if self.pairing_file.as_ref().map(|x| &x.host_id) == Some(&host_id) {
+                        self.validate_res = Some(res.map_err(|e| e.to_string()));
+                    }
+                }
                 GuiCommands::InstalledApps(apps) => self.installed_apps = Some(apps),
-                GuiCommands::InstallPairingFile((name, res)) => {
-                    if let Some(v) = self.install_res.get_mut(&name) {
+                GuiCommands::InstallPairingFile((host_id, name, bundle_id, res)) => {
+                    if res.is_ok()
+                        && let Some(vault) = &mut self.vault
+                        && let Err(e) = vault.record_install(&host_id, &name, Some(&bundle_id))
+                    {
+                        warn!("Failed to record the install in the vault: {e}");
+                    }
+                    // An install can add a backup, so a shown list is out of date
+                    self.backups.remove(&bundle_id);
+                    if let Some(v) = self.install_res.get_mut(&(name, bundle_id)) {
                         *v = Some(res)
                     }
                 }
+                GuiCommands::Backups((bundle_id, res)) => {
+                    self.backups.insert(bundle_id, Some(res));
+                }
+                GuiCommands::ContainerChanged((bundle_id, res)) => {
+                    self.container_res.insert(bundle_id, Some(res));
+                }
+                // Ignore what's no longer being looked at
+                GuiCommands::ContainerDir((bundle_id, dir, res)) => {
+                    if let Some(browser) = &mut self.browser
+                        && browser.bundle_id == bundle_id
+                        && browser.dir == dir
+                    {
+                        browser.entries = Some(res);
+                    }
+                }
+                GuiCommands::ContainerFile((bundle_id, path, res)) => {
+                    if let Some(browser) = &mut self.browser
+                        && browser.bundle_id == bundle_id
+                    {
+                        browser.file_read(path, res);
+                    }
+                }
             },
             Err(e) => match e {
                 tokio::sync::mpsc::error::TryRecvError::Empty => {}
@@ -754,6 +1990,83 @@ impl eframe::App for MyApp {
                 }
             },
         }
+        if self.show_settings {
+            let mut open = true;
+            egui::Window::new("settings")
+                .open(&mut open)
+                .show(ctx, |ui| self.settings_ui(ui));
+            self.show_settings &= open;
+        }
+        if self.show_vault {
+            let mut open = true;
+            egui::Window::new("vault")
+                .open(&mut open)
+                .show(ctx, |ui| self.vault_ui(ui));
+            self.show_vault &= open;
+        }
+        if let Some(title) = self.browser.as_ref().map(|x| format!("files in {}", x.app)) {
+            let mut open = true;
+            egui::Window::new(title)
+                .id(egui::Id::new("files"))
+                .open(&mut open)
+                .show(ctx, |ui| self.browser_ui(ui));
+            if !open {
+                self.browser = None;
+            }
+        }
+        if let Some((action, dev)) = self.confirm_action.clone() {
+            let name = self
+                .devices
+                .as_ref()
+                .and_then(|x| x.get(&dev.udid))
+                .map(|x| x.name.clone())
+                .unwrap_or_else(|| dev.udid.clone());
+            let mut open = true;
+            let mut confirmed = None;
+            egui::Window::new("confirm")
+                .collapsible(false)
+                .resizable(false)
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.label(RichText::new(format!("{} on {name}?", action.label())).strong());
+                    ui.label(action.consequence());
+                    ui.horizontal(|ui| {
+                        if ui.button(action.label()).clicked() {
+                            confirmed = Some(true);
+                        }
+                        if ui.button("Cancel").clicked() {
+                            confirmed = Some(false);
+                        }
+                    });
+                });
+            if confirmed == Some(true) {
+                self.run_action(action, dev);
+            }
+            if !open || confirmed.is_some() {
+                self.confirm_action = None;
+            }
+        }
+        if self.show_convert {
+            let mut open = true;
+            egui::Window::new("convert")
+                .open(&mut open)
+                .show(ctx, |ui| self.convert_ui(ui));
+            self.show_convert &= open;
+        }
+        if self.show_ddi {
+            let mut open = true;
+            egui::Window::new("images")
+                .open(&mut open)
+                .show(ctx, |ui| self.ddi_ui(ui));
+            self.show_ddi &= open;
+        }
+        if self.show_apps {
+            let mut open = true;
+            egui::Window::new("apps")
+                .open(&mut open)
+                .show(ctx, |ui| self.apps_ui(ui));
+            self.show_apps &= open;
+        }
         if self.show_logs {
             egui::Window::new("logs")
                 .open(&mut self.show_logs)
@@ -777,6 +2090,60 @@ impl eframe::App for MyApp {
                         .show(ui);
                 });
         }
+        if let Some(path) = &self.locked_file {
+            let file_name = path
+                .file_name()
+                .map(|x| x.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let mut unlock = false;
+            let mut cancel = false;
+            let modal = egui::Modal::new(egui::Id::new("unlock_pairing_file")).show(ctx, |ui| {
+                ui.heading("Encrypted pairing file");
+                ui.label(format!("Enter the passphrase for {file_name}"));
+                let input =
+                    ui.add(egui::TextEdit::singleline(&mut self.unlock_passphrase).password(true));
+                input.request_focus();
+                if input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    unlock = true;
+                }
+                if let Some(e) = &self.unlock_error {
+                    ui.label(RichText::new(e).color(Color32::RED));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Open").clicked() {
+                        unlock = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+            if unlock {
+                self.unlock_pairing_file();
+            }
+            if cancel || modal.should_close() {
+                self.locked_file = None;
+                self.unlock_passphrase.clear();
+            }
+        }
+        if self.confirm_reveal {
+            let modal = egui::Modal::new(egui::Id::new("reveal_private_keys")).show(ctx, |ui| {
+                ui.heading("Reveal private keys?");
+                ui.label("Anyone who sees these keys can take control of your device. Don't reveal them while sharing your screen.");
+                ui.horizontal(|ui| {
+                    if ui.button(RichText::new("Reveal").color(Color32::RED)).clicked() {
+                        self.reveal_private_keys = true;
+                        self.confirm_reveal = false;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_reveal = false;
+                    }
+                });
+            });
+            if modal.should_close() {
+                self.confirm_reveal = false;
+            }
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.horizontal(|ui| {
@@ -788,60 +2155,49 @@ impl eframe::App for MyApp {
                     };
                     egui::frame::Frame::new().corner_radius(3).inner_margin(3).fill(p_background_color).show(ui, |ui| {
                         ui.toggle_value(&mut self.show_logs, "logs");
+                        ui.toggle_value(&mut self.show_settings, "settings");
+                        ui.toggle_value(&mut self.show_vault, "vault");
+                        ui.toggle_value(&mut self.show_apps, "apps");
+                        ui.toggle_value(&mut self.show_convert, "convert");
+                        ui.toggle_value(&mut self.show_ddi, "images");
+                        if self.settings.device_access == DeviceAccess::Inspect {
+                            ui.label(RichText::new("inspect mode").weak())
+                                .on_hover_text("Devices are only read from, change this in settings");
+                        }
                     });
                 });
+                let mut clicked_device = None;
                 match &self.devices {
                     Some(devs) => {
                         if devs.is_empty() {
-                            ui.label("No devices connected! Plug one in via USB.");
+                            ui.label("No devices connected! Plug one in via USB, or connect it over the network.");
                         } else {
                             ui.horizontal(|ui| {
                                 ui.vertical(|ui| {
                                     ui.label("Choose a device");
+                                    let selected_text = match devs.get(&self.selected_device) {
+                                        Some(dev) => dev.label(),
+                                        None if self.selected_device.is_empty() => String::new(),
+                                        None => format!("Disconnected ({})", self.selected_device),
+                                    };
                                     ComboBox::from_label("")
-                                        .selected_text(&self.selected_device)
+                                        .selected_text(selected_text)
                                         .show_ui(ui, |ui| {
-                                            for (dev_name, dev) in devs {
+                                            for (udid, dev) in devs {
                                                 if ui
                                                     .selectable_value(
                                                         &mut self.selected_device,
-                                                        dev_name.clone(),
-                                                        dev_name.clone(),
+                                                        udid.clone(),
+                                                        dev.label(),
                                                     )
                                                     .clicked()
                                                 {
-                                                    // Get device info immediately
-                                                    self.wireless_enabled = None;
-                                                    self.dev_mode_enabled = None;
-                                                    self.ddi_mounted = None;
-                                                    self.device_info = None;
-
-                                                    // Send all device info requests
-                                                    let dev_clone = dev.clone();
-                                                    self.idevice_sender
-                                                        .send(IdeviceCommands::EnableWireless(dev_clone.clone()))
-                                                        .unwrap();
-                                                    self.idevice_sender
-                                                        .send(IdeviceCommands::CheckDevMode(dev_clone.clone()))
-                                                        .unwrap();
-                                                    self.idevice_sender
-                                                        .send(IdeviceCommands::AutoMount(dev_clone.clone()))
-                                                        .unwrap();
-                                                    self.idevice_sender
-                                                        .send(IdeviceCommands::GetDeviceInfo(dev_clone))
-                                                        .unwrap();self.pairing_file = None;
-                                                    self.pairing_file_message = None;
-                                                    self.pairing_file_string = None;
-                                                    self.installed_apps = None;
-                                                    self.device_info = None;
-                                                    self.idevice_sender.send(IdeviceCommands::InstalledApps((dev.clone(), self.supported_apps.keys().map(|x| x.to_owned()).collect()))).unwrap();
-                                                    self.validating = false;
-                                                    self.validate_res = None;
-                                                };
+                                                    clicked_device = Some(dev.clone());
+                                                }
                                             }
                                         });
                                 });
-                                
+
                                 ui.separator();
 
                                 // Show device info to the right if available
@@ -862,21 +2218,52 @@ impl eframe::App for MyApp {
                         ui.label(&self.devices_placeholder);
                     }
                 }
+                if let Some(dev) = clicked_device {
+                    self.select_device(&dev);
+                }
 
                 ui.separator();
 
-                if let Some(dev) = self
+                let selected = self
                     .devices
                     .as_ref()
                     .and_then(|x| x.get(&self.selected_device))
-                {
+                    .cloned();
+                let is_network = selected.as_ref().is_some_and(|x| x.is_network());
+                let network_address = selected.as_ref().and_then(|x| x.network_address());
+                let dev = selected.map(|x| x.usbmuxd);
+                let read_only = self.settings.device_access == DeviceAccess::Inspect;
+
+                if dev.is_some() {
                     ui.horizontal(|ui| {
                         ui.label("Wireless Debugging:");
                         match &self.wireless_enabled {
-                            Some(Ok(_)) => ui.label(RichText::new("Enabled").color(Color32::GREEN)),
-                            Some(Err(e)) => ui
-                                .label(RichText::new(format!("Failed: {e:?}")).color(Color32::RED)),
-                            None => ui.label("Loading..."),
+                            _ if is_network => {
+                                ui.label(
+                                    RichText::new("Enabled (connected over the network)")
+                                        .color(Color32::GREEN),
+                                );
+                            }
+                            Some(Ok(true)) => {
+                                ui.label(RichText::new("Enabled").color(Color32::GREEN));
+                            }
+                            Some(Ok(false)) => {
+                                ui.label("Disabled");
+                            }
+                            Some(Err(e)) => {
+                                ui.label(RichText::new(format!("Failed: {e:?}")).color(Color32::RED));
+                                if ui.button("Retry").clicked()
+                                    && let Some(dev) = &dev
+                                {
+                                    self.idevice_sender
+                                        .send(IdeviceCommands::CheckWireless(dev.clone()))
+                                        .unwrap();
+                                    self.wireless_enabled = None;
+                                }
+                            }
+                            None => {
+                                ui.label("Loading...");
+                            }
                         };
                     });
                     ui.horizontal(|ui| {
@@ -894,8 +2281,12 @@ impl eframe::App for MyApp {
                         };
                     });
                     ui.horizontal(|ui| {
-                        ui.label("Developer Disk Image (iOS 17+):");
-                        match &self.ddi_mounted {
+                        ui.label("Developer Disk Image:");
+                        match &self.mounted_images {
+                            _ if is_network => {
+                                ui.label(RichText::new("Connect over USB to mount").weak())
+                            }
+                            Some(Ok(images)) if images.is_empty() => ui.label("Not mounted"),
                             Some(Ok(_)) => {
                                 ui.label(RichText::new("Mounted").color(Color32::GREEN))
                             }
@@ -904,10 +2295,66 @@ impl eframe::App for MyApp {
                             None => ui.label("Loading..."),
                         };
                     });
-
-                    // How to load a file
+                    if let Some(Ok(images)) = &self.mounted_images
+                        && !is_network
+                        && !images.is_empty()
+                    {
+                        egui::Grid::new("mounted_images").striped(true).show(ui, |ui| {
+                            for image in images {
+                                ui.label(&image.image_type);
+                                ui.label(
+                                    RichText::new(image.mount_path.as_deref().unwrap_or("-"))
+                                        .monospace(),
+                                );
+                                match (&image.signature, image.short_signature()) {
+                                    (Some(full), Some(short)) => {
+                                        ui.label(RichText::new(format!("{short}…")).monospace())
+                                            .on_hover_text(full);
+                                    }
+                                    _ => {
+                                        ui.label(RichText::new("No signature").weak());
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    }
+                    if let Some(dev) = &dev
+                        && !is_network
+                    {
+                        ui.horizontal(|ui| {
+                            ui.label("Actions:");
+                            if read_only {
+                                ui.label(
+                                    RichText::new("Inspect mode, nothing on the device is changed")
+                                        .weak(),
+                                );
+                                return;
+                            }
+                            let wireless = match &self.wireless_enabled {
+                                Some(Ok(true)) => Some(DeviceAction::DisableWireless),
+                                Some(Ok(false)) => Some(DeviceAction::EnableWireless),
+                                _ => None,
+                            };
+                            let mount: &[DeviceAction] = match &self.mounted_images {
+                                Some(Ok(images)) if images.is_empty() => &[DeviceAction::Mount],
+                                Some(Ok(_)) => &[DeviceAction::Unmount, DeviceAction::Remount],
+                                Some(Err(_)) => &[DeviceAction::Mount],
+                                None => &[],
+                            };
+                            for action in wireless.into_iter().chain(mount.iter().cloned()) {
+                                if ui.button(action.label()).clicked() {
+                                    self.confirm_action = Some((action, dev.clone()));
+                                }
+                            }
+                        });
+                    }
                     ui.separator();
-                    ui.horizontal(|ui| {
+                }
+
+                // How to load a file
+                ui.horizontal(|ui| {
+                    if let Some(dev) = &dev {
                         ui.vertical(|ui| {
                             ui.heading("Load");
                             ui.label("Load the pairing file from the system.");
@@ -916,29 +2363,12 @@ impl eframe::App for MyApp {
                                 {
                                     let ctrl_down = ui.input(|i| i.modifiers.ctrl || i.modifiers.command);
                                     if ctrl_down && self.pairing_file.is_some() {
-                                        if let Some(p) = FileDialog::new()
-                                            .set_can_create_directories(true)
-                                            .set_title("Save Pairing File")
-                                            .set_file_name(format!("{}.plist", &dev.udid))
-                                            .save_file()
-                                        {
-                                            if let Err(e) = std::fs::write(
-                                                p,
-                                                self.pairing_file
-                                                    .as_ref()
-                                                    .unwrap()
-                                                    .clone()
-                                                    .serialize()
-                                                    .unwrap(),
-                                            ) {
-                                                self.save_error = Some(e.to_string());
-                                            }
-                                        }
+                                        self.save_pairing_file();
                                     } else {
                                         self.pairing_file_message = Some("Loading...".to_string());
                                         self.pairing_file_string = None;
                                         self.idevice_sender
-                                            .send(IdeviceCommands::LoadPairingFile(dev.clone()))
+                                            .send(IdeviceCommands::LoadPairingFile((dev.clone(), self.device_name(&dev.udid))))
                                             .unwrap();
                                     }
                                 }
@@ -947,107 +2377,212 @@ impl eframe::App for MyApp {
                                     self.pairing_file_message = Some("Loading...".to_string());
                                     self.pairing_file_string = None;
                                     self.idevice_sender
-                                        .send(IdeviceCommands::LoadPairingFile(dev.clone()))
+                                        .send(IdeviceCommands::LoadPairingFile((dev.clone(), self.device_name(&dev.udid))))
                                         .unwrap();
                                 }
                             }
                         });
                         ui.separator();
                         #[cfg(feature = "generate")]
-                        ui.vertical(|ui| {
-                            ui.heading("Generate");
-                            ui.label("Generate a new pairing file. This may invalidate old ones.");
-                            if ui.button("Generate").clicked() {
-                                self.pairing_file_message = Some("Loading...".to_string());
-                                self.pairing_file_string = None;
-                                self.idevice_sender
-                                    .send(IdeviceCommands::GeneratePairingFile(dev.clone()))
-                                    .unwrap();
-                            }
-                        });
-                    });
-                    if let Some(msg) = &self.pairing_file_message {
-                        ui.label(msg);
+                        if !is_network {
+                            ui.vertical(|ui| {
+                                ui.heading("Generate");
+                                ui.label("Generate a new pairing file. This may invalidate old ones.");
+                                if ui.add_enabled(!read_only, egui::Button::new("Generate")).clicked() {
+                                    self.confirm_action = Some((DeviceAction::Generate, dev.clone()));
+                                }
+                            });
+                            ui.separator();
+                        }
                     }
+                    ui.vertical(|ui| {
+                        ui.heading("Open");
+                        ui.label("Open a pairing file you already have, no device needed.");
+                        if ui.button("Open file…").clicked() {
+                            self.open_pairing_file();
+                        }
+                    });
+                });
+                if let Some(msg) = &self.pairing_file_message {
+                    ui.label(msg);
+                }
 
-                    ui.separator();
-
-                    if let Some(pairing_file) = &self.pairing_file_string {
-                        egui::Grid::new("reee").min_col_width(200.0).show(ui, |ui| {
-                            ui.vertical(|ui| {
-                                #[cfg(feature = "generate")]
-                                {
-                                    ui.heading("Save to File");
-                                    if let Some(msg) = &self.save_error {
-                                        ui.label(RichText::new(msg).color(Color32::RED));
-                                    }
-                                    ui.label("Save this file to your computer, and then transfer it to your device manually.");
-                                    if ui.button("Save to File").clicked()
-                                        && let Some(p) = FileDialog::new()
-                                            .set_can_create_directories(true)
-                                            .set_title("Save Pairing File")
-                                            .set_file_name(format!("{}.plist", &dev.udid))
-                                            .save_file()
-                                        {
-                                            self.save_error = None;
-                                            if let Err(e) = std::fs::write(
-                                                p,
-                                                self.pairing_file
-                                                    .as_ref()
-                                                    .unwrap()
-                                                    .clone()
-                                                    .serialize()
-                                                    .unwrap(),
-                                            ) {
-                                                self.save_error = Some(e.to_string());
-                                            }
-                                        
-                                    }
+                ui.separator();
 
-                                    ui.separator();
+                if let Some(pairing_file) = self.pairing_file_string.clone() {
+                    egui::Grid::new("reee").min_col_width(200.0).show(ui, |ui| {
+                        ui.vertical(|ui| {
+                            #[cfg(feature = "generate")]
+                            {
+                                ui.heading("Save to File");
+                                if let Some(msg) = &self.save_error {
+                                    ui.label(RichText::new(msg).color(Color32::RED));
                                 }
-                                ui.heading("Validation");
-                                ui.label("Verify that your pairing file works over LAN. Your device will be searched for over your network.");
-                                ui.add(egui::TextEdit::singleline(&mut self.validation_ip_input).hint_text("OR enter your device's IP..."));
-                                if ui.button("Validate").clicked() {
-                                    self.validating = true;
-                                    self.validate_res = None;
-                                    if self.validation_ip_input.is_empty() {
-                                        self.idevice_sender.send(IdeviceCommands::Validate((None, self.pairing_file.clone().unwrap()))).unwrap()
-                                    } else {
-                                        match IpAddr::from_str(self.validation_ip_input.as_str()) {
-                                            Ok(i) => {
-                                                self.idevice_sender.send(IdeviceCommands::Validate((Some(i), self.pairing_file.clone().unwrap()))).unwrap()
-                                            },
-                                            Err(_) => self.validate_res = Some(Err("Invalid IP".to_string()))
-                                        };
+                                ui.label("Save this file to your computer, and then transfer it to your device manually.");
+                                ui.checkbox(&mut self.save_encrypted, "Encrypt with a passphrase");
+                                if self.save_encrypted {
+                                    ui.add(egui::TextEdit::singleline(&mut self.save_passphrase).password(true).hint_text("Passphrase"));
+                                    ui.add(egui::TextEdit::singleline(&mut self.save_passphrase_confirm).password(true).hint_text("Confirm passphrase"));
+                                } else {
+                                    ui.horizontal(|ui| {
+                                        ui.label("Save as");
+                                        save_as_combo(ui, "save_as", &mut self.save_as, &self.apps);
+                                    });
+                                    let needs_udid = match &self.save_as {
+                                        SaveAs::Format(_) => {
+                                            ui.checkbox(&mut self.save_udid, "Add the device's UDID if it's missing");
+                                            self.save_udid
+                                        }
+                                        SaveAs::App(name) => self.apps.get(name).is_some_and(|x| x.udid),
+                                    };
+                                    // Opened files don't say which device they're for, so it isn't guessed
+                                    if needs_udid && self.pairing_file.as_ref().is_some_and(|x| x.udid.is_none()) && self.pairing_file_device.is_none() {
+                                        ui.add(egui::TextEdit::singleline(&mut self.save_udid_input).hint_text("Device UDID"));
                                     }
                                 }
-                                if self.validating {
-                                    match &self.validate_res {
-                                        Some(Ok(_)) => ui.label(RichText::new("Success").color(Color32::GREEN)),
-                                        Some(Err(e)) =>ui.label(RichText::new(e).color(Color32::RED)),
-                                        None => ui.label("Loading..."),
+                                if ui.button("Save to File").clicked() {
+                                    self.save_pairing_file();
+                                }
+
+                                ui.separator();
+                            }
+                            ui.heading("Validation");
+                            ui.label("Verify that your pairing file works over LAN. Your device will be searched for over your network.");
+                            ui.add(egui::TextEdit::singleline(&mut self.validation_ip_input).hint_text("OR enter your device's IP..."));
+                            if ui.button("Validate").clicked() {
+                                self.validating = true;
+                                self.validate_res = None;
+                                if self.validation_ip_input.is_empty() {
+                                    self.idevice_sender.send(IdeviceCommands::Validate((network_address, self.pairing_file.clone().unwrap()))).unwrap()
+                                } else {
+                                    match IpAddr::from_str(self.validation_ip_input.as_str()) {
+                                        Ok(i) => {
+                                            self.idevice_sender.send(IdeviceCommands::Validate((Some(i), self.pairing_file.clone().unwrap()))).unwrap()
+                                        },
+                                        Err(_) => self.validate_res = Some(Err("Invalid IP".to_string()))
                                     };
                                 }
+                            }
+                            if self.validating {
+                                match &self.validate_res {
+                                    Some(Ok(_)) => ui.label(RichText::new("Success").color(Color32::GREEN)),
+                                    Some(Err(e)) =>ui.label(RichText::new(e).color(Color32::RED)),
+                                    None => ui.label("Loading..."),
+                                };
+                            }
 
+                            if let Some(dev) = &dev {
                                 match &self.installed_apps {
                                     Some(Ok(apps)) => {
-                                        for (name, bundle_id) in apps {
+                                        if !apps.is_empty() {
+                                            ui.separator();
+                                            ui.heading("All Apps");
+                                            ui.label("Install the pairing file into every app below at once.");
+                                            if ui.add_enabled(!read_only, egui::Button::new("Install to All")).clicked() {
+                                                let supported: Vec<SupportedApp> = self.apps.apps().into_iter().cloned().collect();
+                                                let targets = apps::install_targets(&supported, apps);
+                                                self.confirm_action = Some((DeviceAction::InstallToAll(targets), dev.clone()));
+                                            }
+                                            if !self.install_batch.is_empty() {
+                                                install_summary(ui, &self.install_batch, &self.install_res);
+                                            }
+                                        }
+                                        let mut names: Vec<&String> = apps.keys().collect();
+                                        names.sort_by_key(|x| x.to_lowercase());
+                                        for name in names {
+                                            let candidates = &apps[name];
                                             ui.separator();
                                             ui.heading(name);
-                                            ui.label(RichText::new(bundle_id).italics().weak());
-                                            ui.label(format!("{name} is installed on your device. You can automatically install the pairing file into the app."));
-                                            if ui.button("Install").clicked() {
-                                                self.idevice_sender.send(IdeviceCommands::InstallPairingFile((dev.clone(), name.clone(), bundle_id.clone(), self.supported_apps.get(name).unwrap().to_owned(), self.pairing_file.clone().unwrap()))).unwrap();
-                                                self.install_res.insert(name.to_owned(), None);
+                                            if candidates.len() == 1 {
+                                                ui.label(format!("{name} is installed on your device. You can automatically install the pairing file into the app."));
+                                            } else {
+                                                ui.label(format!("{} copies of {name} are installed on your device. Install the pairing file into the ones you use.", candidates.len()));
                                             }
-                                            if let Some(v) = self.install_res.get(name) {
-                                                match v {
-                                                    Some(Ok(_)) => ui.label(RichText::new("Success").color(Color32::GREEN)),
-                                                    Some(Err(e)) => ui.label(RichText::new(e.to_string()).color(Color32::RED)),
-                                                    None => ui.label("Installing..."),
-                                                };
+                                            for candidate in candidates {
+                                                ui.horizontal(|ui| {
+                                                    if let Some(display_name) = candidate.display_name.as_deref().filter(|x| x != name) {
+                                                        ui.label(display_name);
+                                                    }
+                                                    ui.label(RichText::new(&candidate.bundle_id).italics().weak());
+                                                });
+                                                ui.horizontal(|ui| {
+                                                    if ui.add_enabled(!read_only, egui::Button::new("Install")).clicked() {
+                                                        self.confirm_action = Some((DeviceAction::Install((name.clone(), candidate.bundle_id.clone())), dev.clone()));
+                                                    }
+                                                    if let Some(v) = self.install_res.get(&(name.clone(), candidate.bundle_id.clone())) {
+                                                        match v {
+                                                            Some(Ok(report)) => {
+                                                                ui.label(RichText::new("Installed and verified").color(Color32::GREEN));
+                                                                if let Some(backup) = &report.backup {
+                                                                    ui.label(RichText::new(format!("Previous file kept at {backup}")).weak());
+                                                                }
+                                                            }
+                                                            Some(Err(e)) => {
+                                                                ui.label(RichText::new(error_chain(e)).color(Color32::RED));
+                                                            }
+                                                            None => {
+                                                                ui.label("Installing...");
+                                                            }
+                                                        }
+                                                    }
+                                                });
+                                                ui.horizontal(|ui| {
+                                                    if let Some(app) = self.apps.get(name) {
+                                                        let target = (dev.clone(), candidate.bundle_id.clone(), app.clone());
+                                                        if ui.add_enabled(!read_only, egui::Button::new("Remove")).on_hover_text("Move the pairing file aside as a backup").clicked() {
+                                                            self.confirm_action = Some((DeviceAction::Remove((name.clone(), candidate.bundle_id.clone())), dev.clone()));
+                                                        }
+                                                        if ui.add_enabled(!read_only, egui::Button::new("Restore Previous")).clicked() {
+                                                            self.confirm_action = Some((DeviceAction::Restore((name.clone(), candidate.bundle_id.clone(), None)), dev.clone()));
+                                                        }
+                                                        if ui.button("Backups").clicked() {
+                                                            self.idevice_sender.send(IdeviceCommands::ListBackups(target)).unwrap();
+                                                            self.backups.insert(candidate.bundle_id.clone(), None);
+                                                        }
+                                                        if ui.button("Browse").on_hover_text("Look through the app's Documents folder").clicked() {
+                                                            let mut browser = Browser::new(dev.clone(), candidate.bundle_id.clone(), name.clone());
+                                                            browser.open_dir(&self.idevice_sender, container::DOCUMENTS.to_string());
+                                                            self.browser = Some(browser);
+                                                        }
+                                                    }
+                                                    match self.container_res.get(&candidate.bundle_id) {
+                                                        Some(Some(Ok(message))) => {
+                                                            ui.label(RichText::new(message).color(Color32::GREEN));
+                                                        }
+                                                        Some(Some(Err(e))) => {
+                                                            ui.label(RichText::new(error_chain(e)).color(Color32::RED));
+                                                        }
+                                                        Some(None) => {
+                                                            ui.label("Working...");
+                                                        }
+                                                        None => {}
+                                                    }
+                                                });
+                                                match self.backups.get(&candidate.bundle_id) {
+                                                    Some(Some(Ok(backups))) if backups.is_empty() => {
+                                                        ui.label(RichText::new("No backups").weak());
+                                                    }
+                                                    Some(Some(Ok(backups))) => {
+                                                        egui::Grid::new(("backups", &candidate.bundle_id)).num_columns(3).show(ui, |ui| {
+                                                            for backup in backups {
+                                                                ui.label(backup.taken.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+                                                                    .on_hover_text(&backup.path);
+                                                                ui.label(format!("{} bytes", backup.size));
+                                                                if ui.add_enabled(!read_only, egui::Button::new("Restore")).clicked() {
+                                                                    self.confirm_action = Some((DeviceAction::Restore((name.clone(), candidate.bundle_id.clone(), Some(backup.path.clone()))), dev.clone()));
+                                                                }
+                                                                ui.end_row();
+                                                            }
+                                                        });
+                                                    }
+                                                    Some(Some(Err(e))) => {
+                                                        ui.label(RichText::new(format!("Failed to list backups: {}", error_chain(e))).color(Color32::RED));
+                                                    }
+                                                    Some(None) => {
+                                                        ui.label("Listing backups...");
+                                                    }
+                                                    None => {}
+                                                }
                                             }
                                         }
                                     }
@@ -1058,18 +2593,200 @@ impl eframe::App for MyApp {
                                         ui.label("Getting installed apps...");
                                     }
                                 }
-                            });
-                            let p_background_color = match ctx.theme() {
-                                egui::Theme::Dark => Color32::BLACK,
-                                egui::Theme::Light => Color32::LIGHT_GRAY,
-                            };
-                            egui::frame::Frame::new().corner_radius(10).inner_margin(10).fill(p_background_color).show(ui, |ui| {
-                                ui.label(RichText::new(pairing_file).monospace());
+                            }
+                        });
+                        let p_background_color = match ctx.theme() {
+                            egui::Theme::Dark => Color32::BLACK,
+                            egui::Theme::Light => Color32::LIGHT_GRAY,
+                        };
+                        ui.vertical(|ui| {
+                            if let Some(report) = &self.pairing_file_report {
+                                egui::frame::Frame::new().corner_radius(10).inner_margin(10).fill(p_background_color).show(ui, |ui| {
+                                    pairing_file_inspector(ui, report);
+                                });
+                            }
+                            egui::CollapsingHeader::new("Raw pairing file").default_open(true).show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    if self.reveal_private_keys {
+                                        if ui.button("Hide private keys").clicked() {
+                                            self.reveal_private_keys = false;
+                                        }
+                                    } else if ui.button("Reveal private keys").clicked() {
+                                        self.confirm_reveal = true;
+                                    }
+                                    // Always redacted, Save to File is the way to get the real thing
+                                    if ui.button("Copy (redacted)").clicked() {
+                                        ui.ctx().copy_text(pairing_file.clone());
+                                    }
+                                    if self.vault.is_some() && self.vault_source.is_some() && ui.button("Add to Vault").on_hover_text("Keep a copy, readable only by your user, in the vault").clicked() {
+                                        self.pairing_file_message = Some(match self.add_to_vault() {
+                                            Ok(()) => "Added to the vault".to_string(),
+                                            Err(e) => format!("Failed to add to the vault: {e}"),
+                                        });
+                                    }
+                                });
+                                let text = match &self.pairing_file {
+                                    Some(p) if self.reveal_private_keys => match p.clone().serialize() {
+                                        Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+                                        Err(e) => format!("Failed to display pairing file: {e}"),
+                                    },
+                                    _ => pairing_file,
+                                };
+                                egui::frame::Frame::new().corner_radius(10).inner_margin(10).fill(p_background_color).show(ui, |ui| {
+                                    ui.label(RichText::new(text).monospace());
+                                });
                             });
                         });
-                    }
+                    });
                 }
             });
         });
     }
 }
+
+/// Shows the identifiers and certificates of a pairing file, with any warnings first
+fn pairing_file_inspector(ui: &mut egui::Ui, report: &PairingFileReport) {
+    ui.heading("Pairing File");
+    for warning in &report.warnings {
+        ui.label(RichText::new(format!("⚠ {warning}")).color(Color32::ORANGE));
+    }
+
+    egui::Grid::new("pairing_file_fields")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("HostID:");
+            ui.label(&report.host_id);
+            ui.end_row();
+            ui.label("SystemBUID:");
+            ui.label(&report.system_buid);
+            ui.end_row();
+            ui.label("WiFiMACAddress:");
+            ui.label(&report.wifi_mac_address);
+            ui.end_row();
+            ui.label("UDID:");
+            match &report.udid {
+                Some(udid) => ui.label(udid),
+                None => ui.label(RichText::new("Missing").color(Color32::RED)),
+            };
+            ui.end_row();
+            ui.label("Escrow Bag:");
+            ui.label(if report.has_escrow_bag {
+                "Present"
+            } else {
+                "Missing"
+            });
+            ui.end_row();
+        });
+
+    for (name, cert) in [
+        ("Device Certificate", &report.device_certificate),
+        ("Host Certificate", &report.host_certificate),
+        ("Root Certificate", &report.root_certificate),
+    ] {
+        egui::CollapsingHeader::new(name).show(ui, |ui| match cert {
+            Some(cert) => certificate_details(ui, name, cert),
+            None => {
+                ui.label(RichText::new("Unreadable").color(Color32::RED));
+            }
+        });
+    }
+}
+
+fn certificate_details(ui: &mut egui::Ui, id: &str, cert: &CertificateSummary) {
+    egui::Grid::new(id).num_columns(2).show(ui, |ui| {
+        ui.label("Subject:");
+        ui.label(&cert.subject);
+        ui.end_row();
+        ui.label("Issuer:");
+        ui.label(&cert.issuer);
+        ui.end_row();
+        ui.label("Serial:");
+        ui.label(&cert.serial);
+        ui.end_row();
+        ui.label("Valid From:");
+        ui.label(cert.not_before.to_string());
+        ui.end_row();
+        ui.label("Valid Until:");
+        ui.label(cert.not_after.to_string());
+        ui.end_row();
+        ui.label("Key:");
+        ui.label(&cert.key_type);
+        ui.end_row();
+    });
+}
+
+/// Picks a plain plist format, or the way one of the registry's apps reads pairing files
+fn save_as_combo(ui: &mut egui::Ui, id_salt: &str, value: &mut SaveAs, apps: &AppRegistry) {
+    ComboBox::from_id_salt(id_salt)
+        .selected_text(value.to_string())
+        .show_ui(ui, |ui| {
+            for format in [pairing::Format::Xml, pairing::Format::Binary] {
+                let option = SaveAs::Format(format);
+                let text = option.to_string();
+                ui.selectable_value(value, option, text);
+            }
+            for app in apps.apps() {
+                let option = SaveAs::App(app.name.clone());
+                let text = option.to_string();
+                ui.selectable_value(value, option, text)
+                    .on_hover_text(format!("{} as a {} plist", app.file_name(), app.format));
+            }
+        });
+}
+
+/// The error with its sources, since the top level message alone doesn't say why
+fn error_chain(e: &Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(s) = source {
+        message.push_str(&format!(": {s}"));
+        source = s.source();
+    }
+    message
+}
+
+/// Progress of the last "Install to All", with the outcome for each app
+fn install_summary(ui: &mut egui::Ui, batch: &[InstallTarget], results: &InstallResults) {
+    let done: Vec<&Result<InstallReport, Error>> = batch
+        .iter()
+        .filter_map(|x| {
+            results
+                .get(&(x.app.name.clone(), x.bundle_id.clone()))?
+                .as_ref()
+        })
+        .collect();
+    let failed = done.iter().filter(|x| x.is_err()).count();
+    if done.len() < batch.len() {
+        ui.label(format!(
+            "Installing... {} of {} done",
+            done.len(),
+            batch.len()
+        ));
+    } else if failed == 0 {
+        ui.label(RichText::new("Every install was verified").color(Color32::GREEN));
+    } else {
+        ui.label(
+            RichText::new(format!("{failed} of {} installs failed", batch.len()))
+                .color(Color32::RED),
+        );
+    }
+
+    egui::Grid::new("install_summary")
+        .num_columns(3)
+        .show(ui, |ui| {
+            for target in batch {
+                ui.label(&target.app.name);
+                ui.label(RichText::new(&target.bundle_id).italics().weak());
+                match results.get(&(target.app.name.clone(), target.bundle_id.clone())) {
+                    Some(Some(Ok(_))) => {
+                        ui.label(RichText::new("Installed and verified").color(Color32::GREEN))
+                    }
+                    Some(Some(Err(e))) => {
+                        ui.label(RichText::new(error_chain(e)).color(Color32::RED))
+                    }
+                    _ => ui.label("Installing..."),
+                };
+                ui.end_row();
+            }
+        });
+}
@@ -7,11 +7,96 @@ use idevice::{
     usbmuxd::{UsbmuxdAddr, UsbmuxdDevice},
 };
 
-const BUILD_MANIFEST: &[u8] = include_bytes!("../DDI/BuildManifest.plist");
-const DDI_IMAGE: &[u8] = include_bytes!("../DDI/Image.dmg");
-const DDI_TRUSTCACHE: &[u8] = include_bytes!("../DDI/Image.dmg.trustcache");
-pub async fn auto_mount(dev: UsbmuxdDevice) -> Result<(), IdeviceError> {
-    let p = dev.to_provider(UsbmuxdAddr::default(), "idevice_pair");
+use serde::Serialize;
+
+use crate::{Error, LABEL, ddi, device};
+
+/// A disk image mounted on the device, as the image mounter lists it
+#[derive(Debug, Clone, Serialize)]
+pub struct MountedImage {
+    /// `Developer` for images mounted before iOS 17, `Personalized` after
+    pub image_type: String,
+    /// The image's signature in hex, which tells images of the same type apart
+    pub signature: Option<String>,
+    /// Where it's mounted, which is what unmounting it takes
+    pub mount_path: Option<String>,
+}
+
+impl MountedImage {
+    fn from_entry(entry: &plist::Value) -> Self {
+        let entry = entry.as_dictionary();
+        let get = |key| entry.and_then(|x| x.get(key));
+        Self {
+            image_type: get("DiskImageType")
+                .and_then(|x| x.as_string())
+                .unwrap_or("Unknown")
+                .to_string(),
+            signature: get("ImageSignature")
+                .and_then(|x| x.as_data())
+                .map(|x| x.iter().map(|b| format!("{b:02x}")).collect()),
+            mount_path: get("MountPath")
+                .and_then(|x| x.as_string())
+                .map(|x| x.to_string()),
+        }
+    }
+
+    /// The start of the signature, enough to tell images apart at a glance
+    pub fn short_signature(&self) -> Option<&str> {
+        self.signature.as_deref().map(|x| &x[..x.len().min(16)])
+    }
+}
+
+/// The images mounted on the device, without changing anything
+pub async fn mounted_images(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+) -> Result<Vec<MountedImage>, Error> {
+    device::require_usb(dev)?;
+    let p = dev.to_provider(addr.clone(), LABEL);
+    let mut mc = ImageMounter::connect(&p).await?;
+    Ok(mc
+        .copy_devices()
+        .await?
+        .iter()
+        .map(MountedImage::from_entry)
+        .collect())
+}
+
+/// Unmounts the image mounted at `mount_path`
+pub async fn unmount(
+    addr: &UsbmuxdAddr,
+    dev: &UsbmuxdDevice,
+    mount_path: &str,
+) -> Result<(), Error> {
+    device::require_usb(dev)?;
+    let p = dev.to_provider(addr.clone(), LABEL);
+    let mut mc = ImageMounter::connect(&p).await?;
+    mc.unmount_image(mount_path).await?;
+    Ok(())
+}
+
+/// Unmounts every mounted image, returning the paths they were mounted at
+pub async fn unmount_all(addr: &UsbmuxdAddr, dev: &UsbmuxdDevice) -> Result<Vec<String>, Error> {
+    let mut unmounted = Vec::new();
+    for image in mounted_images(addr, dev).await? {
+        if let Some(path) = image.mount_path {
+            unmount(addr, dev, &path).await?;
+            unmounted.push(path);
+        }
+    }
+    Ok(unmounted)
+}
+
+/// Replaces whatever is mounted with the image for the device's iOS version, for a stale or mismatched one
+pub async fn remount(addr: &UsbmuxdAddr, dev: &UsbmuxdDevice) -> Result<(), Error> {
+    unmount_all(addr, dev).await?;
+    auto_mount(addr, dev).await
+}
+
+/// Mounts the developer disk image for the device's iOS version unless one is already mounted
+pub async fn auto_mount(addr: &UsbmuxdAddr, dev: &UsbmuxdDevice) -> Result<(), Error> {
+    device::require_usb(dev)?;
+    let p = dev.to_provider(addr.clone(), LABEL);
 
     let mut mc = ImageMounter::connect(&p).await?;
     let images = mc.copy_devices().await?;
@@ -20,17 +105,32 @@ pub async fn auto_mount(dev: UsbmuxdDevice) -> Result<(), IdeviceError> {
     }
 
     let mut lc = LockdownClient::connect(&p).await?;
+    let version = lc
+        .get_value(Some("ProductVersion"), None)
+        .await?
+        .as_string()
+        .ok_or(IdeviceError::UnexpectedResponse)?
+        .to_string();
+
+    if !ddi::is_personalized(&version) {
+        let ddi = ddi::legacy_image(&version)?;
+        log::info!("Mounting the iOS {} image on iOS {version}", ddi.version);
+        mc.mount_developer(&ddi.image, ddi.signature).await?;
+        return Ok(());
+    }
+
+    let ddi = ddi::personalized_image()?;
     let ucid = lc
         .get_value(Some("UniqueChipID"), None)
         .await?
         .as_unsigned_integer()
-        .unwrap();
+        .ok_or(IdeviceError::UnexpectedResponse)?;
 
     mc.mount_personalized(
         &p,
-        DDI_IMAGE.to_vec(),
-        DDI_TRUSTCACHE.to_vec(),
-        BUILD_MANIFEST,
+        ddi.image,
+        ddi.trustcache,
+        &ddi.build_manifest,
         None,
         ucid,
     )
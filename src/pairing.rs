@@ -0,0 +1,286 @@
+// Jackson Coxson
+
+use std::{
+    io::Write,
+    net::{IpAddr, SocketAddr},
+    path::Path,
+};
+
+use idevice::{
+    Idevice, IdeviceError, IdeviceService,
+    lockdown::LockdownClient,
+    pairing_file::PairingFile,
+    usbmuxd::{UsbmuxdAddr, UsbmuxdDevice},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error, LABEL,
+    device::{connect_usbmuxd, require_usb},
+    encryption,
+};
+
+/// How a pairing file is written out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    #[default]
+    Xml,
+    Binary,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Format::Xml => "XML",
+            Format::Binary => "binary",
+        })
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "xml" => Ok(Format::Xml),
+            "binary" => Ok(Format::Binary),
+            _ => Err(format!("unknown format {s}, expected xml or binary")),
+        }
+    }
+}
+
+/// Port lockdownd listens on when wireless debugging is enabled
+pub const LOCKDOWN_PORT: u16 = 62078;
+
+/// Keys holding the host's private keys, which give control over the device
+pub const PRIVATE_KEYS: [&str; 2] = ["HostPrivateKey", "RootPrivateKey"];
+
+/// Shown in place of private keys when a pairing file is displayed
+pub const REDACTED: &str = "<redacted>";
+
+/// Keys every pairing file must have, and whether they hold data or a string
+const REQUIRED_KEYS: [(&str, bool); 9] = [
+    ("DeviceCertificate", true),
+    ("HostCertificate", true),
+    ("HostPrivateKey", true),
+    ("RootCertificate", true),
+    ("RootPrivateKey", true),
+    ("EscrowBag", true),
+    ("SystemBUID", false),
+    ("HostID", false),
+    ("WiFiMACAddress", false),
+];
+
+/// Parses a pairing file, explaining what is wrong with it when it can't be used
+///
+/// [`PairingFile::from_bytes`] only reports that parsing failed, so the plist is
+/// checked key by key first.
+pub fn from_bytes(bytes: &[u8]) -> Result<PairingFile, Error> {
+    let value = plist::Value::from_reader(std::io::Cursor::new(bytes))
+        .map_err(|e| Error::InvalidPairingFile(format!("not a property list ({e})")))?;
+    let dict = value
+        .as_dictionary()
+        .ok_or_else(|| Error::InvalidPairingFile("root is not a dictionary".to_string()))?;
+
+    for (key, is_data) in REQUIRED_KEYS {
+        let v = dict
+            .get(key)
+            .ok_or_else(|| Error::InvalidPairingFile(format!("missing {key}")))?;
+        let valid = if is_data {
+            v.as_data().is_some()
+        } else {
+            v.as_string().is_some()
+        };
+        if !valid {
+            let expected = if is_data { "data" } else { "a string" };
+            return Err(Error::InvalidPairingFile(format!(
+                "{key} should be {expected}"
+            )));
+        }
+    }
+    if dict.get("UDID").is_some_and(|v| v.as_string().is_none()) {
+        return Err(Error::InvalidPairingFile(
+            "UDID should be a string".to_string(),
+        ));
+    }
+
+    PairingFile::from_value(&value).map_err(|e| Error::InvalidPairingFile(e.to_string()))
+}
+
+/// Serializes the pairing file as XML with the private keys masked
+///
+/// Use this anywhere a pairing file is shown, logged or copied, the result
+/// can't be used to connect to the device.
+pub fn redacted_xml(pairing_file: &PairingFile) -> Result<String, Error> {
+    let bytes = pairing_file.clone().serialize()?;
+    let mut value = plist::Value::from_reader_xml(bytes.as_slice()).map_err(IdeviceError::from)?;
+    redact(&mut value);
+
+    let mut buf = Vec::new();
+    value.to_writer_xml(&mut buf).map_err(IdeviceError::from)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Masks the private keys in a plist that holds a pairing file
+pub(crate) fn redact(value: &mut plist::Value) {
+    if let Some(dict) = value.as_dictionary_mut() {
+        for key in PRIVATE_KEYS {
+            if dict.contains_key(key) {
+                dict.insert(key.to_string(), plist::Value::String(REDACTED.to_string()));
+            }
+        }
+    }
+}
+
+/// Serializes the pairing file as an XML or binary plist
+pub fn serialize(pairing_file: &PairingFile, format: Format) -> Result<Vec<u8>, Error> {
+    let bytes = pairing_file.clone().serialize()?;
+    match format {
+        Format::Xml => Ok(bytes),
+        Format::Binary => {
+            let value =
+                plist::Value::from_reader_xml(bytes.as_slice()).map_err(IdeviceError::from)?;
+            let mut buf = Vec::new();
+            value
+                .to_writer_binary(&mut buf)
+                .map_err(IdeviceError::from)?;
+            Ok(buf)
+        }
+    }
+}
+
+/// The pairing file with `udid` added, unless it already says which device it's for
+pub fn with_udid(pairing_file: &PairingFile, udid: &str) -> PairingFile {
+    let mut pairing_file = pairing_file.clone();
+    pairing_file.udid.get_or_insert_with(|| udid.to_string());
+    pairing_file
+}
+
+/// Parses a plain or encrypted pairing file, `passphrase` is only needed for encrypted ones
+pub fn decode(bytes: &[u8], passphrase: Option<&str>) -> Result<PairingFile, Error> {
+    if !encryption::is_encrypted(bytes) {
+        return from_bytes(bytes);
+    }
+    match passphrase {
+        Some(p) => encryption::decrypt(bytes, p),
+        None => Err(Error::PassphraseRequired),
+    }
+}
+
+/// Serializes the pairing file as `format`, or encrypted when a passphrase is given
+///
+/// Encrypted files always hold XML, `format` only applies to plain ones.
+pub fn encode(
+    pairing_file: &PairingFile,
+    format: Format,
+    passphrase: Option<&str>,
+) -> Result<Vec<u8>, Error> {
+    match passphrase {
+        Some(p) => encryption::encrypt(pairing_file, p),
+        None => serialize(pairing_file, format),
+    }
+}
+
+/// Rewrites any pairing file this crate can read as `format`, without needing the device
+///
+/// The file is parsed into a [`PairingFile`] first, so only the keys it knows are kept.
+/// `udid` is added when the file doesn't already say which device it's for.
+pub fn convert(
+    bytes: &[u8],
+    passphrase: Option<&str>,
+    format: Format,
+    udid: Option<&str>,
+) -> Result<Vec<u8>, Error> {
+    let pairing_file = decode(bytes, passphrase)?;
+    match udid {
+        Some(udid) => serialize(&with_udid(&pairing_file, udid), format),
+        None => serialize(&pairing_file, format),
+    }
+}
+
+/// Reads and parses a plain or encrypted pairing file from disk
+pub fn read_from_file(
+    path: impl AsRef<Path>,
+    passphrase: Option<&str>,
+) -> Result<PairingFile, Error> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    decode(&bytes, passphrase)
+}
+
+/// Writes a pairing file only the current user can read, since it holds private keys
+///
+/// On Unix the file is created with mode 0600, so the keys are never readable by others,
+/// and an existing file is tightened to 0600 before anything is written to it.
+pub fn write_to_file(path: impl AsRef<Path>, bytes: &[u8]) -> Result<(), Error> {
+    let path = path.as_ref();
+    let io = |source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(io)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(io)?;
+    }
+    file.write_all(bytes).map_err(io)
+}
+
+/// Fetches the pairing record usbmuxd already has for the device
+pub async fn load(addr: &UsbmuxdAddr, dev: &UsbmuxdDevice) -> Result<PairingFile, Error> {
+    let mut uc = connect_usbmuxd(addr).await?;
+
+    let mut pairing_file = uc.get_pair_record(&dev.udid).await?;
+    pairing_file.udid = Some(dev.udid.clone());
+    Ok(pairing_file)
+}
+
+/// Pairs with the device again, producing a fresh pairing file
+///
+/// The device will show a trust prompt if it hasn't trusted this host yet.
+pub async fn generate(addr: &UsbmuxdAddr, dev: &UsbmuxdDevice) -> Result<PairingFile, Error> {
+    // The device only accepts pairing requests over USB
+    require_usb(dev)?;
+    let mut uc = connect_usbmuxd(addr).await?;
+
+    let p = dev.to_provider(addr.clone(), LABEL);
+    let mut lc = LockdownClient::connect(&p).await?;
+
+    let buid = uc.get_buid().await?;
+
+    // Modify it slightly so iOS doesn't invalidate the one connected right now.
+    let mut buid: Vec<char> = buid.chars().collect();
+    let first = buid.first_mut().ok_or(Error::EmptyBuid)?;
+    *first = if *first == 'F' { 'A' } else { 'F' };
+    let buid: String = buid.into_iter().collect();
+
+    let id = uuid::Uuid::new_v4().to_string().to_uppercase();
+    let mut pairing_file = lc.pair(id, buid).await?;
+
+    pairing_file.udid = Some(dev.udid.clone());
+    Ok(pairing_file)
+}
+
+/// Starts a lockdown session over the network to prove the pairing file works
+pub async fn validate(ip: IpAddr, pairing_file: &PairingFile) -> Result<(), Error> {
+    let stream = tokio::net::TcpStream::connect(SocketAddr::new(ip, LOCKDOWN_PORT))
+        .await
+        .map_err(IdeviceError::Socket)?;
+
+    let mut lc = LockdownClient::new(Idevice::new(Box::new(stream), LABEL));
+    lc.start_session(pairing_file).await?;
+    Ok(())
+}
@@ -0,0 +1,95 @@
+// Jackson Coxson
+
+use std::path::{Path, PathBuf};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, LABEL};
+
+/// Overrides where settings and other app data are kept
+pub const CONFIG_DIR_VAR: &str = "IDEVICE_PAIR_CONFIG_DIR";
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// How much the app may change on a device without being asked
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceAccess {
+    /// Only read from devices, for ones managed by someone else
+    Inspect,
+    /// Change devices only once each change is confirmed
+    #[default]
+    Confirm,
+    /// Turn on wireless debugging and mount the developer disk image as soon as a USB device is selected
+    Automatic,
+}
+
+/// Preferences kept between runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// usbmuxd socket path or host:port, empty to use the environment or platform default
+    pub usbmuxd_address: String,
+    pub device_access: DeviceAccess,
+    /// Store every pairing file loaded, generated or opened unencrypted in the vault without asking
+    pub keep_in_vault: bool,
+}
+
+impl Settings {
+    /// Reads the saved settings, falling back to the defaults if there are none
+    pub fn load() -> Self {
+        let Some(path) = config_dir().map(|x| x.join(SETTINGS_FILE)) else {
+            return Self::default();
+        };
+        match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable settings at {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let dir = config_dir().ok_or(Error::NoConfigDir)?;
+        write_file(
+            &dir.join(SETTINGS_FILE),
+            &serde_json::to_vec_pretty(self).unwrap(),
+        )
+    }
+
+    /// The usbmuxd address to pass to [`crate::device::resolve_usbmuxd_addr`]
+    pub fn usbmuxd_address(&self) -> Option<&str> {
+        Some(self.usbmuxd_address.as_str()).filter(|x| !x.is_empty())
+    }
+}
+
+/// The directory app data is kept in, following each platform's convention
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(CONFIG_DIR_VAR) {
+        return Some(PathBuf::from(dir));
+    }
+    let env_dir = |var| std::env::var_os(var).map(PathBuf::from);
+
+    let base = if cfg!(windows) {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|x| x.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_CONFIG_HOME").or_else(|| env_dir("HOME").map(|x| x.join(".config")))
+    };
+    base.map(|x| x.join(LABEL))
+}
+
+/// Writes a file, creating its parent directories first
+pub(crate) fn write_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let io = |source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(io)?;
+    }
+    std::fs::write(path, contents).map_err(io)
+}
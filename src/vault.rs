@@ -0,0 +1,273 @@
+// Jackson Coxson
+
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use idevice::pairing_file::PairingFile;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{Error, pairing, settings};
+
+const INDEX_FILE: &str = "index.json";
+
+/// Where a pairing file in the vault came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// Read from the pairing record usbmuxd already had
+    Loaded,
+    /// Made by pairing with the device again
+    Generated,
+    /// Opened from a file on disk
+    Imported,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Source::Loaded => "loaded",
+            Source::Generated => "generated",
+            Source::Imported => "imported",
+        })
+    }
+}
+
+/// An app the pairing file was installed into
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Install {
+    pub app: String,
+    #[serde(default)]
+    pub bundle_id: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+/// The outcome of the last time the pairing file was validated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Validation {
+    pub at: DateTime<Utc>,
+    pub ip: Option<IpAddr>,
+    /// Why validation failed, None if it succeeded
+    pub error: Option<String>,
+}
+
+/// What the vault knows about a stored pairing file, which is kept next to the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultEntry {
+    pub host_id: String,
+    pub udid: Option<String>,
+    pub device_name: Option<String>,
+    pub created: DateTime<Utc>,
+    pub source: Source,
+    #[serde(default)]
+    pub installs: Vec<Install>,
+    pub last_validation: Option<Validation>,
+}
+
+impl VaultEntry {
+    /// Whether `query` is this entry's UDID or HostID, ignoring case
+    pub fn matches(&self, query: &str) -> bool {
+        self.host_id.eq_ignore_ascii_case(query)
+            || self
+                .udid
+                .as_deref()
+                .is_some_and(|x| x.eq_ignore_ascii_case(query))
+    }
+}
+
+/// Pairing files kept on disk, indexed by HostID
+#[derive(Debug)]
+pub struct Vault {
+    dir: PathBuf,
+    entries: Vec<VaultEntry>,
+}
+
+impl Vault {
+    /// Opens the vault in the app's config directory
+    pub fn open() -> Result<Self, Error> {
+        let dir = settings::config_dir().ok_or(Error::NoConfigDir)?;
+        Self::open_at(dir.join("vault"))
+    }
+
+    /// Opens the vault kept in `dir`, which doesn't have to exist yet
+    pub fn open_at(dir: impl Into<PathBuf>) -> Result<Self, Error> {
+        let dir = dir.into();
+        let index = dir.join(INDEX_FILE);
+        let entries = match std::fs::read(&index) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| Error::Io {
+                path: index,
+                source: e.into(),
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(Error::Io {
+                    path: index,
+                    source: e,
+                });
+            }
+        };
+        Ok(Self { dir, entries })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Every entry, newest first
+    pub fn entries(&self) -> &[VaultEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, host_id: &str) -> Option<&VaultEntry> {
+        self.entries.iter().find(|x| x.host_id == host_id)
+    }
+
+    /// Entries whose UDID or HostID is `query`, newest first
+    pub fn find<'a>(&'a self, query: &'a str) -> impl Iterator<Item = &'a VaultEntry> {
+        self.entries.iter().filter(move |x| x.matches(query))
+    }
+
+    /// Stores a pairing file, keeping the history if its HostID is already in the vault
+    pub fn add(
+        &mut self,
+        pairing_file: &PairingFile,
+        source: Source,
+        device_name: Option<String>,
+    ) -> Result<&VaultEntry, Error> {
+        let bytes = pairing_file.clone().serialize()?;
+        self.write_private(&self.file_path(&pairing_file.host_id), &bytes)?;
+
+        let entry = match self
+            .entries
+            .iter()
+            .position(|x| x.host_id == pairing_file.host_id)
+        {
+            Some(i) => {
+                let mut entry = self.entries.remove(i);
+                entry.udid = pairing_file.udid.clone().or(entry.udid);
+                entry.device_name = device_name.or(entry.device_name);
+                entry
+            }
+            None => VaultEntry {
+                host_id: pairing_file.host_id.clone(),
+                udid: pairing_file.udid.clone(),
+                device_name,
+                created: Utc::now(),
+                source,
+                installs: Vec::new(),
+                last_validation: None,
+            },
+        };
+        self.entries.insert(0, entry);
+        self.save()?;
+        Ok(&self.entries[0])
+    }
+
+    /// Reads a stored pairing file back
+    pub fn pairing_file(&self, host_id: &str) -> Result<PairingFile, Error> {
+        pairing::read_from_file(self.file_path(host_id), None)
+    }
+
+    /// Notes that the pairing file was installed into `app`, if it's in the vault
+    pub fn record_install(
+        &mut self,
+        host_id: &str,
+        app: &str,
+        bundle_id: Option<&str>,
+    ) -> Result<(), Error> {
+        let Some(entry) = self.entries.iter_mut().find(|x| x.host_id == host_id) else {
+            return Ok(());
+        };
+        entry
+            .installs
+            .retain(|x| x.app != app || x.bundle_id.as_deref() != bundle_id);
+        entry.installs.push(Install {
+            app: app.to_string(),
+            bundle_id: bundle_id.map(|x| x.to_string()),
+            at: Utc::now(),
+        });
+        self.save()
+    }
+
+    /// Keeps the result of validating the pairing file, if it's in the vault
+    pub fn record_validation(
+        &mut self,
+        host_id: &str,
+        ip: Option<IpAddr>,
+        result: &Result<(), Error>,
+    ) -> Result<(), Error> {
+        let Some(entry) = self.entries.iter_mut().find(|x| x.host_id == host_id) else {
+            return Ok(());
+        };
+        entry.last_validation = Some(Validation {
+            at: Utc::now(),
+            ip,
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        self.save()
+    }
+
+    pub fn remove(&mut self, host_id: &str) -> Result<(), Error> {
+        let path = self.file_path(host_id);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Error::Io { path, source: e }),
+        }
+        self.entries.retain(|x| x.host_id != host_id);
+        self.save()
+    }
+
+    /// Where the pairing file for `host_id` is kept, named by a hash so no two HostIDs share a file
+    pub fn file_path(&self, host_id: &str) -> PathBuf {
+        let name: String = Sha256::digest(host_id.as_bytes())
+            .iter()
+            .map(|x| format!("{x:02x}"))
+            .collect();
+        self.dir.join(format!("{name}.plist"))
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let index = serde_json::to_vec_pretty(&self.entries).unwrap();
+        self.write_private(&self.dir.join(INDEX_FILE), &index)
+    }
+
+    /// Writes a file in the vault only the current user can read, since pairing files hold private keys
+    fn write_private(&self, path: &Path, contents: &[u8]) -> Result<(), Error> {
+        create_private_dir(&self.dir)?;
+        pairing::write_to_file(path, contents)
+    }
+}
+
+/// Creates the vault directory so only the current user can list or enter it
+fn create_private_dir(dir: &Path) -> Result<(), Error> {
+    let io = |source| Error::Io {
+        path: dir.to_path_buf(),
+        source,
+    };
+    if let Some(parent) = dir.parent() {
+        std::fs::create_dir_all(parent).map_err(io)?;
+    }
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    match builder.create(dir) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+                    .map_err(io)?;
+            }
+            Ok(())
+        }
+        Err(e) => Err(io(e)),
+    }
+}
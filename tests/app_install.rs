@@ -0,0 +1,411 @@
+// Jackson Coxson
+// End to end tests of installing pairing files into apps, against the mock device's house_arrest
+
+#![cfg(unix)]
+
+mod common;
+
+use common::{MockDevice, MockUsbmuxd, PAIRING_FILE, usb_device};
+use futures_util::StreamExt;
+use idevice_pair::{
+    Error,
+    apps::{self, SupportedApp},
+    container, pairing,
+};
+
+const UDID: &str = "00008030-000A1B2C3D4E5F60";
+const BUNDLE_ID: &str = "com.stik.stikdebug";
+const PATH: &str = "/Documents/pairingFile.plist";
+
+fn stikdebug() -> SupportedApp {
+    SupportedApp {
+        name: "StikDebug".to_string(),
+        bundle_ids: vec![BUNDLE_ID.to_string()],
+        path: "pairingFile.plist".to_string(),
+        format: pairing::Format::Xml,
+        udid: false,
+    }
+}
+
+#[tokio::test]
+async fn install_into_an_app_without_a_pairing_file() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID, "iPhone").with_app(BUNDLE_ID, "StikDebug"),
+    ])
+    .await;
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+
+    let installed = apps::installed_apps(&mock.addr, &usb_device(1, UDID), &[stikdebug()])
+        .await
+        .unwrap();
+    assert_eq!(installed["StikDebug"][0].bundle_id, BUNDLE_ID);
+
+    let report = apps::install_pairing_file(
+        &mock.addr,
+        &usb_device(1, UDID),
+        BUNDLE_ID,
+        &stikdebug(),
+        &pairing_file,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.path, PATH);
+    assert_eq!(report.backup, None);
+    assert_eq!(
+        mock.file(UDID, BUNDLE_ID, PATH).unwrap(),
+        pairing::serialize(&pairing_file, pairing::Format::Xml).unwrap()
+    );
+    assert_eq!(mock.files(UDID, BUNDLE_ID), [PATH]);
+}
+
+#[tokio::test]
+async fn install_backs_up_a_different_pairing_file() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID, "iPhone")
+            .with_app(BUNDLE_ID, "StikDebug")
+            .with_file(BUNDLE_ID, PATH, b"an older pairing file"),
+    ])
+    .await;
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+
+    let report = apps::install_pairing_file(
+        &mock.addr,
+        &usb_device(1, UDID),
+        BUNDLE_ID,
+        &stikdebug(),
+        &pairing_file,
+    )
+    .await
+    .unwrap();
+    let backup = report.backup.unwrap();
+    assert!(backup.starts_with("/Documents/pairingFile.plist."));
+    assert!(backup.ends_with(".bak"));
+    assert_eq!(
+        mock.file(UDID, BUNDLE_ID, &backup).unwrap(),
+        b"an older pairing file"
+    );
+    assert_eq!(
+        mock.file(UDID, BUNDLE_ID, PATH).unwrap(),
+        pairing::serialize(&pairing_file, pairing::Format::Xml).unwrap()
+    );
+
+    // Installing the same file again has nothing to back up
+    let report = apps::install_pairing_file(
+        &mock.addr,
+        &usb_device(1, UDID),
+        BUNDLE_ID,
+        &stikdebug(),
+        &pairing_file,
+    )
+    .await
+    .unwrap();
+    assert_eq!(report.backup, None);
+    assert_eq!(mock.files(UDID, BUNDLE_ID).len(), 2);
+}
+
+#[tokio::test]
+async fn install_creates_missing_folders() {
+    let bundle_id = "com.kdt.livecontainer";
+    let path = "/Documents/SideStore/Documents/ALTPairingFile.mobiledevicepairing";
+    let livecontainer = SupportedApp {
+        name: "LiveContainer".to_string(),
+        bundle_ids: vec![bundle_id.to_string()],
+        path: "SideStore/Documents/ALTPairingFile.mobiledevicepairing".to_string(),
+        format: pairing::Format::Xml,
+        udid: false,
+    };
+    let mut device = MockDevice::new(1, UDID, "iPhone")
+        .with_app(bundle_id, "LiveContainer")
+        .with_app("com.example.empty", "Empty")
+        .with_app("com.example.odd", "Odd")
+        .with_file("com.example.odd", "/Documents/SideStore", b"not a folder");
+    device
+        .apps
+        .get_mut("com.example.empty")
+        .unwrap()
+        .dirs
+        .remove("/Documents");
+    let mock = MockUsbmuxd::start(vec![device]).await;
+    let (addr, dev) = (&mock.addr, &usb_device(1, UDID));
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+
+    let report = apps::install_pairing_file(addr, dev, bundle_id, &livecontainer, &pairing_file)
+        .await
+        .unwrap();
+    assert_eq!(report.path, path);
+    assert_eq!(
+        mock.file(UDID, bundle_id, path).unwrap(),
+        pairing::serialize(&pairing_file, pairing::Format::Xml).unwrap()
+    );
+
+    // Containers that aren't laid out as expected are left alone
+    let res = apps::install_pairing_file(
+        addr,
+        dev,
+        "com.example.empty",
+        &livecontainer,
+        &pairing_file,
+    )
+    .await;
+    assert!(
+        matches!(&res, Err(Error::NoDocuments(x)) if x == "com.example.empty"),
+        "{res:?}"
+    );
+    let res =
+        apps::install_pairing_file(addr, dev, "com.example.odd", &livecontainer, &pairing_file)
+            .await;
+    assert!(
+        matches!(&res, Err(Error::NotAFolder(x)) if x == "/Documents/SideStore"),
+        "{res:?}"
+    );
+    assert_eq!(
+        mock.files(UDID, "com.example.odd"),
+        ["/Documents/SideStore"]
+    );
+}
+
+#[tokio::test]
+async fn install_reports_a_file_that_reads_back_differently() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID, "iPhone")
+            .with_app(BUNDLE_ID, "StikDebug")
+            .corrupt_writes(BUNDLE_ID),
+    ])
+    .await;
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+
+    let res = apps::install_pairing_file(
+        &mock.addr,
+        &usb_device(1, UDID),
+        BUNDLE_ID,
+        &stikdebug(),
+        &pairing_file,
+    )
+    .await;
+    match res {
+        Err(Error::InstallMismatch {
+            path,
+            expected,
+            actual,
+        }) => {
+            assert_eq!(path, PATH);
+            assert_eq!(actual, expected - 1);
+        }
+        other => panic!("expected a mismatch, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn install_into_an_app_that_is_not_installed() {
+    let mock = MockUsbmuxd::start(vec![MockDevice::new(1, UDID, "iPhone")]).await;
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+
+    let res = apps::install_pairing_file(
+        &mock.addr,
+        &usb_device(1, UDID),
+        BUNDLE_ID,
+        &stikdebug(),
+        &pairing_file,
+    )
+    .await;
+    assert!(matches!(res, Err(Error::Idevice(_))), "{res:?}");
+}
+
+#[tokio::test]
+async fn install_all_reports_each_app() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID, "iPhone")
+            .with_app(BUNDLE_ID, "StikDebug")
+            .with_app("com.stik.stikdebug.ABCDE12345", "StikDebug (1)")
+            .with_app("thewonderofyou.Feather", "Feather")
+            .with_app("com.example.other", "Other")
+            .corrupt_writes("thewonderofyou.Feather"),
+    ])
+    .await;
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    let feather = SupportedApp {
+        name: "Feather".to_string(),
+        bundle_ids: vec!["thewonderofyou.Feather".to_string()],
+        path: "pairingFile.plist".to_string(),
+        format: pairing::Format::Binary,
+        udid: false,
+    };
+    let supported = [stikdebug(), feather];
+
+    let installed = apps::installed_apps(&mock.addr, &usb_device(1, UDID), &supported)
+        .await
+        .unwrap();
+    let targets = apps::install_targets(&supported, &installed);
+    let bundle_ids: Vec<&str> = targets.iter().map(|x| x.bundle_id.as_str()).collect();
+    assert_eq!(
+        bundle_ids,
+        [
+            BUNDLE_ID,
+            "com.stik.stikdebug.ABCDE12345",
+            "thewonderofyou.Feather"
+        ]
+    );
+
+    let mut results: Vec<_> =
+        apps::install_all(&mock.addr, &usb_device(1, UDID), targets, &pairing_file)
+            .collect()
+            .await;
+    results.sort_by(|a, b| a.0.bundle_id.cmp(&b.0.bundle_id));
+    assert!(results[0].1.is_ok());
+    assert!(results[1].1.is_ok());
+    assert!(matches!(results[2].1, Err(Error::InstallMismatch { .. })));
+    assert_eq!(
+        mock.file(UDID, "com.stik.stikdebug.ABCDE12345", PATH)
+            .unwrap(),
+        pairing::serialize(&pairing_file, pairing::Format::Xml).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn remove_and_restore_keep_every_file() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID, "iPhone")
+            .with_app(BUNDLE_ID, "StikDebug")
+            .with_file(BUNDLE_ID, PATH, b"an older pairing file"),
+    ])
+    .await;
+    let (addr, dev, app) = (&mock.addr, &usb_device(1, UDID), &stikdebug());
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    let new = pairing::serialize(&pairing_file, pairing::Format::Xml).unwrap();
+
+    let older = apps::install_pairing_file(addr, dev, BUNDLE_ID, app, &pairing_file)
+        .await
+        .unwrap()
+        .backup
+        .unwrap();
+    let removed = apps::remove_pairing_file(addr, dev, BUNDLE_ID, app)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(mock.file(UDID, BUNDLE_ID, PATH), None);
+    assert_eq!(mock.file(UDID, BUNDLE_ID, &removed).unwrap(), new);
+    assert_eq!(
+        apps::remove_pairing_file(addr, dev, BUNDLE_ID, app)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Newest first, even when both were made in the same second
+    let backups = apps::list_backups(addr, dev, BUNDLE_ID, app).await.unwrap();
+    let paths: Vec<&str> = backups.iter().map(|x| x.path.as_str()).collect();
+    assert_eq!(paths, [removed.as_str(), older.as_str()]);
+    assert_eq!(backups[1].size, b"an older pairing file".len());
+
+    let report = apps::restore_pairing_file(addr, dev, BUNDLE_ID, app, Some(&older))
+        .await
+        .unwrap();
+    assert_eq!(report.backup, None);
+    assert_eq!(
+        mock.file(UDID, BUNDLE_ID, PATH).unwrap(),
+        b"an older pairing file"
+    );
+
+    // Restoring the newest puts the removed file back and keeps the older one aside
+    let report = apps::restore_pairing_file(addr, dev, BUNDLE_ID, app, None)
+        .await
+        .unwrap();
+    assert_eq!(mock.file(UDID, BUNDLE_ID, PATH).unwrap(), new);
+    let kept = report.backup.unwrap();
+    assert_eq!(
+        mock.file(UDID, BUNDLE_ID, &kept).unwrap(),
+        b"an older pairing file"
+    );
+    assert_eq!(mock.files(UDID, BUNDLE_ID).len(), 2);
+}
+
+#[tokio::test]
+async fn restore_needs_a_backup() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID, "iPhone")
+            .with_app(BUNDLE_ID, "StikDebug")
+            .with_file(BUNDLE_ID, "/Documents/notes.txt", b"notes"),
+    ])
+    .await;
+    let (addr, dev, app) = (&mock.addr, &usb_device(1, UDID), &stikdebug());
+
+    assert!(matches!(
+        apps::restore_pairing_file(addr, dev, BUNDLE_ID, app, None).await,
+        Err(Error::NoBackup(_))
+    ));
+    assert!(matches!(
+        apps::restore_pairing_file(addr, dev, BUNDLE_ID, app, Some("/Documents/notes.txt")).await,
+        Err(Error::NotABackup(_))
+    ));
+    assert_eq!(
+        mock.file(UDID, BUNDLE_ID, "/Documents/notes.txt").unwrap(),
+        b"notes"
+    );
+}
+
+#[tokio::test]
+async fn restoring_a_missing_backup_keeps_the_file() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID, "iPhone")
+            .with_app(BUNDLE_ID, "StikDebug")
+            .with_file(BUNDLE_ID, PATH, PAIRING_FILE),
+    ])
+    .await;
+    let (addr, dev, app) = (&mock.addr, &usb_device(1, UDID), &stikdebug());
+
+    // Named like a backup, but there is no such file
+    let missing = format!("{PATH}.20260101T120000Z.bak");
+    assert!(matches!(
+        apps::restore_pairing_file(addr, dev, BUNDLE_ID, app, Some(&missing)).await,
+        Err(Error::MissingBackup(x)) if x == missing
+    ));
+    assert_eq!(mock.file(UDID, BUNDLE_ID, PATH).unwrap(), PAIRING_FILE);
+    assert_eq!(mock.files(UDID, BUNDLE_ID).len(), 1);
+}
+
+#[tokio::test]
+async fn browse_the_documents_container() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID, "iPhone")
+            .with_app(BUNDLE_ID, "StikDebug")
+            .with_file(BUNDLE_ID, PATH, PAIRING_FILE)
+            .with_file(BUNDLE_ID, "/Documents/logs/today.txt", b"log"),
+    ])
+    .await;
+    let (addr, dev) = (&mock.addr, &usb_device(1, UDID));
+
+    let entries = container::list_dir(addr, dev, BUNDLE_ID, container::DOCUMENTS)
+        .await
+        .unwrap();
+    let names: Vec<(&str, bool)> = entries
+        .iter()
+        .map(|x| (x.name.as_str(), x.is_dir))
+        .collect();
+    assert_eq!(names, [("logs", true), ("pairingFile.plist", false)]);
+    assert_eq!(entries[1].path, PATH);
+    assert_eq!(entries[1].size, PAIRING_FILE.len());
+
+    let logs = container::list_dir(addr, dev, BUNDLE_ID, "/Documents/logs")
+        .await
+        .unwrap();
+    assert_eq!(logs[0].path, "/Documents/logs/today.txt");
+    assert_eq!(container::parent(&logs[0].path), "/Documents/logs");
+    assert_eq!(container::parent(container::DOCUMENTS), "/");
+
+    let bytes = container::read_file(addr, dev, BUNDLE_ID, PATH)
+        .await
+        .unwrap();
+    assert_eq!(bytes, PAIRING_FILE);
+    // Values are escaped in the XML
+    let preview = container::preview(&bytes).unwrap();
+    assert!(preview.contains("&lt;redacted&gt;"));
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    let key = String::from_utf8_lossy(&pairing_file.host_private_key);
+    assert!(!preview.contains(key.lines().nth(1).unwrap()));
+    assert_eq!(container::preview(b"log"), None);
+
+    assert!(matches!(
+        container::read_file(addr, dev, BUNDLE_ID, "/Documents/missing").await,
+        Err(Error::Idevice(_))
+    ));
+}
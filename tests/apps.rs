@@ -0,0 +1,208 @@
+// Jackson Coxson
+// Tests of the supported apps registry
+
+use std::path::PathBuf;
+
+use idevice_pair::{
+    Error,
+    apps::{AppRegistry, SupportedApp},
+    pairing::{self, Format},
+};
+
+const PAIRING_FILE: &[u8] = include_bytes!("fixtures/pairing_file.plist");
+
+/// A user apps file that is removed when the test ends
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("idevice_pair-apps-{}.json", uuid::Uuid::new_v4())))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn app(name: &str, path: &str) -> SupportedApp {
+    SupportedApp {
+        name: name.to_string(),
+        bundle_ids: vec![format!("com.example.{name}")],
+        path: path.to_string(),
+        format: Format::Binary,
+        udid: false,
+    }
+}
+
+#[test]
+fn bundled_apps_are_loaded() {
+    let registry = AppRegistry::load_from(None);
+    let stik = registry.get("StikDebug").unwrap();
+    assert_eq!(stik.path, "pairingFile.plist");
+    assert_eq!(stik.format, Format::Xml);
+    assert!(registry.is_bundled("SideStore"));
+    assert!(!registry.is_user("SideStore"));
+}
+
+#[test]
+fn user_apps_extend_and_override_bundled_ones() {
+    let file = TempFile::new();
+    let mut registry = AppRegistry::load_from(Some(file.0.clone()));
+    let bundled = registry.apps().len();
+
+    registry.set_user(app("Internal", "pairing.plist")).unwrap();
+    registry
+        .set_user(app("StikDebug", "Custom/pairingFile.plist"))
+        .unwrap();
+
+    // Saved for the next run
+    let mut registry = AppRegistry::load_from(Some(file.0.clone()));
+    assert_eq!(registry.apps().len(), bundled + 1);
+    assert_eq!(registry.get("Internal").unwrap().format, Format::Binary);
+    assert_eq!(
+        registry.get("StikDebug").unwrap().path,
+        "Custom/pairingFile.plist"
+    );
+
+    // Removing the override brings the bundled app back
+    registry.remove_user("StikDebug").unwrap();
+    assert_eq!(registry.get("StikDebug").unwrap().path, "pairingFile.plist");
+    assert!(!registry.is_user("StikDebug"));
+}
+
+#[test]
+fn unreadable_user_apps_are_ignored() {
+    let file = TempFile::new();
+    std::fs::write(&file.0, "not json").unwrap();
+
+    let registry = AppRegistry::load_from(Some(file.0.clone()));
+    assert_eq!(
+        registry.apps().len(),
+        AppRegistry::load_from(None).apps().len()
+    );
+}
+
+#[test]
+fn bundle_ids_match_re_signed_copies() {
+    use idevice_pair::apps::bundle_id_matches;
+
+    let pattern = "com.SideStore.SideStore";
+    assert!(bundle_id_matches(pattern, "com.sidestore.sidestore"));
+    assert!(bundle_id_matches(
+        pattern,
+        "com.SideStore.SideStore.ABCDE12345"
+    ));
+    assert!(bundle_id_matches(
+        pattern,
+        "ABCDE12345.com.SideStore.SideStore"
+    ));
+    assert!(!bundle_id_matches(pattern, "com.SideStore.SideStore.a.b"));
+    assert!(!bundle_id_matches(pattern, "com.SideStore.SideStoreWidget"));
+
+    assert!(bundle_id_matches(
+        "com.kdt.livecontainer*",
+        "com.kdt.livecontainer2.ABCDE12345"
+    ));
+    assert!(bundle_id_matches("*.stik.*", "com.stik.sj"));
+    assert!(!bundle_id_matches("*.stik.*", "com.stikdebug"));
+}
+
+#[test]
+fn every_candidate_is_listed() {
+    use idevice_pair::apps::{InstalledApp, match_apps};
+
+    let installed = |bundle_id: &str, display_name: Option<&str>| InstalledApp {
+        bundle_id: bundle_id.to_string(),
+        display_name: display_name.map(|x| x.to_string()),
+    };
+    let installed = [
+        installed("com.SideStore.SideStore.ABCDE12345", None),
+        installed("com.example.copy", Some("SideStore (1)")),
+        installed("com.example.other", Some("SideStore Helper")),
+        installed("com.example.internal", None),
+    ];
+    let registry = AppRegistry::load_from(None);
+    let mut apps: Vec<SupportedApp> = registry.apps().into_iter().cloned().collect();
+    apps.push(app("internal", "pairing.plist"));
+    apps.last_mut().unwrap().bundle_ids = vec!["com.example.internal".to_string()];
+
+    let matched = match_apps(&apps, &installed);
+    let ids =
+        |name: &str| -> Vec<&str> { matched[name].iter().map(|x| x.bundle_id.as_str()).collect() };
+    assert_eq!(
+        ids("SideStore"),
+        ["com.SideStore.SideStore.ABCDE12345", "com.example.copy"]
+    );
+    assert_eq!(ids("internal"), ["com.example.internal"]);
+    assert!(!matched.contains_key("StikDebug"));
+}
+
+#[test]
+fn apps_get_the_pairing_file_the_way_they_read_it() {
+    let registry = AppRegistry::load_from(None);
+    let sidestore = registry.get("SideStore").unwrap();
+    assert!(sidestore.udid);
+    assert_eq!(sidestore.file_name(), "ALTPairingFile.mobiledevicepairing");
+    assert_eq!(
+        registry.get("LiveContainer").unwrap().file_name(),
+        "ALTPairingFile.mobiledevicepairing"
+    );
+
+    let mut pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    let udid = pairing_file.udid.take().unwrap();
+    let bytes = sidestore.serialize(&pairing_file, &udid).unwrap();
+    assert_eq!(
+        pairing::from_bytes(&bytes).unwrap().udid,
+        Some(udid.clone())
+    );
+
+    // Only added for apps that need it, and never in place of the file's own
+    let bytes = app("Internal", "pairing.plist")
+        .serialize(&pairing_file, &udid)
+        .unwrap();
+    assert!(bytes.starts_with(b"bplist00"));
+    assert_eq!(pairing::from_bytes(&bytes).unwrap().udid, None);
+    let with_udid = pairing::with_udid(&pairing::with_udid(&pairing_file, &udid), "other");
+    assert_eq!(with_udid.udid, Some(udid));
+
+    assert_eq!("Binary".parse::<Format>(), Ok(Format::Binary));
+    assert!("json".parse::<Format>().is_err());
+}
+
+#[test]
+fn convert_between_apps_without_a_device() {
+    let registry = AppRegistry::load_from(None);
+    let sidestore = registry.get("SideStore").unwrap();
+    let stikdebug = registry.get("StikDebug").unwrap();
+    let expected = pairing::from_bytes(PAIRING_FILE).unwrap();
+
+    // A binary file without a UDID, as some tools write them
+    let mut pairing_file = expected.clone();
+    let udid = pairing_file.udid.take().unwrap();
+    let binary = pairing::serialize(&pairing_file, Format::Binary).unwrap();
+
+    assert!(matches!(
+        sidestore.convert(&binary, None, None),
+        Err(Error::UdidRequired(name)) if name == "SideStore"
+    ));
+    let converted = sidestore.convert(&binary, None, Some(&udid)).unwrap();
+    let read_back = pairing::from_bytes(&converted).unwrap();
+    assert_eq!(read_back.udid, Some(udid.clone()));
+    assert_eq!(read_back.host_private_key, expected.host_private_key);
+
+    // And back again, StikDebug takes it as it is
+    let converted = stikdebug.convert(&converted, None, None).unwrap();
+    assert_eq!(
+        converted,
+        pairing::serialize(&expected, Format::Xml).unwrap()
+    );
+
+    let converted = pairing::convert(&binary, None, Format::Xml, Some(&udid)).unwrap();
+    assert_eq!(pairing::from_bytes(&converted).unwrap().udid, Some(udid));
+    assert!(matches!(
+        pairing::convert(b"not a pairing file", None, Format::Xml, None),
+        Err(Error::InvalidPairingFile(_))
+    ));
+}
@@ -0,0 +1,121 @@
+// Jackson Coxson
+// Tests of what the CLI prints, running the binary against a vault in a temporary config directory
+
+use std::{path::PathBuf, process::Command};
+
+use idevice_pair::{
+    device::USBMUXD_ADDRESS_VAR,
+    pairing,
+    settings::{CONFIG_DIR_VAR, Settings},
+    vault::{Source, Vault},
+};
+use plist::Dictionary;
+
+const PAIRING_FILE: &[u8] = include_bytes!("fixtures/pairing_file.plist");
+const UDID: &str = "00008030-000A1B2C3D4E5F60";
+
+/// A config directory that is removed when the test ends
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("idevice_pair-cli-{}", uuid::Uuid::new_v4())))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs the CLI with `dir` as its config directory and parses the plist it prints
+fn export(dir: &TempDir, args: &[&str]) -> Dictionary {
+    let output = Command::new(env!("CARGO_BIN_EXE_idevice_pair_cli"))
+        .env(CONFIG_DIR_VAR, &dir.0)
+        .args(["vault", "export", UDID])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    plist::from_bytes(&output.stdout).unwrap()
+}
+
+#[test]
+fn stdout_is_redacted_unless_keys_are_asked_for() {
+    let dir = TempDir::new();
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    Vault::open_at(dir.0.join("vault"))
+        .unwrap()
+        .add(&pairing_file, Source::Loaded, None)
+        .unwrap();
+
+    let redacted = export(&dir, &[]);
+    let shown = export(&dir, &["--show-keys"]);
+
+    assert_eq!(redacted.len(), shown.len());
+    for (key, value) in &shown {
+        if pairing::PRIVATE_KEYS.contains(&key.as_str()) {
+            assert_eq!(redacted[key].as_string(), Some(pairing::REDACTED), "{key}");
+        } else {
+            assert_eq!(&redacted[key], value, "{key}");
+        }
+    }
+    assert_eq!(
+        shown["HostPrivateKey"].as_data(),
+        Some(pairing_file.host_private_key.as_slice())
+    );
+    assert_eq!(
+        shown["RootPrivateKey"].as_data(),
+        Some(pairing_file.root_private_key.as_slice())
+    );
+}
+
+/// Runs `list` with `dir` as the config directory, returning the exit code and what was printed to stderr
+fn list(dir: &TempDir, args: &[&str], env_address: Option<&str>) -> (i32, String) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_idevice_pair_cli"));
+    command
+        .env(CONFIG_DIR_VAR, &dir.0)
+        .env_remove(USBMUXD_ADDRESS_VAR);
+    if let Some(address) = env_address {
+        command.env(USBMUXD_ADDRESS_VAR, address);
+    }
+    let output = command.arg("list").args(args).output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn usbmuxd_address_from_settings() {
+    let dir = TempDir::new();
+    // Host names aren't accepted, so the error shows which address was picked without connecting
+    let settings = Settings {
+        usbmuxd_address: "from-settings:27015".to_string(),
+        ..Default::default()
+    };
+    std::fs::create_dir_all(&dir.0).unwrap();
+    std::fs::write(
+        dir.0.join("settings.json"),
+        serde_json::to_vec(&settings).unwrap(),
+    )
+    .unwrap();
+
+    let (code, stderr) = list(&dir, &[], Some("from-env:27015"));
+    assert_eq!(code, 3);
+    assert!(stderr.contains("from-settings:27015"), "{stderr}");
+
+    let (code, stderr) = list(&dir, &["--usbmuxd", "from-flag:27015"], None);
+    assert_eq!(code, 3);
+    assert!(stderr.contains("from-flag:27015"), "{stderr}");
+
+    std::fs::remove_file(dir.0.join("settings.json")).unwrap();
+    let (code, stderr) = list(&dir, &[], Some("from-env:27015"));
+    assert_eq!(code, 3);
+    assert!(stderr.contains("from-env:27015"), "{stderr}");
+}
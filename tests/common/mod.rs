@@ -0,0 +1,1036 @@
+// Jackson Coxson
+//! Stand-ins for usbmuxd and lockdownd, so device flows can be tested without an iPhone.
+//!
+//! [`MockUsbmuxd`] listens on a Unix socket and speaks the usbmuxd plist protocol. A
+//! `Connect` to port 62078 hands the socket to a fake lockdownd for that device, which
+//! answers GetValue, SetValue, StartSession (upgrading to TLS), Pair and StartService.
+//! The services it can start are installation_proxy, which lists the device's apps,
+//! house_arrest, which serves AFC over an in-memory copy of an app's container, and
+//! mobile_image_mounter, which lists and unmounts images but can't mount them.
+//!
+//! The certificates in `tests/fixtures` were made with openssl and are valid until 2126.
+
+#![allow(dead_code)] // each test binary uses a different part of the harness
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    net::Ipv4Addr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use idevice::usbmuxd::{Connection, UsbmuxdAddr, UsbmuxdDevice};
+use plist::{Dictionary, Value};
+use rustls::{
+    ServerConfig,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+    task::JoinHandle,
+};
+use tokio_rustls::TlsAcceptor;
+
+/// A pairing record usbmuxd holds for the fixture device
+pub const PAIRING_FILE: &[u8] = include_bytes!("../fixtures/pairing_file.plist");
+const DEVICE_KEY: &[u8] = include_bytes!("../fixtures/device_key.pem");
+const DEVICE_PUBLIC_KEY: &[u8] = include_bytes!("../fixtures/device_public_key.pem");
+
+pub const BUID: &str = "F1E2D3C4-B5A6-4978-8695-A4B3C2D1E0F9";
+const LOCKDOWN_PORT: u16 = 62078;
+const WIRELESS_DOMAIN: &str = "com.apple.mobile.wireless_lockdown";
+const AMFI_DOMAIN: &str = "com.apple.security.mac.amfi";
+/// The services lockdownd can start, and the ports it says they're on
+const SERVICES: &[(&str, u16)] = &[
+    ("com.apple.mobile.installation_proxy", 49152),
+    ("com.apple.mobile.house_arrest", 49153),
+    ("com.apple.mobile.mobile_image_mounter", 49154),
+];
+
+/// A user app and the files in its container, by absolute path
+#[derive(Debug, Clone)]
+pub struct MockApp {
+    pub display_name: String,
+    pub files: BTreeMap<String, Vec<u8>>,
+    /// Every directory, always including "/" and "/Documents"
+    pub dirs: BTreeSet<String>,
+    /// Drop the last byte of every write, like storage that doesn't keep what it's given
+    pub corrupt_writes: bool,
+}
+
+/// A device as usbmuxd and its lockdownd see it
+#[derive(Debug, Clone)]
+pub struct MockDevice {
+    pub udid: String,
+    pub device_id: u32,
+    /// Listed as a network device at this address instead of USB
+    pub network: Option<Ipv4Addr>,
+    /// Lockdown values by domain, the global domain is ""
+    pub values: HashMap<String, Dictionary>,
+    /// What usbmuxd returns for ReadPairRecord
+    pub pair_record: Option<Vec<u8>>,
+    /// HostIDs lockdownd will start a session for
+    pub trusted_hosts: HashSet<String>,
+    /// Installed user apps by bundle ID
+    pub apps: BTreeMap<String, MockApp>,
+    /// What the image mounter lists for CopyDevices
+    pub mounted: Vec<Dictionary>,
+}
+
+impl MockDevice {
+    /// A USB device paired with the fixture pairing file
+    pub fn new(device_id: u32, udid: &str, name: &str) -> Self {
+        let mut global = Dictionary::new();
+        global.insert("DeviceName".into(), name.into());
+        global.insert("ProductType".into(), "iPhone14,2".into());
+        global.insert("ProductVersion".into(), "17.5.1".into());
+        global.insert("BuildVersion".into(), "21F90".into());
+        global.insert("UniqueDeviceID".into(), udid.into());
+        global.insert("UniqueChipID".into(), 0x1A2B3C4D5E6F_u64.into());
+        global.insert("WiFiAddress".into(), "02:00:00:00:00:01".into());
+        global.insert(
+            "DevicePublicKey".into(),
+            Value::Data(DEVICE_PUBLIC_KEY.to_vec()),
+        );
+
+        let mut values = HashMap::new();
+        values.insert(String::new(), global);
+        values.insert(
+            WIRELESS_DOMAIN.to_string(),
+            Dictionary::from_iter([("EnableWifiDebugging".to_string(), Value::from(false))]),
+        );
+        values.insert(
+            AMFI_DOMAIN.to_string(),
+            Dictionary::from_iter([("DeveloperModeStatus".to_string(), Value::from(true))]),
+        );
+
+        Self {
+            udid: udid.to_string(),
+            device_id,
+            network: None,
+            values,
+            pair_record: Some(PAIRING_FILE.to_vec()),
+            trusted_hosts: HashSet::from([fixture_host_id()]),
+            apps: BTreeMap::new(),
+            mounted: Vec::new(),
+        }
+    }
+
+    pub fn network(mut self, ip: Ipv4Addr) -> Self {
+        self.network = Some(ip);
+        self
+    }
+
+    /// Removes the pairing record, like a device that was never trusted
+    pub fn unpaired(mut self) -> Self {
+        self.pair_record = None;
+        self.trusted_hosts.clear();
+        self
+    }
+
+    pub fn with_value(mut self, domain: &str, key: &str, value: impl Into<Value>) -> Self {
+        self.values
+            .entry(domain.to_string())
+            .or_default()
+            .insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Installs an app with an empty Documents folder
+    pub fn with_app(mut self, bundle_id: &str, display_name: &str) -> Self {
+        self.apps.insert(
+            bundle_id.to_string(),
+            MockApp {
+                display_name: display_name.to_string(),
+                files: BTreeMap::new(),
+                dirs: BTreeSet::from(["/".to_string(), "/Documents".to_string()]),
+                corrupt_writes: false,
+            },
+        );
+        self
+    }
+
+    /// Puts a file in an app's container, making its directories
+    pub fn with_file(mut self, bundle_id: &str, path: &str, contents: &[u8]) -> Self {
+        let app = self.apps.get_mut(bundle_id).expect("app isn't installed");
+        let path = normalize(path);
+        let mut parent = parent(&path);
+        while app.dirs.insert(parent.clone()) {
+            parent = self::parent(&parent);
+        }
+        app.files.insert(path, contents.to_vec());
+        self
+    }
+
+    /// Lists an image as mounted, like one left from before the device was plugged in
+    pub fn with_mounted_image(
+        mut self,
+        image_type: &str,
+        mount_path: &str,
+        signature: &[u8],
+    ) -> Self {
+        self.mounted.push(Dictionary::from_iter([
+            ("DiskImageType".to_string(), Value::from(image_type)),
+            ("MountPath".to_string(), Value::from(mount_path)),
+            (
+                "ImageSignature".to_string(),
+                Value::Data(signature.to_vec()),
+            ),
+            ("IsMounted".to_string(), Value::from(true)),
+        ]));
+        self
+    }
+
+    pub fn corrupt_writes(mut self, bundle_id: &str) -> Self {
+        self.apps
+            .get_mut(bundle_id)
+            .expect("app isn't installed")
+            .corrupt_writes = true;
+        self
+    }
+
+    fn list_entry(&self) -> Value {
+        let mut properties = Dictionary::new();
+        properties.insert("DeviceID".into(), self.device_id.into());
+        properties.insert("SerialNumber".into(), self.udid.clone().into());
+        match self.network {
+            Some(ip) => {
+                // A sockaddr_in, family first and the address at offset 4
+                let mut addr = vec![0x02, 0, 0, 0];
+                addr.extend(ip.octets());
+                addr.extend([0; 8]);
+                properties.insert("ConnectionType".into(), "Network".into());
+                properties.insert("NetworkAddress".into(), Value::Data(addr));
+            }
+            None => {
+                properties.insert("ConnectionType".into(), "USB".into());
+            }
+        }
+
+        let mut entry = Dictionary::new();
+        entry.insert("DeviceID".into(), self.device_id.into());
+        entry.insert("MessageType".into(), "Attached".into());
+        entry.insert("Properties".into(), Value::Dictionary(properties));
+        Value::Dictionary(entry)
+    }
+}
+
+/// The HostID in the fixture pairing file
+pub fn fixture_host_id() -> String {
+    let record: Dictionary = plist::from_bytes(PAIRING_FILE).unwrap();
+    record["HostID"].as_string().unwrap().to_string()
+}
+
+#[derive(Debug, Default)]
+struct State {
+    devices: Vec<MockDevice>,
+    buid: String,
+    /// Every request lockdownd received, in order
+    lockdown_requests: Vec<Dictionary>,
+}
+
+/// The device as usbmuxd lists it, without going through lockdown like `get_devices` does
+pub fn usb_device(device_id: u32, udid: &str) -> UsbmuxdDevice {
+    UsbmuxdDevice {
+        connection_type: Connection::Usb,
+        udid: udid.to_string(),
+        device_id,
+    }
+}
+
+/// A usbmuxd on a temporary Unix socket, serving the given devices
+pub struct MockUsbmuxd {
+    pub addr: UsbmuxdAddr,
+    path: PathBuf,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl MockUsbmuxd {
+    pub async fn start(devices: Vec<MockDevice>) -> Self {
+        let path = std::env::temp_dir().join(format!("idevice_pair-{}.sock", uuid::Uuid::new_v4()));
+        let listener = UnixListener::bind(&path).unwrap();
+        let state = Arc::new(Mutex::new(State {
+            devices,
+            buid: BUID.to_string(),
+            ..Default::default()
+        }));
+
+        let task_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_usbmuxd(stream, task_state.clone()));
+            }
+        });
+
+        Self {
+            addr: UsbmuxdAddr::UnixSocket(path.to_string_lossy().to_string()),
+            path,
+            state,
+            task,
+        }
+    }
+
+    /// Changes the system BUID usbmuxd answers ReadBUID with
+    pub fn set_buid(&self, buid: &str) {
+        self.state.lock().unwrap().buid = buid.to_string();
+    }
+
+    /// A lockdown value as it is now, after any SetValue requests
+    pub fn value(&self, udid: &str, domain: &str, key: &str) -> Option<Value> {
+        let state = self.state.lock().unwrap();
+        let dev = state.devices.iter().find(|x| x.udid == udid)?;
+        dev.values.get(domain)?.get(key).cloned()
+    }
+
+    /// The `Request` of every message lockdownd received, in order
+    pub fn lockdown_requests(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .unwrap()
+            .lockdown_requests
+            .iter()
+            .filter_map(|x| x.get("Request").and_then(|x| x.as_string()))
+            .map(|x| x.to_string())
+            .collect()
+    }
+
+    /// A file in an app's container as it is now
+    pub fn file(&self, udid: &str, bundle_id: &str, path: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        let dev = state.devices.iter().find(|x| x.udid == udid)?;
+        dev.apps
+            .get(bundle_id)?
+            .files
+            .get(&normalize(path))
+            .cloned()
+    }
+
+    /// The paths of every file in an app's container
+    pub fn files(&self, udid: &str, bundle_id: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .devices
+            .iter()
+            .find(|x| x.udid == udid)
+            .and_then(|x| x.apps.get(bundle_id))
+            .map(|x| x.files.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Where the device's images are mounted now, after any UnmountImage requests
+    pub fn mount_paths(&self, udid: &str) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state
+            .devices
+            .iter()
+            .find(|x| x.udid == udid)
+            .map(|x| {
+                x.mounted
+                    .iter()
+                    .filter_map(|x| x.get("MountPath").and_then(|x| x.as_string()))
+                    .map(|x| x.to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The last Pair request lockdownd received
+    pub fn last_pair_request(&self) -> Option<Dictionary> {
+        self.state
+            .lock()
+            .unwrap()
+            .lockdown_requests
+            .iter()
+            .rev()
+            .find(|x| x.get("Request").and_then(|x| x.as_string()) == Some("Pair"))
+            .cloned()
+    }
+}
+
+impl Drop for MockUsbmuxd {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn serve_usbmuxd(mut stream: UnixStream, state: Arc<Mutex<State>>) {
+    loop {
+        let mut header = [0; 16];
+        if stream.read_exact(&mut header).await.is_err() {
+            return;
+        }
+        let size = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let tag = u32::from_le_bytes(header[12..].try_into().unwrap());
+        let mut body = vec![0; size - 16];
+        if stream.read_exact(&mut body).await.is_err() {
+            return;
+        }
+        let req: Dictionary = plist::from_bytes(&body).unwrap();
+
+        let message_type = req
+            .get("MessageType")
+            .and_then(|x| x.as_string())
+            .unwrap_or_default();
+        let res = match message_type {
+            "ListDevices" => {
+                let state = state.lock().unwrap();
+                let list = state.devices.iter().map(|x| x.list_entry()).collect();
+                Dictionary::from_iter([("DeviceList".to_string(), Value::Array(list))])
+            }
+            "ReadBUID" => {
+                let buid = state.lock().unwrap().buid.clone();
+                Dictionary::from_iter([("BUID".to_string(), Value::from(buid))])
+            }
+            "ReadPairRecord" => {
+                let udid = req["PairRecordID"].as_string().unwrap();
+                let state = state.lock().unwrap();
+                match state
+                    .devices
+                    .iter()
+                    .find(|x| x.udid == udid)
+                    .and_then(|x| x.pair_record.clone())
+                {
+                    Some(record) => {
+                        Dictionary::from_iter([("PairRecordData".to_string(), Value::Data(record))])
+                    }
+                    None => result(2),
+                }
+            }
+            "SavePairRecord" => {
+                let udid = req["PairRecordID"].as_string().unwrap();
+                let record = req["PairRecordData"].as_data().unwrap().to_vec();
+                let mut state = state.lock().unwrap();
+                match state.devices.iter_mut().find(|x| x.udid == udid) {
+                    Some(dev) => {
+                        dev.pair_record = Some(record);
+                        result(0)
+                    }
+                    None => result(2),
+                }
+            }
+            "Connect" => {
+                let device_id = req["DeviceID"].as_unsigned_integer().unwrap() as u32;
+                // The port is sent in network byte order
+                let port = u16::from_be(req["PortNumber"].as_unsigned_integer().unwrap() as u16);
+                let udid = state
+                    .lock()
+                    .unwrap()
+                    .devices
+                    .iter()
+                    .find(|x| x.device_id == device_id)
+                    .map(|x| x.udid.clone());
+                let service = SERVICES.iter().find(|x| x.1 == port).map(|x| x.0);
+                match udid {
+                    Some(udid) if port == LOCKDOWN_PORT => {
+                        write_usbmuxd(&mut stream, tag, result(0)).await;
+                        serve_lockdown(Box::new(stream), udid, state).await;
+                        return;
+                    }
+                    Some(udid) if let Some(service) = service => {
+                        write_usbmuxd(&mut stream, tag, result(0)).await;
+                        serve_service(Box::new(stream), service, udid, state).await;
+                        return;
+                    }
+                    Some(_) => result(3),
+                    None => result(2),
+                }
+            }
+            // Accepted, but no events are ever sent
+            "Listen" => result(0),
+            _ => result(1),
+        };
+        write_usbmuxd(&mut stream, tag, res).await;
+    }
+}
+
+fn result(number: u64) -> Dictionary {
+    Dictionary::from_iter([
+        ("MessageType".to_string(), Value::from("Result")),
+        ("Number".to_string(), Value::from(number)),
+    ])
+}
+
+async fn write_usbmuxd(stream: &mut UnixStream, tag: u32, res: Dictionary) {
+    let mut body = Vec::new();
+    plist::to_writer_xml(&mut body, &res).unwrap();
+
+    let mut packet = Vec::with_capacity(16 + body.len());
+    packet.extend((16 + body.len() as u32).to_le_bytes());
+    packet.extend(1_u32.to_le_bytes()); // XML plist version
+    packet.extend(8_u32.to_le_bytes()); // plist message
+    packet.extend(tag.to_le_bytes());
+    packet.extend(body);
+    let _ = stream.write_all(&packet).await;
+}
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+/// Reads a plist with a big endian length in front, as lockdownd and its services use
+async fn read_plist(stream: &mut Box<dyn Io>) -> Option<Dictionary> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len).await.ok()?;
+    let mut body = vec![0; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut body).await.ok()?;
+    Some(plist::from_bytes(&body).unwrap())
+}
+
+async fn write_plist(stream: &mut Box<dyn Io>, res: &Dictionary) -> Option<()> {
+    let mut body = Vec::new();
+    plist::to_writer_xml(&mut body, res).unwrap();
+    stream
+        .write_all(&(body.len() as u32).to_be_bytes())
+        .await
+        .ok()?;
+    stream.write_all(&body).await.ok()
+}
+
+async fn serve_lockdown(mut stream: Box<dyn Io>, udid: String, state: Arc<Mutex<State>>) {
+    let mut in_session = false;
+    loop {
+        let Some(req) = read_plist(&mut stream).await else {
+            return;
+        };
+        let (res, start_tls) = handle_lockdown(req, &udid, &state, in_session);
+        if write_plist(&mut stream, &res).await.is_none() {
+            return;
+        }
+
+        if start_tls {
+            stream = match tls_acceptor().accept(stream).await {
+                Ok(s) => Box::new(s),
+                Err(_) => return,
+            };
+            in_session = true;
+        }
+    }
+}
+
+/// Answers one lockdown request, returning the response and whether to upgrade to TLS after it
+fn handle_lockdown(
+    req: Dictionary,
+    udid: &str,
+    state: &Mutex<State>,
+    in_session: bool,
+) -> (Dictionary, bool) {
+    let request = req
+        .get("Request")
+        .and_then(|x| x.as_string())
+        .unwrap_or_default()
+        .to_string();
+    let key = req.get("Key").and_then(|x| x.as_string());
+    let domain = req
+        .get("Domain")
+        .and_then(|x| x.as_string())
+        .unwrap_or_default();
+
+    let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+    state.lockdown_requests.push(req.clone());
+    let dev = state
+        .devices
+        .iter_mut()
+        .find(|x| x.udid == udid)
+        .expect("device was detached");
+
+    let mut res = Dictionary::new();
+    res.insert("Request".into(), request.clone().into());
+    let mut start_tls = false;
+    match request.as_str() {
+        "QueryType" => {
+            res.insert("Type".into(), "com.apple.mobile.lockdown".into());
+        }
+        "GetValue" => {
+            let values = dev.values.get(domain);
+            let value = match key {
+                Some(key) => values.and_then(|x| x.get(key)).cloned(),
+                None => values.map(|x| Value::Dictionary(x.clone())),
+            };
+            match value {
+                Some(v) => res.insert("Value".into(), v),
+                None => res.insert("Error".into(), "MissingValue".into()),
+            };
+        }
+        "SetValue" if !in_session => {
+            res.insert("Error".into(), "SessionInactive".into());
+        }
+        "SetValue" => {
+            dev.values
+                .entry(domain.to_string())
+                .or_default()
+                .insert(key.unwrap().to_string(), req["Value"].clone());
+        }
+        "StartSession" => {
+            let host_id = req["HostID"].as_string().unwrap();
+            if dev.trusted_hosts.contains(host_id) {
+                res.insert("EnableSessionSSL".into(), true.into());
+                res.insert("SessionID".into(), uuid::Uuid::new_v4().to_string().into());
+                start_tls = true;
+            } else {
+                res.insert("Error".into(), "InvalidHostID".into());
+            }
+        }
+        "Pair" => {
+            let record = req["PairRecord"].as_dictionary().unwrap();
+            let host_id = record["HostID"].as_string().unwrap();
+            dev.trusted_hosts.insert(host_id.to_string());
+            res.insert("EscrowBag".into(), Value::Data(vec![0xE5; 32]));
+        }
+        "StartService" => {
+            let service = req["Service"].as_string().unwrap();
+            match SERVICES.iter().find(|x| x.0 == service) {
+                Some((_, port)) => {
+                    res.insert("Service".into(), service.into());
+                    res.insert("Port".into(), (*port as u64).into());
+                }
+                None => {
+                    res.insert("Error".into(), "InvalidService".into());
+                }
+            }
+        }
+        _ => {
+            res.insert("Error".into(), "UnknownRequest".into());
+        }
+    }
+    (res, start_tls)
+}
+
+async fn serve_service(
+    mut stream: Box<dyn Io>,
+    service: &str,
+    udid: String,
+    state: Arc<Mutex<State>>,
+) {
+    while let Some(req) = read_plist(&mut stream).await {
+        let command = req
+            .get("Command")
+            .and_then(|x| x.as_string())
+            .unwrap_or_default();
+        let mut res = Dictionary::new();
+        match (service, command) {
+            ("com.apple.mobile.installation_proxy", "Lookup") => {
+                let state = state.lock().unwrap();
+                let dev = state.devices.iter().find(|x| x.udid == udid).unwrap();
+                let apps = dev.apps.iter().map(|(bundle_id, app)| {
+                    let info = Dictionary::from_iter([
+                        (
+                            "CFBundleIdentifier".to_string(),
+                            Value::from(bundle_id.as_str()),
+                        ),
+                        (
+                            "CFBundleDisplayName".to_string(),
+                            Value::from(app.display_name.as_str()),
+                        ),
+                        ("ApplicationType".to_string(), Value::from("User")),
+                    ]);
+                    (bundle_id.clone(), Value::Dictionary(info))
+                });
+                res.insert(
+                    "LookupResult".into(),
+                    Value::Dictionary(Dictionary::from_iter(apps)),
+                );
+                res.insert("Status".into(), "Complete".into());
+            }
+            ("com.apple.mobile.house_arrest", "VendDocuments" | "VendContainer") => {
+                let bundle_id = req["Identifier"].as_string().unwrap().to_string();
+                let installed = state
+                    .lock()
+                    .unwrap()
+                    .devices
+                    .iter()
+                    .any(|x| x.udid == udid && x.apps.contains_key(&bundle_id));
+                if installed {
+                    res.insert("Status".into(), "Complete".into());
+                    if write_plist(&mut stream, &res).await.is_some() {
+                        serve_afc(stream, udid, bundle_id, state).await;
+                    }
+                    return;
+                }
+                res.insert("Error".into(), "ApplicationLookupFailed".into());
+            }
+            ("com.apple.mobile.mobile_image_mounter", "CopyDevices") => {
+                let state = state.lock().unwrap();
+                let dev = state.devices.iter().find(|x| x.udid == udid).unwrap();
+                let entries = dev.mounted.iter().cloned().map(Value::Dictionary).collect();
+                res.insert("EntryList".into(), Value::Array(entries));
+                res.insert("Status".into(), "Complete".into());
+            }
+            ("com.apple.mobile.mobile_image_mounter", "UnmountImage") => {
+                let path = req["MountPath"].as_string().unwrap().to_string();
+                let mut state = state.lock().unwrap();
+                let dev = state.devices.iter_mut().find(|x| x.udid == udid).unwrap();
+                let before = dev.mounted.len();
+                dev.mounted
+                    .retain(|x| x.get("MountPath").and_then(|x| x.as_string()) != Some(&path));
+                if dev.mounted.len() < before {
+                    res.insert("Status".into(), "Complete".into());
+                } else {
+                    res.insert("Error".into(), "InternalError".into());
+                }
+            }
+            _ => {
+                res.insert("Error".into(), "UnknownCommand".into());
+            }
+        }
+        if write_plist(&mut stream, &res).await.is_none() {
+            return;
+        }
+    }
+}
+
+const AFC_MAGIC: u64 = 0x4141504c36414643;
+const AFC_HEADER_LEN: usize = 40;
+
+// The AFC operations the mock knows, see idevice::afc::opcode::AfcOpcode
+const AFC_STATUS: u64 = 0x01;
+const AFC_DATA: u64 = 0x02;
+const AFC_READ_DIR: u64 = 0x03;
+const AFC_REMOVE_PATH: u64 = 0x08;
+const AFC_MAKE_DIR: u64 = 0x09;
+const AFC_GET_FILE_INFO: u64 = 0x0a;
+const AFC_FILE_OPEN: u64 = 0x0d;
+const AFC_FILE_OPEN_RES: u64 = 0x0e;
+const AFC_READ: u64 = 0x0f;
+const AFC_WRITE: u64 = 0x10;
+const AFC_FILE_SEEK: u64 = 0x11;
+const AFC_FILE_TELL: u64 = 0x12;
+const AFC_FILE_TELL_RES: u64 = 0x13;
+const AFC_FILE_CLOSE: u64 = 0x14;
+const AFC_RENAME_PATH: u64 = 0x18;
+const AFC_REMOVE_PATH_AND_CONTENTS: u64 = 0x22;
+
+// Status codes, see idevice::afc::errors::AfcError
+const AFC_INVALID_ARG: u64 = 7;
+const AFC_OBJECT_NOT_FOUND: u64 = 8;
+const AFC_OBJECT_IS_DIR: u64 = 9;
+const AFC_OBJECT_EXISTS: u64 = 16;
+const AFC_DIR_NOT_EMPTY: u64 = 33;
+
+/// The timestamps of every file, in nanoseconds
+const AFC_TIME: u64 = 1_700_000_000_000_000_000;
+
+/// A file opened over AFC
+struct OpenFile {
+    path: String,
+    pos: usize,
+}
+
+/// A response is an operation with its header payload and payload, or a status code
+type AfcResponse = Result<(u64, Vec<u8>, Vec<u8>), u64>;
+
+async fn serve_afc(
+    mut stream: Box<dyn Io>,
+    udid: String,
+    bundle_id: String,
+    state: Arc<Mutex<State>>,
+) {
+    let mut open_files = HashMap::new();
+    let mut next_fd = 1;
+    loop {
+        let mut header = [0; AFC_HEADER_LEN];
+        if stream.read_exact(&mut header).await.is_err() {
+            return;
+        }
+        let field = |i: usize| u64::from_le_bytes(header[i * 8..i * 8 + 8].try_into().unwrap());
+        assert_eq!(field(0), AFC_MAGIC);
+        let (entire_len, header_len) = (field(1) as usize, field(2) as usize);
+        let (packet_num, operation) = (field(3), field(4));
+        let mut header_payload = vec![0; header_len - AFC_HEADER_LEN];
+        let mut payload = vec![0; entire_len - header_len];
+        if stream.read_exact(&mut header_payload).await.is_err()
+            || stream.read_exact(&mut payload).await.is_err()
+        {
+            return;
+        }
+
+        let res = {
+            let mut state = state.lock().unwrap();
+            let app = state
+                .devices
+                .iter_mut()
+                .find(|x| x.udid == udid)
+                .and_then(|x| x.apps.get_mut(&bundle_id))
+                .expect("app was uninstalled");
+            handle_afc(
+                app,
+                &mut open_files,
+                &mut next_fd,
+                operation,
+                &header_payload,
+                payload,
+            )
+        };
+        let (operation, header_payload, payload) = match res {
+            Ok(res) => res,
+            Err(code) => (AFC_STATUS, code.to_le_bytes().to_vec(), Vec::new()),
+        };
+
+        let header_len = (AFC_HEADER_LEN + header_payload.len()) as u64;
+        let mut packet = Vec::new();
+        for x in [
+            AFC_MAGIC,
+            header_len + payload.len() as u64,
+            header_len,
+            packet_num,
+            operation,
+        ] {
+            packet.extend(x.to_le_bytes());
+        }
+        packet.extend(header_payload);
+        packet.extend(payload);
+        if stream.write_all(&packet).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Answers one AFC request against the app's container
+fn handle_afc(
+    app: &mut MockApp,
+    open_files: &mut HashMap<u64, OpenFile>,
+    next_fd: &mut u64,
+    operation: u64,
+    header_payload: &[u8],
+    payload: Vec<u8>,
+) -> AfcResponse {
+    let success = Ok((AFC_STATUS, 0_u64.to_le_bytes().to_vec(), Vec::new()));
+    let u64_at = |i: usize| -> Result<u64, u64> {
+        header_payload
+            .get(i * 8..i * 8 + 8)
+            .map(|x| u64::from_le_bytes(x.try_into().unwrap()))
+            .ok_or(AFC_INVALID_ARG)
+    };
+    let path = || normalize(&String::from_utf8_lossy(header_payload));
+
+    match operation {
+        AFC_READ_DIR => {
+            let path = path();
+            if !app.dirs.contains(&path) {
+                return Err(AFC_OBJECT_NOT_FOUND);
+            }
+            let mut names = vec![".".to_string(), "..".to_string()];
+            names.extend(children(app, &path));
+            let mut payload = Vec::new();
+            for name in names {
+                payload.extend(name.as_bytes());
+                payload.push(0);
+            }
+            Ok((AFC_DATA, Vec::new(), payload))
+        }
+        AFC_REMOVE_PATH => {
+            let path = path();
+            if app.files.remove(&path).is_some() {
+                return success;
+            }
+            if !app.dirs.contains(&path) {
+                return Err(AFC_OBJECT_NOT_FOUND);
+            }
+            if children(app, &path).next().is_some() {
+                return Err(AFC_DIR_NOT_EMPTY);
+            }
+            app.dirs.remove(&path);
+            success
+        }
+        AFC_REMOVE_PATH_AND_CONTENTS => {
+            let path = path();
+            if !app.files.contains_key(&path) && !app.dirs.contains(&path) {
+                return Err(AFC_OBJECT_NOT_FOUND);
+            }
+            let inside = |x: &String| *x == path || x.starts_with(&format!("{path}/"));
+            app.files.retain(|k, _| !inside(k));
+            app.dirs.retain(|k| !inside(k));
+            success
+        }
+        AFC_MAKE_DIR => {
+            let path = path();
+            if app.files.contains_key(&path) {
+                return Err(AFC_OBJECT_EXISTS);
+            }
+            if !app.dirs.contains(&parent(&path)) {
+                return Err(AFC_OBJECT_NOT_FOUND);
+            }
+            app.dirs.insert(path);
+            success
+        }
+        AFC_GET_FILE_INFO => {
+            let path = path();
+            let (size, ifmt, nlink) = match app.files.get(&path) {
+                Some(contents) => (contents.len(), "S_IFREG", 1),
+                None if app.dirs.contains(&path) => (0, "S_IFDIR", 2),
+                None => return Err(AFC_OBJECT_NOT_FOUND),
+            };
+            let mut payload = Vec::new();
+            for (k, v) in [
+                ("st_size", size.to_string()),
+                ("st_blocks", size.div_ceil(512).to_string()),
+                ("st_nlink", nlink.to_string()),
+                ("st_ifmt", ifmt.to_string()),
+                ("st_mtime", AFC_TIME.to_string()),
+                ("st_birthtime", AFC_TIME.to_string()),
+            ] {
+                for x in [k.as_bytes(), v.as_bytes()] {
+                    payload.extend(x);
+                    payload.push(0);
+                }
+            }
+            Ok((AFC_DATA, Vec::new(), payload))
+        }
+        AFC_FILE_OPEN => {
+            let mode = u64_at(0)?;
+            let path = normalize(&String::from_utf8_lossy(&header_payload[8..]));
+            if app.dirs.contains(&path) {
+                return Err(AFC_OBJECT_IS_DIR);
+            }
+            match mode {
+                // Read only and read/write don't create the file
+                1 | 2 if !app.files.contains_key(&path) => return Err(AFC_OBJECT_NOT_FOUND),
+                1 | 2 => {}
+                // The write modes create it, and all but appending truncate it
+                3..=6 => {
+                    if !app.dirs.contains(&parent(&path)) {
+                        return Err(AFC_OBJECT_NOT_FOUND);
+                    }
+                    let contents = app.files.entry(path.clone()).or_default();
+                    if mode == 3 || mode == 4 {
+                        contents.clear();
+                    }
+                }
+                _ => return Err(AFC_INVALID_ARG),
+            }
+            let fd = *next_fd;
+            *next_fd += 1;
+            open_files.insert(fd, OpenFile { path, pos: 0 });
+            Ok((AFC_FILE_OPEN_RES, fd.to_le_bytes().to_vec(), Vec::new()))
+        }
+        AFC_READ => {
+            let file = open_files.get_mut(&u64_at(0)?).ok_or(AFC_INVALID_ARG)?;
+            let len = u64_at(1)? as usize;
+            let contents = app.files.get(&file.path).ok_or(AFC_OBJECT_NOT_FOUND)?;
+            let start = file.pos.min(contents.len());
+            let end = (start + len).min(contents.len());
+            file.pos = end;
+            Ok((AFC_DATA, Vec::new(), contents[start..end].to_vec()))
+        }
+        AFC_WRITE => {
+            let file = open_files.get_mut(&u64_at(0)?).ok_or(AFC_INVALID_ARG)?;
+            let contents = app.files.get_mut(&file.path).ok_or(AFC_OBJECT_NOT_FOUND)?;
+            let mut payload = payload;
+            if app.corrupt_writes {
+                payload.pop();
+            }
+            let end = file.pos + payload.len();
+            if contents.len() < end {
+                contents.resize(end, 0);
+            }
+            contents[file.pos..end].copy_from_slice(&payload);
+            file.pos = end;
+            success
+        }
+        AFC_FILE_SEEK => {
+            let file = open_files.get_mut(&u64_at(0)?).ok_or(AFC_INVALID_ARG)?;
+            let len = app.files.get(&file.path).map(|x| x.len()).unwrap_or(0) as i64;
+            let offset = u64_at(2)? as i64;
+            let pos = match u64_at(1)? {
+                0 => offset,
+                1 => file.pos as i64 + offset,
+                2 => len + offset,
+                _ => return Err(AFC_INVALID_ARG),
+            };
+            file.pos = usize::try_from(pos).map_err(|_| AFC_INVALID_ARG)?;
+            success
+        }
+        AFC_FILE_TELL => {
+            let file = open_files.get(&u64_at(0)?).ok_or(AFC_INVALID_ARG)?;
+            let pos = (file.pos as u64).to_le_bytes().to_vec();
+            Ok((AFC_FILE_TELL_RES, pos, Vec::new()))
+        }
+        AFC_FILE_CLOSE => {
+            open_files.remove(&u64_at(0)?).ok_or(AFC_INVALID_ARG)?;
+            success
+        }
+        AFC_RENAME_PATH => {
+            let mut paths = header_payload
+                .split(|x| *x == 0)
+                .map(|x| normalize(&String::from_utf8_lossy(x)));
+            let (Some(from), Some(to)) = (paths.next(), paths.next()) else {
+                return Err(AFC_INVALID_ARG);
+            };
+            if !app.dirs.contains(&parent(&to)) {
+                return Err(AFC_OBJECT_NOT_FOUND);
+            }
+            if let Some(contents) = app.files.remove(&from) {
+                app.files.insert(to, contents);
+                return success;
+            }
+            if !app.dirs.contains(&from) {
+                return Err(AFC_OBJECT_NOT_FOUND);
+            }
+            let moved = |x: &str| {
+                let rest = x.strip_prefix(&from)?;
+                (rest.is_empty() || rest.starts_with('/')).then(|| format!("{to}{rest}"))
+            };
+            app.files = std::mem::take(&mut app.files)
+                .into_iter()
+                .map(|(k, v)| (moved(&k).unwrap_or(k), v))
+                .collect();
+            app.dirs = std::mem::take(&mut app.dirs)
+                .into_iter()
+                .map(|k| moved(&k).unwrap_or(k))
+                .collect();
+            success
+        }
+        _ => Err(AFC_INVALID_ARG),
+    }
+}
+
+/// An absolute path without empty, `.` or `..` components
+fn normalize(path: &str) -> String {
+    let mut parts = Vec::new();
+    for part in path.trim_end_matches('\0').split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+fn parent(path: &str) -> String {
+    match path.rsplit_once('/') {
+        Some(("", _)) | None => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+    }
+}
+
+/// The names of the files and directories directly inside `dir`
+fn children<'a>(app: &'a MockApp, dir: &'a str) -> impl Iterator<Item = String> + 'a {
+    app.files
+        .keys()
+        .chain(app.dirs.iter())
+        .filter(move |x| x.as_str() != "/" && parent(x) == dir)
+        .filter_map(|x| x.rsplit('/').next())
+        .map(|x| x.to_string())
+}
+
+/// Serves the fixture device certificate, which the client doesn't verify
+fn tls_acceptor() -> TlsAcceptor {
+    let record: Dictionary = plist::from_bytes(PAIRING_FILE).unwrap();
+    let cert = CertificateDer::from_pem_slice(record["DeviceCertificate"].as_data().unwrap())
+        .unwrap()
+        .into_owned();
+    let key = PrivateKeyDer::from_pem_slice(DEVICE_KEY).unwrap();
+
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], key)
+        .unwrap();
+    TlsAcceptor::from(Arc::new(config))
+}
@@ -0,0 +1,245 @@
+// Jackson Coxson
+// Tests of the developer disk image cache, importing from folders and archives made on the fly
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use flate2::{Compression, write::GzEncoder};
+use idevice_pair::{
+    Error,
+    ddi::{self, DdiCache, ImageKind},
+};
+use zip::{CompressionMethod, write::SimpleFileOptions};
+
+const IMAGE: &[u8] = b"not really a disk image";
+const TRUSTCACHE: &[u8] = b"not really a trust cache";
+
+/// A directory that is removed when the test ends
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("idevice_pair-ddi-{}", uuid::Uuid::new_v4())))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn build_manifest(identities: usize) -> Vec<u8> {
+    let identity = plist::Value::Dictionary(plist::Dictionary::from_iter([(
+        "ApBoardID".to_string(),
+        plist::Value::String("0x0C".to_string()),
+    )]));
+    let manifest = plist::Dictionary::from_iter([(
+        "BuildIdentities".to_string(),
+        plist::Value::Array(vec![identity; identities]),
+    )]);
+    let mut bytes = Vec::new();
+    plist::to_writer_xml(&mut bytes, &manifest).unwrap();
+    bytes
+}
+
+/// The personalized image's files, where Xcode keeps them
+fn image_files() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("Xcode_iOS_DDI_Personalized/Image.dmg", IMAGE.to_vec()),
+        (
+            "Xcode_iOS_DDI_Personalized/Image.dmg.trustcache",
+            TRUSTCACHE.to_vec(),
+        ),
+        (
+            "Xcode_iOS_DDI_Personalized/BuildManifest.plist",
+            build_manifest(1),
+        ),
+        ("README.md", b"unrelated".to_vec()),
+    ]
+}
+
+fn write_folder(dir: &Path, files: &[(&str, Vec<u8>)]) {
+    for (name, bytes) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, bytes).unwrap();
+    }
+}
+
+/// A zip with the first file stored and the rest deflated
+fn zip(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (i, (name, bytes)) in files.iter().enumerate() {
+        let method = if i == 0 {
+            CompressionMethod::Stored
+        } else {
+            CompressionMethod::Deflated
+        };
+        zip.start_file(
+            *name,
+            SimpleFileOptions::default().compression_method(method),
+        )
+        .unwrap();
+        zip.write_all(bytes).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+fn tar_gz(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, bytes) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, name, bytes.as_slice())
+            .unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap()
+}
+
+fn assert_imported(cache_dir: &Path) {
+    // Everything is on disk, not just in memory
+    let cache = DdiCache::open_at(cache_dir).unwrap();
+    let image = cache.personalized().unwrap().unwrap();
+    assert_eq!(image.image, IMAGE);
+    assert_eq!(image.trustcache, TRUSTCACHE);
+    assert_eq!(image.build_manifest, build_manifest(1));
+    assert_eq!(cache.images().len(), 1);
+    assert_eq!(cache.find("personalized").unwrap().files.len(), 3);
+}
+
+#[test]
+fn import_a_folder() {
+    let dir = TempDir::new();
+    write_folder(&dir.0.join("source"), &image_files());
+
+    let mut cache = DdiCache::open_at(dir.0.join("cache")).unwrap();
+    let imported = cache.import(&dir.0.join("source")).unwrap();
+    assert_eq!(imported, [ImageKind::Personalized]);
+    assert_imported(&dir.0.join("cache"));
+}
+
+#[test]
+fn import_archives() {
+    let dir = TempDir::new();
+    std::fs::create_dir_all(&dir.0).unwrap();
+    let zip_path = dir.0.join("ddi.zip");
+    std::fs::write(&zip_path, zip(&image_files())).unwrap();
+    let tar_path = dir.0.join("ddi.tar.gz");
+    std::fs::write(&tar_path, tar_gz(&image_files())).unwrap();
+
+    let mut cache = DdiCache::open_at(dir.0.join("zip")).unwrap();
+    cache.import(&zip_path).unwrap();
+    assert_imported(&dir.0.join("zip"));
+
+    let mut cache = DdiCache::open_at(dir.0.join("tar")).unwrap();
+    cache.import(&tar_path).unwrap();
+    assert_imported(&dir.0.join("tar"));
+
+    // Cut short, so the zip's central directory is missing
+    let mut damaged = zip(&image_files());
+    damaged.truncate(damaged.len() / 2);
+    std::fs::write(&zip_path, damaged).unwrap();
+    let mut cache = DdiCache::open_at(dir.0.join("damaged")).unwrap();
+    assert!(matches!(
+        cache.import(&zip_path),
+        Err(Error::InvalidDdi { .. })
+    ));
+}
+
+#[test]
+fn changed_files_fail_the_check() {
+    let dir = TempDir::new();
+    write_folder(&dir.0.join("source"), &image_files());
+    let mut cache = DdiCache::open_at(dir.0.join("cache")).unwrap();
+    cache.import(&dir.0.join("source")).unwrap();
+    cache.check_unchanged(&ImageKind::Personalized).unwrap();
+
+    let path = cache.file_path(&ImageKind::Personalized, ddi::IMAGE);
+    std::fs::write(&path, b"something else").unwrap();
+    assert!(matches!(
+        cache.check_unchanged(&ImageKind::Personalized),
+        Err(Error::InvalidDdi { .. })
+    ));
+    assert!(cache.personalized().is_err());
+
+    cache.remove(&ImageKind::Personalized).unwrap();
+    assert!(!path.exists());
+    assert!(cache.personalized().unwrap().is_none());
+}
+
+#[test]
+fn unusable_images_are_not_imported() {
+    let dir = TempDir::new();
+    let mut files = image_files();
+    files[2].1 = build_manifest(0);
+    write_folder(&dir.0.join("no_identities"), &files);
+    write_folder(&dir.0.join("missing"), &files[..2]);
+
+    let mut cache = DdiCache::open_at(dir.0.join("cache")).unwrap();
+    for source in ["no_identities", "missing"] {
+        assert!(matches!(
+            cache.import(&dir.0.join(source)),
+            Err(Error::InvalidDdi { .. })
+        ));
+    }
+    assert!(cache.images().is_empty());
+    assert!(!dir.0.join("cache").exists());
+}
+
+#[test]
+fn legacy_images_match_the_device_version() {
+    let dir = TempDir::new();
+    let legacy = |folder: &str, signature: bool| {
+        let mut files = vec![(
+            format!("DeveloperDiskImages/{folder}/DeveloperDiskImage.dmg"),
+            format!("image for {folder}").into_bytes(),
+        )];
+        if signature {
+            files.push((
+                format!("DeveloperDiskImages/{folder}/DeveloperDiskImage.dmg.signature"),
+                format!("signature for {folder}").into_bytes(),
+            ));
+        }
+        files
+    };
+    let files: Vec<(String, Vec<u8>)> = [
+        legacy("15.7", true),
+        legacy("16.4 (20E247)", true),
+        // Without its signature it can't be mounted, so it's skipped
+        legacy("16.0", false),
+    ]
+    .concat();
+    let files: Vec<(&str, Vec<u8>)> = files.iter().map(|(n, b)| (n.as_str(), b.clone())).collect();
+    write_folder(&dir.0.join("source"), &files);
+
+    let mut cache = DdiCache::open_at(dir.0.join("cache")).unwrap();
+    let mut imported = cache.import(&dir.0.join("source")).unwrap();
+    imported.sort_by_key(|x| x.id());
+    assert_eq!(
+        imported,
+        [
+            ImageKind::Legacy {
+                version: "15.7".to_string()
+            },
+            ImageKind::Legacy {
+                version: "16.4".to_string()
+            },
+        ]
+    );
+
+    let image = cache.legacy("16.4.1").unwrap().unwrap();
+    assert_eq!(image.version, "16.4");
+    assert_eq!(image.image, b"image for 16.4 (20E247)");
+    assert_eq!(image.signature, b"signature for 16.4 (20E247)");
+    // The closest older image of the same major version is used
+    assert_eq!(cache.legacy("15.8").unwrap().unwrap().version, "15.7");
+    assert!(cache.legacy("16.2").unwrap().is_none());
+    assert!(cache.legacy("14.8").unwrap().is_none());
+
+    assert!(ddi::is_personalized("17.0"));
+    assert!(!ddi::is_personalized("16.7.10"));
+}
@@ -0,0 +1,304 @@
+// Jackson Coxson
+// End to end tests of the device operations against a mock usbmuxd and lockdownd
+
+#![cfg(unix)]
+
+mod common;
+
+use std::net::Ipv4Addr;
+
+use common::{BUID, MockDevice, MockUsbmuxd, usb_device};
+use idevice::{IdeviceService, lockdown::LockdownClient, usbmuxd::UsbmuxdAddr};
+use idevice_pair::{Error, LABEL, device, mount, pairing};
+
+const UDID_A: &str = "00008030-000A1B2C3D4E5F60";
+const UDID_B: &str = "00008030-0011223344556677";
+
+#[tokio::test]
+async fn get_devices_keeps_devices_with_the_same_name_apart() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID_A, "iPhone"),
+        MockDevice::new(2, UDID_B, "iPhone"),
+    ])
+    .await;
+
+    let devices = device::get_devices(&mock.addr).await.unwrap();
+    assert_eq!(devices.len(), 2);
+    for udid in [UDID_A, UDID_B] {
+        let dev = &devices[udid];
+        assert_eq!(dev.name, "iPhone");
+        assert_eq!(dev.model, "iPhone14,2");
+        assert_eq!(dev.udid(), udid);
+        assert!(!dev.is_network());
+    }
+    assert_ne!(devices[UDID_A].label(), devices[UDID_B].label());
+}
+
+#[tokio::test]
+async fn get_devices_prefers_usb_for_a_device_on_both() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(7, UDID_A, "Lab Phone").network(Ipv4Addr::new(192, 168, 1, 20)),
+        MockDevice::new(8, UDID_A, "Lab Phone"),
+        MockDevice::new(9, UDID_B, "Wireless Phone").network(Ipv4Addr::new(192, 168, 1, 21)),
+    ])
+    .await;
+
+    let devices = device::get_devices(&mock.addr).await.unwrap();
+    assert_eq!(devices.len(), 2);
+    assert_eq!(devices[UDID_A].usbmuxd.device_id, 8);
+    assert!(!devices[UDID_A].is_network());
+    assert_eq!(
+        devices[UDID_B].network_address(),
+        Some(Ipv4Addr::new(192, 168, 1, 21).into())
+    );
+    assert!(devices[UDID_B].label().ends_with("[Network]"));
+}
+
+#[tokio::test]
+async fn get_devices_without_usbmuxd() {
+    let addr = UsbmuxdAddr::UnixSocket("/nonexistent/usbmuxd".to_string());
+    assert!(matches!(
+        device::get_devices(&addr).await,
+        Err(Error::NoUsbmuxd(_))
+    ));
+}
+
+#[tokio::test]
+async fn load_pairing_file_from_usbmuxd() {
+    let mock = MockUsbmuxd::start(vec![MockDevice::new(1, UDID_A, "iPhone")]).await;
+
+    let pairing_file = pairing::load(&mock.addr, &usb_device(1, UDID_A))
+        .await
+        .unwrap();
+    assert_eq!(pairing_file.host_id, common::fixture_host_id());
+    assert_eq!(pairing_file.udid.as_deref(), Some(UDID_A));
+    assert_eq!(pairing_file.wifi_mac_address, "02:00:00:00:00:01");
+}
+
+#[tokio::test]
+async fn load_pairing_file_for_an_unpaired_device() {
+    let mock = MockUsbmuxd::start(vec![MockDevice::new(1, UDID_A, "iPhone").unpaired()]).await;
+
+    assert!(
+        pairing::load(&mock.addr, &usb_device(1, UDID_A))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn generate_pairing_file() {
+    let mock = MockUsbmuxd::start(vec![MockDevice::new(1, UDID_A, "iPhone").unpaired()]).await;
+    let dev = usb_device(1, UDID_A);
+
+    let pairing_file = pairing::generate(&mock.addr, &dev).await.unwrap();
+    assert_eq!(pairing_file.udid.as_deref(), Some(UDID_A));
+    assert_eq!(pairing_file.wifi_mac_address, "02:00:00:00:00:01");
+    assert_eq!(pairing_file.escrow_bag, vec![0xE5; 32]);
+    // The BUID is changed so the host's own pairing stays valid
+    assert_ne!(pairing_file.system_buid, BUID);
+    assert_eq!(pairing_file.system_buid[1..], BUID[1..]);
+
+    let pair = mock.last_pair_request().unwrap();
+    let record = pair["PairRecord"].as_dictionary().unwrap();
+    assert_eq!(
+        record["HostID"].as_string(),
+        Some(pairing_file.host_id.as_str())
+    );
+
+    // The device now trusts the new host
+    let mut lc = LockdownClient::connect(&dev.to_provider(mock.addr.clone(), LABEL))
+        .await
+        .unwrap();
+    lc.start_session(&pairing_file).await.unwrap();
+}
+
+#[tokio::test]
+async fn generate_without_a_buid() {
+    let mock = MockUsbmuxd::start(vec![MockDevice::new(1, UDID_A, "iPhone").unpaired()]).await;
+    mock.set_buid("");
+
+    let res = pairing::generate(&mock.addr, &usb_device(1, UDID_A)).await;
+    assert!(matches!(res, Err(Error::EmptyBuid)), "{res:?}");
+    assert!(mock.last_pair_request().is_none());
+}
+
+#[tokio::test]
+async fn generate_pairing_file_needs_usb() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID_A, "iPhone").network(Ipv4Addr::new(192, 168, 1, 20)),
+    ])
+    .await;
+    let dev = device::get_devices(&mock.addr).await.unwrap()[UDID_A]
+        .usbmuxd
+        .clone();
+
+    assert!(matches!(
+        pairing::generate(&mock.addr, &dev).await,
+        Err(Error::UsbRequired)
+    ));
+    assert!(mock.last_pair_request().is_none());
+}
+
+#[tokio::test]
+async fn set_wireless_reads_the_value_back() {
+    let mock = MockUsbmuxd::start(vec![MockDevice::new(1, UDID_A, "iPhone")]).await;
+    let dev = usb_device(1, UDID_A);
+
+    assert!(!device::wireless_status(&mock.addr, &dev).await.unwrap());
+    assert!(device::set_wireless(&mock.addr, &dev, true).await.unwrap());
+    assert_eq!(
+        mock.value(
+            UDID_A,
+            "com.apple.mobile.wireless_lockdown",
+            "EnableWifiDebugging"
+        ),
+        Some(true.into())
+    );
+    assert_eq!(
+        mock.lockdown_requests(),
+        [
+            "StartSession",
+            "GetValue",
+            "StartSession",
+            "SetValue",
+            "GetValue"
+        ]
+    );
+
+    assert!(!device::set_wireless(&mock.addr, &dev, false).await.unwrap());
+    assert!(!device::wireless_status(&mock.addr, &dev).await.unwrap());
+}
+
+#[tokio::test]
+async fn wireless_is_off_when_never_set() {
+    let mut dev = MockDevice::new(1, UDID_A, "iPhone");
+    dev.values.remove("com.apple.mobile.wireless_lockdown");
+    let mock = MockUsbmuxd::start(vec![dev]).await;
+
+    assert!(
+        !device::wireless_status(&mock.addr, &usb_device(1, UDID_A))
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn set_wireless_for_an_untrusted_host() {
+    let mut dev = MockDevice::new(1, UDID_A, "iPhone");
+    dev.trusted_hosts.clear();
+    let mock = MockUsbmuxd::start(vec![dev]).await;
+
+    assert!(
+        device::set_wireless(&mock.addr, &usb_device(1, UDID_A), true)
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        mock.value(
+            UDID_A,
+            "com.apple.mobile.wireless_lockdown",
+            "EnableWifiDebugging"
+        ),
+        Some(false.into())
+    );
+}
+
+#[tokio::test]
+async fn check_dev_mode() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID_A, "iPhone"),
+        MockDevice::new(2, UDID_B, "iPhone").with_value(
+            "com.apple.security.mac.amfi",
+            "DeveloperModeStatus",
+            false,
+        ),
+    ])
+    .await;
+
+    let devices = device::get_devices(&mock.addr).await.unwrap();
+    assert!(
+        device::dev_mode_status(&mock.addr, &devices[UDID_A].usbmuxd)
+            .await
+            .unwrap()
+    );
+    assert!(
+        !device::dev_mode_status(&mock.addr, &devices[UDID_B].usbmuxd)
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn device_info_reads_lockdown_values() {
+    let mock = MockUsbmuxd::start(vec![MockDevice::new(1, UDID_A, "Lab Phone")]).await;
+
+    let info = device::device_info(&mock.addr, &usb_device(1, UDID_A))
+        .await
+        .unwrap();
+    assert_eq!(
+        info,
+        [
+            ("Device Name", "Lab Phone"),
+            ("Model", "iPhone14,2"),
+            ("iOS Version", "17.5.1"),
+            ("Build Number", "21F90"),
+            ("UDID", UDID_A),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+    );
+}
+
+#[tokio::test]
+async fn list_and_unmount_images() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID_A, "iPhone").with_mounted_image(
+            "Personalized",
+            "/System/Developer",
+            &[0xAB; 20],
+        ),
+    ])
+    .await;
+    let dev = usb_device(1, UDID_A);
+
+    let images = mount::mounted_images(&mock.addr, &dev).await.unwrap();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].image_type, "Personalized");
+    assert_eq!(images[0].mount_path.as_deref(), Some("/System/Developer"));
+    assert_eq!(images[0].signature, Some("ab".repeat(20)));
+    assert_eq!(images[0].short_signature(), Some("abababababababab"));
+
+    // Nothing is mounted there, so the device refuses
+    assert!(
+        mount::unmount(&mock.addr, &dev, "/Developer")
+            .await
+            .is_err()
+    );
+    assert_eq!(mock.mount_paths(UDID_A), ["/System/Developer"]);
+
+    let unmounted = mount::unmount_all(&mock.addr, &dev).await.unwrap();
+    assert_eq!(unmounted, ["/System/Developer"]);
+    assert!(mock.mount_paths(UDID_A).is_empty());
+    assert!(
+        mount::mounted_images(&mock.addr, &dev)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn mounted_images_need_usb() {
+    let mock = MockUsbmuxd::start(vec![
+        MockDevice::new(1, UDID_A, "iPhone").network(Ipv4Addr::new(192, 168, 1, 20)),
+    ])
+    .await;
+    let dev = device::get_devices(&mock.addr).await.unwrap()[UDID_A]
+        .usbmuxd
+        .clone();
+
+    assert!(matches!(
+        mount::mounted_images(&mock.addr, &dev).await,
+        Err(Error::UsbRequired)
+    ));
+}
@@ -0,0 +1,90 @@
+// Jackson Coxson
+// Tests of passphrase encrypted pairing files
+
+use idevice_pair::{Error, encryption, pairing};
+
+const PAIRING_FILE: &[u8] = include_bytes!("fixtures/pairing_file.plist");
+
+#[test]
+fn encrypted_round_trip() {
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+
+    let bytes =
+        pairing::encode(&pairing_file, pairing::Format::Xml, Some("correct horse")).unwrap();
+    assert!(encryption::is_encrypted(&bytes));
+    // Nothing of the plist should be readable
+    assert!(
+        !bytes
+            .windows(pairing_file.host_id.len())
+            .any(|x| x == pairing_file.host_id.as_bytes())
+    );
+
+    let decoded = pairing::decode(&bytes, Some("correct horse")).unwrap();
+    assert_eq!(decoded.host_id, pairing_file.host_id);
+    assert_eq!(decoded.host_private_key, pairing_file.host_private_key);
+}
+
+#[test]
+fn plain_files_ignore_the_passphrase() {
+    let pairing_file = pairing::decode(PAIRING_FILE, Some("unused")).unwrap();
+    assert_eq!(
+        pairing_file.udid.as_deref(),
+        Some("00008030-000A1B2C3D4E5F60")
+    );
+    assert!(!encryption::is_encrypted(PAIRING_FILE));
+}
+
+#[test]
+fn encrypted_files_need_the_right_passphrase() {
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    let bytes = encryption::encrypt(&pairing_file, "correct horse").unwrap();
+
+    assert!(matches!(
+        pairing::decode(&bytes, None),
+        Err(Error::PassphraseRequired)
+    ));
+    assert!(matches!(
+        pairing::decode(&bytes, Some("battery staple")),
+        Err(Error::WrongPassphrase)
+    ));
+}
+
+#[test]
+fn tampering_is_detected() {
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    let bytes = encryption::encrypt(&pairing_file, "correct horse").unwrap();
+
+    // Flip a bit in the salt, then in the contents
+    for i in [25, bytes.len() - 1] {
+        let mut tampered = bytes.clone();
+        tampered[i] ^= 1;
+        assert!(matches!(
+            encryption::decrypt(&tampered, "correct horse"),
+            Err(Error::WrongPassphrase)
+        ));
+    }
+    assert!(matches!(
+        encryption::decrypt(&bytes[..40], "correct horse"),
+        Err(Error::InvalidPairingFile(_))
+    ));
+}
+
+#[test]
+fn costly_headers_are_refused() {
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    let bytes = encryption::encrypt(&pairing_file, "correct horse").unwrap();
+
+    // Memory, iterations and lanes follow the 8 byte magic. Running Argon2 with any of
+    // these would take minutes or all the memory, so a quick error means it didn't run
+    for (field, value) in [(0, u32::MAX), (1, u32::MAX), (2, 1 << 16)] {
+        let mut crafted = bytes.clone();
+        let start = 8 + field * 4;
+        crafted[start..start + 4].copy_from_slice(&value.to_le_bytes());
+        let started = std::time::Instant::now();
+        assert!(matches!(
+            encryption::decrypt(&crafted, "correct horse"),
+            Err(Error::InvalidPairingFile(_))
+        ));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,98 @@
+// Jackson Coxson
+// Tests of the pairing file inspector's warnings, using mutated copies of the fixture.
+// The expired and self signed host certificates were made with openssl from the fixture's keys.
+
+use idevice_pair::{inspect::inspect, pairing};
+use plist::{Dictionary, Value};
+
+const PAIRING_FILE: &[u8] = include_bytes!("fixtures/pairing_file.plist");
+
+/// The warnings for the fixture after `change` is made to it
+fn warnings_after(change: impl FnOnce(&mut Dictionary)) -> Vec<String> {
+    let mut dict: Dictionary = plist::from_bytes(PAIRING_FILE).unwrap();
+    change(&mut dict);
+    let mut bytes = Vec::new();
+    plist::to_writer_xml(&mut bytes, &dict).unwrap();
+    inspect(&pairing::from_bytes(&bytes).unwrap()).warnings
+}
+
+fn set(dict: &mut Dictionary, key: &str, bytes: &[u8]) {
+    dict.insert(key.to_string(), Value::Data(bytes.to_vec()));
+}
+
+#[test]
+fn fixture_has_no_warnings() {
+    let report = inspect(&pairing::from_bytes(PAIRING_FILE).unwrap());
+    assert_eq!(report.warnings, Vec::<String>::new());
+    assert!(report.has_escrow_bag);
+    assert_eq!(report.host_certificate.unwrap().subject, "CN=Host");
+}
+
+#[test]
+fn expired_certificate() {
+    let warnings = warnings_after(|dict| {
+        set(
+            dict,
+            "HostCertificate",
+            include_bytes!("fixtures/expired_host_certificate.pem"),
+        )
+    });
+    assert_eq!(
+        warnings,
+        ["HostCertificate expired on 2001-01-01 00:00:00 UTC"]
+    );
+}
+
+#[test]
+fn issuer_mismatch() {
+    let warnings = warnings_after(|dict| {
+        set(
+            dict,
+            "HostCertificate",
+            include_bytes!("fixtures/self_signed_host_certificate.pem"),
+        )
+    });
+    assert_eq!(
+        warnings,
+        ["HostCertificate was not issued by RootCertificate (CN=Host != CN=Root)"]
+    );
+}
+
+#[test]
+fn private_key_not_matching_certificate() {
+    let warnings = warnings_after(|dict| {
+        let host_key = dict.remove("HostPrivateKey").unwrap();
+        let root_key = dict.insert("RootPrivateKey".to_string(), host_key).unwrap();
+        dict.insert("HostPrivateKey".to_string(), root_key);
+    });
+    assert_eq!(
+        warnings,
+        [
+            "HostPrivateKey does not belong to HostCertificate",
+            "RootPrivateKey does not belong to RootCertificate",
+        ]
+    );
+
+    let warnings = warnings_after(|dict| set(dict, "HostPrivateKey", b"not a key"));
+    assert_eq!(warnings, ["HostPrivateKey is not a readable RSA key"]);
+}
+
+#[test]
+fn missing_udid() {
+    let warnings = warnings_after(|dict| {
+        dict.remove("UDID");
+    });
+    assert_eq!(
+        warnings,
+        ["UDID is missing, some apps need it to know which device the file is for"]
+    );
+}
+
+#[test]
+fn empty_escrow_bag() {
+    let warnings = warnings_after(|dict| set(dict, "EscrowBag", b""));
+    assert_eq!(
+        warnings,
+        ["EscrowBag is empty, the device must be unlocked to connect"]
+    );
+}
@@ -0,0 +1,123 @@
+// Jackson Coxson
+// Tests of reading pairing files, using mutated copies of the fixture
+
+use idevice_pair::{Error, pairing};
+use plist::{Dictionary, Value};
+
+const PAIRING_FILE: &[u8] = include_bytes!("fixtures/pairing_file.plist");
+
+/// The keys `from_bytes` requires, and whether they hold data
+const REQUIRED_KEYS: [(&str, bool); 9] = [
+    ("DeviceCertificate", true),
+    ("HostCertificate", true),
+    ("HostPrivateKey", true),
+    ("RootCertificate", true),
+    ("RootPrivateKey", true),
+    ("EscrowBag", true),
+    ("SystemBUID", false),
+    ("HostID", false),
+    ("WiFiMACAddress", false),
+];
+
+fn fixture() -> Dictionary {
+    plist::from_bytes(PAIRING_FILE).unwrap()
+}
+
+fn to_bytes(dict: &Dictionary) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    plist::to_writer_xml(&mut bytes, dict).unwrap();
+    bytes
+}
+
+/// The reason `from_bytes` gives for rejecting the bytes
+fn reason(bytes: &[u8]) -> String {
+    match pairing::from_bytes(bytes) {
+        Err(Error::InvalidPairingFile(reason)) => reason,
+        other => panic!("expected an invalid pairing file, got {other:?}"),
+    }
+}
+
+#[test]
+fn fixture_is_valid() {
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    assert_eq!(
+        pairing_file.host_id,
+        fixture()["HostID"].as_string().unwrap()
+    );
+}
+
+#[test]
+fn each_missing_key_is_named() {
+    for (key, _) in REQUIRED_KEYS {
+        let mut dict = fixture();
+        dict.remove(key);
+        assert_eq!(reason(&to_bytes(&dict)), format!("missing {key}"));
+    }
+}
+
+#[test]
+fn each_mistyped_key_is_named() {
+    for (key, is_data) in REQUIRED_KEYS {
+        let mut dict = fixture();
+        let (wrong, expected) = if is_data {
+            (Value::String("not data".to_string()), "data")
+        } else {
+            (Value::Data(b"not a string".to_vec()), "a string")
+        };
+        dict.insert(key.to_string(), wrong);
+        assert_eq!(
+            reason(&to_bytes(&dict)),
+            format!("{key} should be {expected}")
+        );
+    }
+
+    let mut dict = fixture();
+    dict.insert("UDID".to_string(), Value::Integer(1.into()));
+    assert_eq!(reason(&to_bytes(&dict)), "UDID should be a string");
+}
+
+#[test]
+fn not_a_pairing_file_at_all() {
+    assert!(reason(b"definitely not a plist").starts_with("not a property list"));
+
+    let mut bytes = Vec::new();
+    plist::to_writer_xml(&mut bytes, &Value::Array(Vec::new())).unwrap();
+    assert_eq!(reason(&bytes), "root is not a dictionary");
+}
+
+#[test]
+fn redacted_xml_masks_only_the_private_keys() {
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    let redacted: Dictionary =
+        plist::from_bytes(pairing::redacted_xml(&pairing_file).unwrap().as_bytes()).unwrap();
+
+    // Compared with the file as it would be written, which is what redaction changes
+    let original: Dictionary =
+        plist::from_bytes(&pairing_file.clone().serialize().unwrap()).unwrap();
+    assert_eq!(redacted.len(), original.len());
+    for (key, value) in &original {
+        if pairing::PRIVATE_KEYS.contains(&key.as_str()) {
+            assert_eq!(redacted[key].as_string(), Some(pairing::REDACTED), "{key}");
+        } else {
+            assert_eq!(&redacted[key], value, "{key}");
+        }
+    }
+}
+
+#[test]
+fn encode_writes_the_format_asked_for() {
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    for format in [pairing::Format::Xml, pairing::Format::Binary] {
+        let bytes = pairing::encode(&pairing_file, format, None).unwrap();
+        assert_eq!(bytes, pairing::serialize(&pairing_file, format).unwrap());
+        assert_eq!(
+            pairing::from_bytes(&bytes).unwrap().host_id,
+            pairing_file.host_id
+        );
+    }
+    assert!(
+        pairing::encode(&pairing_file, pairing::Format::Binary, None)
+            .unwrap()
+            .starts_with(b"bplist00")
+    );
+}
@@ -0,0 +1,81 @@
+// Jackson Coxson
+// Tests of parsing and picking the usbmuxd address
+
+use idevice_pair::{
+    Error,
+    device::{self, USBMUXD_ADDRESS_VAR},
+    idevice::usbmuxd::UsbmuxdAddr,
+};
+
+fn parsed(address: &str) -> UsbmuxdAddr {
+    device::parse_usbmuxd_addr(address).unwrap()
+}
+
+#[test]
+fn tcp_addresses() {
+    for address in ["127.0.0.1:27015", "[::1]:27015", "[fe80::1%2]:27015"] {
+        let addr = parsed(address);
+        assert!(matches!(addr, UsbmuxdAddr::TcpSocket(_)), "{address}");
+        assert_eq!(device::describe_usbmuxd_addr(&addr), address);
+    }
+    match parsed("[::1]:27015") {
+        UsbmuxdAddr::TcpSocket(addr) => {
+            assert!(addr.is_ipv6());
+            assert_eq!(addr.port(), 27015);
+        }
+        #[cfg(unix)]
+        other => panic!("expected a TCP address, got {other:?}"),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn socket_paths() {
+    for address in ["/var/run/usbmuxd", "relative/usbmuxd"] {
+        let addr = parsed(address);
+        assert!(matches!(addr, UsbmuxdAddr::UnixSocket(_)), "{address}");
+        assert_eq!(device::describe_usbmuxd_addr(&addr), address);
+    }
+}
+
+#[test]
+fn invalid_addresses() {
+    // Host names aren't looked up and IPv6 needs brackets to be told apart from the port
+    for address in [
+        "localhost:27015",
+        "127.0.0.1:",
+        "127.0.0.1:99999",
+        "::1",
+        "[::1]",
+    ] {
+        match device::parse_usbmuxd_addr(address) {
+            Err(Error::InvalidUsbmuxdAddress { address: a, .. }) => assert_eq!(a, address),
+            other => panic!("{address} should be invalid, got {other:?}"),
+        }
+    }
+}
+
+/// The only test that touches the environment, so nothing else reads it while it changes
+#[test]
+fn configured_address_wins_over_the_environment() {
+    let resolved = |configured| {
+        device::describe_usbmuxd_addr(&device::resolve_usbmuxd_addr(configured).unwrap())
+    };
+    let default = device::describe_usbmuxd_addr(&UsbmuxdAddr::default());
+
+    // SAFETY: no other test in this binary reads or writes the environment
+    unsafe { std::env::remove_var(USBMUXD_ADDRESS_VAR) };
+    assert_eq!(resolved(None), default);
+    assert_eq!(resolved(Some("10.0.0.2:27015")), "10.0.0.2:27015");
+
+    unsafe { std::env::set_var(USBMUXD_ADDRESS_VAR, "10.0.0.1:27015") };
+    assert_eq!(resolved(None), "10.0.0.1:27015");
+    assert_eq!(resolved(Some("10.0.0.2:27015")), "10.0.0.2:27015");
+    // A blank setting means no setting
+    assert_eq!(resolved(Some("  ")), "10.0.0.1:27015");
+    assert!(device::resolve_usbmuxd_addr(Some("not:valid")).is_err());
+
+    unsafe { std::env::set_var(USBMUXD_ADDRESS_VAR, "") };
+    assert_eq!(resolved(None), default);
+    unsafe { std::env::remove_var(USBMUXD_ADDRESS_VAR) };
+}
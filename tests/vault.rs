@@ -0,0 +1,138 @@
+// Jackson Coxson
+// Tests of the pairing file vault, kept in a temporary directory
+
+use std::{net::Ipv4Addr, path::PathBuf};
+
+use idevice::IdeviceError;
+use idevice_pair::{
+    Error, pairing,
+    vault::{Source, Vault},
+};
+
+const PAIRING_FILE: &[u8] = include_bytes!("fixtures/pairing_file.plist");
+const UDID: &str = "00008030-000A1B2C3D4E5F60";
+
+/// A vault directory that is removed when the test ends
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("idevice_pair-vault-{}", uuid::Uuid::new_v4())))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn add_and_read_back() {
+    let dir = TempDir::new();
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+
+    let mut vault = Vault::open_at(&dir.0).unwrap();
+    vault
+        .add(&pairing_file, Source::Loaded, Some("iPhone".to_string()))
+        .unwrap();
+
+    // Everything is on disk, not just in memory
+    let vault = Vault::open_at(&dir.0).unwrap();
+    let entry = vault.find(UDID).next().unwrap();
+    assert_eq!(entry.host_id, pairing_file.host_id);
+    assert_eq!(entry.device_name.as_deref(), Some("iPhone"));
+    assert_eq!(entry.source, Source::Loaded);
+    assert_eq!(vault.find(&pairing_file.host_id.to_lowercase()).count(), 1);
+
+    let stored = vault.pairing_file(&pairing_file.host_id).unwrap();
+    assert_eq!(stored.host_id, pairing_file.host_id);
+    assert_eq!(stored.host_private_key, pairing_file.host_private_key);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(vault.file_path(&pairing_file.host_id))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let mode = std::fs::metadata(vault.dir()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+}
+
+#[test]
+fn history_is_kept_when_added_again() {
+    let dir = TempDir::new();
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+    let host_id = pairing_file.host_id.clone();
+
+    let mut vault = Vault::open_at(&dir.0).unwrap();
+    vault.add(&pairing_file, Source::Generated, None).unwrap();
+    vault
+        .record_install(&host_id, "StikDebug", Some("com.stik.sj"))
+        .unwrap();
+    vault.record_install(&host_id, "SideStore", None).unwrap();
+    vault
+        .record_install(&host_id, "StikDebug", Some("com.stik.sj"))
+        .unwrap();
+    vault
+        .record_validation(
+            &host_id,
+            Some(Ipv4Addr::new(192, 168, 1, 20).into()),
+            &Err(Error::Idevice(IdeviceError::DeviceNotFound)),
+        )
+        .unwrap();
+    vault.add(&pairing_file, Source::Imported, None).unwrap();
+
+    let vault = Vault::open_at(&dir.0).unwrap();
+    assert_eq!(vault.entries().len(), 1);
+    let entry = vault.get(&host_id).unwrap();
+    // The first source is kept, it says where the pairing came from
+    assert_eq!(entry.source, Source::Generated);
+    let apps: Vec<&str> = entry.installs.iter().map(|x| x.app.as_str()).collect();
+    assert_eq!(apps, ["SideStore", "StikDebug"]);
+    let validation = entry.last_validation.as_ref().unwrap();
+    assert!(validation.error.is_some());
+}
+
+#[test]
+fn remove_deletes_the_file() {
+    let dir = TempDir::new();
+    let pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+
+    let mut vault = Vault::open_at(&dir.0).unwrap();
+    vault.add(&pairing_file, Source::Loaded, None).unwrap();
+    let path = vault.file_path(&pairing_file.host_id);
+    vault.remove(&pairing_file.host_id).unwrap();
+
+    assert!(!path.exists());
+    assert!(Vault::open_at(&dir.0).unwrap().entries().is_empty());
+    // Records for files that aren't in the vault are ignored
+    vault
+        .record_install(&pairing_file.host_id, "StikDebug", None)
+        .unwrap();
+    assert!(vault.entries().is_empty());
+}
+
+#[test]
+fn similar_host_ids_are_kept_apart() {
+    let dir = TempDir::new();
+    let mut vault = Vault::open_at(&dir.0).unwrap();
+    // Differ only in characters that can't go in a file name, or only in case
+    let host_ids = ["a.b", "a_b", "a/b", "A.B"];
+    for host_id in host_ids {
+        let mut pairing_file = pairing::from_bytes(PAIRING_FILE).unwrap();
+        pairing_file.host_id = host_id.to_string();
+        pairing_file.system_buid = format!("buid {host_id}");
+        vault.add(&pairing_file, Source::Loaded, None).unwrap();
+    }
+
+    for host_id in host_ids {
+        let path = vault.file_path(host_id);
+        assert_eq!(path.parent(), Some(vault.dir()), "{host_id}");
+        let stored = vault.pairing_file(host_id).unwrap();
+        assert_eq!(stored.system_buid, format!("buid {host_id}"));
+    }
+}